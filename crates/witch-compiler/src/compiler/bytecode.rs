@@ -0,0 +1,89 @@
+use std::ops::{Deref, DerefMut, Range};
+
+/// A chunk of emitted bytecode, along with debug information about where it came from.
+/// It dereferences to its bytes, so it can be built like any `Vec<u8>`. Chunks must be joined
+/// with `Bytecode::append` in order to keep their debug information, as offsets within the
+/// appended chunk get relocated.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Bytecode {
+    bytes: Vec<u8>,
+
+    /// Source spans of the AST nodes the bytecode was compiled from: (bytecode range, source span)
+    pub spans: Vec<(Range<usize>, Range<usize>)>,
+
+    /// Bodies of the functions within the chunk
+    pub functions: Vec<FunctionBody>,
+
+    /// Offsets of the jumps emitted by `break` and `continue`, which get patched once the loop
+    /// they jump out of has been compiled
+    pub breaks: Vec<usize>,
+    pub continues: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionBody {
+    pub range: Range<usize>,
+
+    /// The name the function was declared with, if any
+    pub name: Option<String>,
+    pub arity: usize,
+}
+
+impl Bytecode {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves all bytes and debug information of `other` to the end of this chunk, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        let offset = self.bytes.len();
+        self.spans.extend(
+            other
+                .spans
+                .drain(..)
+                .map(|(range, span)| (range.start + offset..range.end + offset, span)),
+        );
+        self.functions
+            .extend(other.functions.drain(..).map(|function| FunctionBody {
+                range: function.range.start + offset..function.range.end + offset,
+                ..function
+            }));
+        self.breaks
+            .extend(other.breaks.drain(..).map(|at| at + offset));
+        self.continues
+            .extend(other.continues.drain(..).map(|at| at + offset));
+        self.bytes.append(&mut other.bytes);
+    }
+
+    /// Marks the chunk as compiled from the given source span.
+    pub fn add_span(&mut self, span: Range<usize>) {
+        self.spans.push((0..self.bytes.len(), span));
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl From<Vec<u8>> for Bytecode {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            ..Default::default()
+        }
+    }
+}
+
+impl Deref for Bytecode {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.bytes
+    }
+}
+
+impl DerefMut for Bytecode {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.bytes
+    }
+}
@@ -1,9 +1,9 @@
-use super::{type_system::TypeSystem, LocalVariable};
+use super::{bytecode::Bytecode, type_system::TypeSystem, util::operand, LocalVariable};
 use crate::error::{Error, Result};
 use anyhow::anyhow;
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, ops::Range, path::PathBuf};
 use witch_parser::{types::Type, Ast};
-use witch_runtime::vm::Op;
+use witch_runtime::{value::Value, vm::Op};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Upvalue {
@@ -18,6 +18,14 @@ pub struct Scope {
     pub locals: Vec<LocalVariable>,
     pub generic_functions: Vec<(String, Ast)>,
     pub upvalues: Vec<Upvalue>,
+
+    /// Whether the last local is declared by a `let` whose value is still being evaluated. It only
+    /// takes up its slot on the stack once the first value of the evaluation lands in it.
+    pub pending_let: bool,
+
+    /// How many `match` expressions have declared locals in the scope, which tells whether an
+    /// expression depends on how many values are held on the stack while it is evaluated.
+    pub matches: usize,
 }
 
 impl Scope {
@@ -38,7 +46,7 @@ impl Scope {
 
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct Cached {
-    pub bytecode: Vec<u8>,
+    pub bytecode: Bytecode,
     pub flushed: bool,
 }
 
@@ -82,13 +90,24 @@ pub struct Context<'a> {
     /// If we are currently within a function declaration, this is it's type.
     pub current_function_type: Option<Type>,
 
+    /// Where the return type of that function is written, unless it is inferred.
+    pub current_returns_span: Option<Range<usize>>,
+
     /// If we're currently in an assignment, keep track of Ident and the assigned Type (may be Unknown)
     pub assignment_ctx: Option<(String, Type)>,
 
+    /// For each loop being compiled within the current function, innermost last, the number of
+    /// locals in scope when it started. Jumping out of a loop drops the locals declared since.
+    pub loops: Vec<usize>,
+
     /// Values get cached in order keep the subsequent programs smaller
     pub prelude: Option<Vec<u8>>,
     pub functions_cache: Vec<Cached>,
     pub value_cache: Vec<Cached>,
+
+    /// The constant pool of the program. It is shared by every module, so it gets handed
+    /// from one context to the next.
+    pub constants: Vec<Value>,
 }
 
 impl<'a> Context<'a> {
@@ -100,10 +119,13 @@ impl<'a> Context<'a> {
             scopes: vec![Scope::default()],
             lineage: Default::default(),
             current_function_type: None,
+            current_returns_span: None,
             assignment_ctx: None,
+            loops: vec![],
             prelude: None,
             functions_cache: Default::default(),
             value_cache: Default::default(),
+            constants: Default::default(),
         }
     }
 
@@ -133,7 +155,8 @@ impl<'a> Context<'a> {
         } else {
             0
         };
-        for (i, local) in self.scope().unwrap().locals.iter().enumerate() {
+        // Later locals shadow earlier ones of the same name
+        for (i, local) in self.scope().unwrap().locals.iter().enumerate().rev() {
             if local.name == *ident {
                 return Some(((offset + i), local.to_owned()));
             }
@@ -245,8 +268,8 @@ impl<'a> Context<'a> {
     /// Flushes the current context.
     /// Unflushed cached values get returned as a `prelude` bytecode and marked as such.
     /// Scopes and AST lineage of the context get reset.
-    pub fn flush(&mut self) -> Vec<u8> {
-        let mut bc = vec![];
+    pub fn flush(&mut self) -> Result<Bytecode> {
+        let mut bc = Bytecode::new();
 
         // Setup imported modules using the following strategy:
         // - Create a DAG of imports in order to deduce a working order
@@ -268,19 +291,29 @@ impl<'a> Context<'a> {
             }
         }
         bc.push(Op::SetupFunctionCache as u8);
-        let len: [u8; std::mem::size_of::<usize>()] = len.to_ne_bytes();
-        bc.append(&mut len.to_vec());
-        bc
+        bc.extend_from_slice(&operand(len)?);
+        Ok(bc)
+    }
+
+    /// Adds a value to the constant pool unless an equal one is already in it,
+    /// and returns its index.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        if let Some(idx) = self.constants.iter().position(|c| *c == value) {
+            idx
+        } else {
+            self.constants.push(value);
+            self.constants.len() - 1
+        }
     }
 
     /// Adds a fn bytecode to the cache, unless it has already been cached
     /// before. Whether the cached value has been flushed in a previous script
     /// has no bearing on the cached entry index.
-    pub fn cache_fn(&mut self, fn_bytecode: Vec<u8>) -> usize {
+    pub fn cache_fn(&mut self, fn_bytecode: Bytecode) -> usize {
         if let Some(idx) = self
             .functions_cache
             .iter()
-            .position(|cached| &cached.bytecode == &fn_bytecode)
+            .position(|cached| *cached.bytecode == *fn_bytecode)
         {
             idx
         } else {
@@ -292,8 +325,15 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Reserves an index within the functions cache, to be filled in later with `cache_fn_at`.
+    /// Unlike `cache_fn`, every call gets an index of its own.
+    pub fn reserve_fn(&mut self) -> usize {
+        self.functions_cache.push(Cached::default());
+        self.functions_cache.len() - 1
+    }
+
     /// Replaces the bytecode at index `idx` within the functions cache.
-    pub fn cache_fn_at(&mut self, idx: usize, fn_bytecode: Vec<u8>) {
+    pub fn cache_fn_at(&mut self, idx: usize, fn_bytecode: Bytecode) {
         self.functions_cache[idx] = Cached {
             bytecode: fn_bytecode,
             flushed: false,
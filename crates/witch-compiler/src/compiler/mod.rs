@@ -1,20 +1,24 @@
 //! The compiler module takes an AST representation of our program and
 //! emits bytecode from it.
+pub mod bytecode;
 pub mod context;
-mod type_system;
+pub mod type_system;
 mod util;
 use std::collections::HashMap;
 use std::ops::Range;
 use std::path::PathBuf;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 
+use bytecode::{Bytecode, FunctionBody};
 use context::{Context, Scope};
-use witch_parser::ast::{Ast, Key, Operator};
+use witch_parser::ast::{Ast, Key, MatchArm, Operator, Pattern};
 use witch_parser::types::{Type, TypeDecl};
 
+use witch_runtime::image::OPERAND_SIZE;
+use witch_runtime::numeric::Numeric;
 use witch_runtime::value::{Function, Value};
-use witch_runtime::vm::Op;
+use witch_runtime::vm::{InfixOp, Op, UnaryOp};
 
 /// Contains all compile-time information about a locally scoped
 /// variable.
@@ -26,11 +30,51 @@ pub struct LocalVariable {
 }
 
 /// Turns an AST into bytecode
-pub fn compile<'a>(ctx: &mut Context, ast: &Ast) -> Result<(Vec<u8>, Type)> {
+pub fn compile<'a>(ctx: &mut Context, ast: &Ast) -> Result<(Bytecode, Type)> {
     ctx.lineage.push(ast.clone());
+    let result = compile_ast(ctx, ast);
+    ctx.lineage.pop();
+
+    // Errors raised without a span point at the innermost AST node being compiled
+    result.map_err(|err| match err.downcast::<Error>() {
+        Ok(err) if !err.has_span() => err.with_span(ast.span()).into(),
+        Ok(err) => err.into(),
+        Err(err) => err,
+    })
+}
+
+/// Compiles `ast` while `held` values, such as the left hand side of a binary operation, are on
+/// top of the stack without being locals. They take up local slots in the meantime, so that the
+/// locals of a `match` within `ast` line up with the stack.
+fn compile_held(ctx: &mut Context, ast: &Ast, held: usize) -> Result<(Bytecode, Type)> {
+    let scope = ctx.scope()?;
+    let pending_let = scope.pending_let;
+    let held = if pending_let && held > 0 {
+        scope.pending_let = false;
+        held - 1
+    } else {
+        held
+    };
+    let locals = scope.locals.len();
+    scope.locals.extend((0..held).map(|_| LocalVariable {
+        name: String::new(),
+        is_captured: false,
+        r#type: Type::Unknown,
+    }));
+
+    let result = compile(ctx, ast);
+
+    let scope = ctx.scope()?;
+    scope.locals.truncate(locals);
+    scope.pending_let = pending_let;
+    result
+}
 
-    let (bytecode, return_type) = match &ast {
+fn compile_ast(ctx: &mut Context, ast: &Ast) -> Result<(Bytecode, Type)> {
+    let (mut bytecode, return_type) = match &ast {
         Ast::Assignment { lhs, rhs, span } => assignment(ctx, lhs, rhs, span)?,
+        Ast::Block { expr, span: _ } => (block(ctx, expr)?, Type::Void),
+        Ast::Break { span: _ } => break_(ctx, false)?,
         Ast::Call {
             expr,
             args,
@@ -40,17 +84,31 @@ pub fn compile<'a>(ctx: &mut Context, ast: &Ast) -> Result<(Vec<u8>, Type)> {
             is_variadic,
             args,
             returns,
+            returns_span,
             body,
             generics,
-        } => function(ctx, is_variadic, args, returns, body, generics)?,
+        } => function(
+            ctx,
+            is_variadic,
+            args,
+            returns,
+            returns_span,
+            body,
+            generics,
+        )?,
         Ast::If {
             predicate,
             then_,
             else_,
             span: _,
         } => if_(ctx, predicate, then_, else_)?,
+        Ast::Continue { span: _ } => break_(ctx, true)?,
+        Ast::Match { expr, arms, span } => match_(ctx, expr, arms, span)?,
         Ast::Import { path, span } => import(ctx, path, span)?,
         Ast::Infix { lhs, op, rhs, .. } => infix(ctx, lhs, op, rhs)?,
+        Ast::Prefix { op, rhs, span } => prefix(ctx, op, rhs, span)?,
+        Ast::Cast { expr, to, span } => cast(ctx, expr, to, span)?,
+        Ast::Try { expr, span } => try_(ctx, expr, span)?,
         Ast::Let {
             ident,
             annotated_type,
@@ -58,6 +116,8 @@ pub fn compile<'a>(ctx: &mut Context, ast: &Ast) -> Result<(Vec<u8>, Type)> {
             span,
         } => let_(ctx, ident, annotated_type, expr, span)?,
         Ast::List { items, span } => list(ctx, items, span)?,
+        Ast::Map { entries, span } => map(ctx, entries, span)?,
+        Ast::Interpolation { segments, span } => interpolation(ctx, segments, span)?,
         Ast::Member {
             container,
             key,
@@ -72,11 +132,28 @@ pub fn compile<'a>(ctx: &mut Context, ast: &Ast) -> Result<(Vec<u8>, Type)> {
         } => struct_literal(ctx, ident, fields, span)?,
         Ast::Type { name, decl, span } => decl_type(ctx, name, decl, span)?,
         Ast::Value(v) => value(ctx, v)?,
-        Ast::Var(ident) => var(ctx, ident)?,
-        Ast::Nop => (vec![], Type::Void),
-        x => todo!("{:?}", x),
+        Ast::Var { ident, .. } => var(ctx, ident)?,
+        Ast::While {
+            predicate,
+            expr,
+            span: _,
+        } => while_(ctx, predicate, expr)?,
+        Ast::Nop => (Bytecode::new(), Type::Void),
+        Ast::Error { .. } => {
+            return Err(Error::new("syntax_error", "statement failed to parse")
+                .with_label("syntax error")
+                .into())
+        }
+        x => {
+            return Err(Error::unsupported(format!("{:?} expressions are not supported", x)).into())
+        }
     };
 
+    // Statements only group their children, which keep track of their own spans
+    if let (Some(span), false) = (ast.span(), matches!(ast, Ast::Statement { .. })) {
+        bytecode.add_span(span);
+    }
+
     // For each compilation step, we try to resolve the return type to be as concrete as possible
     Ok((bytecode, ctx.ts.resolve(return_type)?))
 }
@@ -88,18 +165,63 @@ fn assignment(
     lhs: &Ast,
     rhs: &Ast,
     _span: &Range<usize>,
-) -> Result<(Vec<u8>, Type)> {
+) -> Result<(Bytecode, Type)> {
+    // Assigning to a key of a map inserts into it, e.g. m["a"] = 1, and assigning to an index of a
+    // list which is not known up front sets the item at it, e.g. items[i] = 1
+    if let Ast::Member {
+        container,
+        key: key @ (Key::Usize(_) | Key::Expression(_)),
+        span,
+    } = lhs
+    {
+        let method = match (compile(ctx, container)?.1, key) {
+            (Type::Map(..), _) => Some("insert"),
+            (Type::List(_), Key::Expression(_)) => Some("set"),
+            _ => None,
+        };
+        if let Some(method) = method {
+            let key = match key {
+                Key::Usize(idx) => Ast::Value(Value::Usize(*idx)),
+                Key::Expression(expr) => *expr.clone(),
+                Key::String(_) => return Err(Error::fatal().into()),
+            };
+            let call = Ast::Call {
+                expr: Box::new(Ast::Member {
+                    container: container.clone(),
+                    key: Key::String(method.to_string()),
+                    span: span.clone(),
+                }),
+                args: vec![key, rhs.clone()],
+                span: span.clone(),
+            };
+            let (mut bytecode, _) = compile(ctx, &call)?;
+            bytecode.push(Op::Pop as u8);
+            return Ok((bytecode, Type::Void));
+        }
+    }
+
     let mut member_key = None;
     let ident = match lhs.clone() {
-        Ast::Var(ident) => ident,
+        Ast::Var { ident, .. } => ident,
         Ast::Member { container, key, .. } => match *container {
-            Ast::Var(ident) => {
+            Ast::Var { ident, .. } => {
                 member_key = Some(key);
                 ident
             }
-            _ => unimplemented!(),
+            _ => {
+                return Err(
+                    Error::unsupported("only members of variables can be assigned to").into(),
+                )
+            }
         },
-        _ => unimplemented!(),
+        _ => {
+            return Err(
+                Error::new("invalid_assignment", "invalid left-hand side of assignment")
+                    .with_label("cannot be assigned to")
+                    .with_help("only variables and their members can be assigned to")
+                    .into(),
+            )
+        }
     };
 
     let (mut expr_bytes, expr_type) = compile(ctx, rhs)?;
@@ -111,10 +233,13 @@ fn assignment(
             match (member_key, &var_type) {
                 (None, _) => {
                     if var_type != Type::Unknown && var_type != expr_type {
-                        panic!(
-                            "cant coerce between types!!! var ({:?}): {:?}, expr_type: {:?}",
-                            local.name, var_type, &expr_type
-                        );
+                        return Err(Error::type_mismatch(var_type, expr_type)
+                            .with_span(rhs.span())
+                            .with_help(format!(
+                                "variable `{}` was declared with a different type",
+                                local.name
+                            ))
+                            .into());
                     }
                     expr_bytes.push(Op::Set as u8);
                     expr_bytes.push(local_variable);
@@ -122,24 +247,43 @@ fn assignment(
                 }
 
                 (Some(Key::String(key)), Type::Struct { fields, .. }) => {
-                    let idx = fields.iter().position(|f| f.0 == key).unwrap();
+                    let Some(idx) = fields.iter().position(|f| f.0 == key) else {
+                        return Err(Error::new(
+                            "unknown_field",
+                            format!("no field `{}` on struct", key),
+                        )
+                        .with_label("unknown field")
+                        .into());
+                    };
                     expr_bytes.push(Op::SetProperty as u8);
                     expr_bytes.push(local_variable);
                     expr_bytes.push(idx as u8);
                     return Ok((expr_bytes, expr_type));
                 }
 
-                (Some(Key::Usize(idx)), Type::List(_)) => {
+                (Some(Key::Usize(idx)), Type::List(item_type)) => {
+                    if **item_type != expr_type {
+                        return Err(Error::type_mismatch(item_type, expr_type)
+                            .with_span(rhs.span())
+                            .with_help(format!("`{}` is a list of a different type", local.name))
+                            .into());
+                    }
                     expr_bytes.push(Op::SetProperty as u8);
                     expr_bytes.push(local_variable);
                     expr_bytes.push(idx as u8);
                     return Ok((expr_bytes, expr_type));
                 }
 
-                _ => unreachable!(),
+                _ => {
+                    return Err(Error::unsupported(format!(
+                        "assigning to members of `{}` is not supported",
+                        var_type
+                    ))
+                    .into())
+                }
             }
         }
-        unreachable!()
+        Err(Error::unsupported("too many local variables in scope").into())
     } else if false
     //let Some((upvalue, ty)) = resolve_upvalue(&mut program.compiler_stack, compiler, ident)
     {
@@ -155,7 +299,13 @@ fn assignment(
         // bytecode.push(upvalue);
         // return_type = expr_type;
     } else {
-        panic!("Attempted to reassign unknown variable {:?}", ident)
+        Err(Error::new(
+            "unknown_variable",
+            format!("cannot find variable `{}`", ident),
+        )
+        .with_label("not found in this scope")
+        .with_help("variables need to be declared with `let` before being assigned to")
+        .into())
     }
 }
 
@@ -163,19 +313,84 @@ fn assignment(
 /// a function and hopefully it'll evaluate to something callable.
 /// While the emitted bytecode is simple, some (a lot) more effort is required for the type checking
 /// during compilation.
-fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>, Type)> {
-    let mut bytecode = vec![];
+fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Bytecode, Type)> {
+    // Calling a member of an enum constructs a variant with a payload
+    if let Ast::Member { container, key, .. } = &**expr
+        && let Some(enum_) = enum_type(ctx, container)
+    {
+        return variant(ctx, enum_, key, args);
+    }
+
+    // The value a method is called on gets compiled once, after which its type tells how it is
+    // passed to the method
+    let mut receiver = None;
+    let mut callee = None;
+    if let Ast::Member {
+        container,
+        key: key @ Key::String(name),
+        ..
+    } = &**expr
+    {
+        let matches = ctx.scope()?.matches;
+        let (self_bc, self_ty) = compile_held(ctx, container, 1)?;
+        let self_ty = ctx.ts.resolve(self_ty)?;
+        if self_ty.builtin_methods().contains_key(name) {
+            callee = Some(builtin_method(ctx, &self_ty, name)?);
+            receiver = Some(self_bc);
+        } else if let Some(function) = prelude_list_method(ctx, &self_ty, name) {
+            // Lists have methods written in Witch within the prelude, which take the list as
+            // their first argument: items.map(f) calls list_map(items, f)
+            let args: Vec<Ast> = std::iter::once(*container.clone())
+                .chain(args.iter().cloned())
+                .collect();
+            let function = Ast::Var {
+                ident: function,
+                span: expr.span().unwrap_or_default(),
+            };
+            return call_with(ctx, &function, &args, Some((self_bc, self_ty)), None, None);
+        } else if ctx.scope()?.matches == matches {
+            // The locals of a `match` would have to line up with where the value ends up on the
+            // stack, otherwise it can be reused there
+            callee = Some(member_of(ctx, container, self_bc, self_ty, key)?);
+        }
+    }
+    call_with(ctx, expr, args, None, receiver, callee)
+}
+
+/// Compiles a call of `expr` with `args`, of which the first may already be compiled. Methods
+/// get the value they are called on as `receiver` if they are builtins, and along with the
+/// `callee` they are found as otherwise.
+fn call_with(
+    ctx: &mut Context,
+    expr: &Ast,
+    args: &[Ast],
+    first_arg: Option<(Bytecode, Type)>,
+    receiver: Option<Bytecode>,
+    callee: Option<(Bytecode, Type)>,
+) -> Result<(Bytecode, Type)> {
+    let mut bytecode = Bytecode::new();
     let mut arity = args.len();
 
-    let mut args_bytecode = vec![];
+    // The return address is on the stack before the arguments, along with the value builtin
+    // methods are called on
+    let held = 1 + usize::from(receiver.is_some());
+
+    let mut first_arg = first_arg;
+    let mut args_bytecode = Bytecode::new();
     let mut args_with_types = vec![];
-    for arg in args.iter() {
-        let (mut bc, arg_type) = compile(ctx, arg)?;
+    for (i, arg) in args.iter().enumerate() {
+        let (mut bc, arg_type) = match first_arg.take() {
+            Some(compiled) => compiled,
+            None => compile_held(ctx, arg, held + i)?,
+        };
         args_bytecode.append(&mut bc);
         args_with_types.push((arg.clone(), arg_type));
     }
 
-    let (mut bc, mut called_type) = compile(ctx, expr)?;
+    let (mut bc, mut called_type) = match callee {
+        Some(callee) => callee,
+        None => compile_held(ctx, expr, held + args.len())?,
+    };
 
     // If we're calling a function stub, it needs to undergo monomorphization
     if let Type::GenericFunctionStub { scope, idx } = called_type {
@@ -184,8 +399,8 @@ fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>,
             ref is_variadic,
             ref args,
             ref returns,
-            ref body,
             ref mut generics,
+            ..
         } = function_ast
         {
             ctx.push_type_scope(&generics);
@@ -196,23 +411,32 @@ fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>,
                 // if ctx.ts.resolve(arg_typ.clone())? != ctx.ts.resolve(args[arg_idx].1.clone())? {
 
                 // }
-                if let Type::TypeVar(ident) = args[caller_arg_idx].1.clone() {
-                    // If the arg is a typevar, check the function generics for it
-
-                    // If theres a match, replace with our caller type (or error if wrong type)
-                    if let Some((i, (n, t))) =
-                        generics.iter_mut().enumerate().find(|(_, g)| g.0 == ident)
-                    {
-                        // Caller type matches the generic constraint
-                        if caller_arg_typ == &ctx.ts.resolve(t.to_owned())? {
-                            generics[i] = (n.to_owned(), caller_arg_typ.clone());
-                        } else {
-                            panic!(
-                                "type error: generic function arg {} expected type {:?}, got {:?}",
-                                args[caller_arg_idx].0,
-                                ctx.ts.resolve(args[caller_arg_idx].1.clone())?,
-                                caller_arg_typ
-                            );
+                for (arg_typ, caller_arg_typ) in
+                    generic_args(&args[caller_arg_idx].1, caller_arg_typ)
+                {
+                    let caller_arg_typ = &caller_arg_typ;
+                    if let Type::TypeVar(ident) = arg_typ {
+                        // If the arg is a typevar, check the function generics for it
+
+                        // If theres a match, replace with our caller type (or error if wrong type)
+                        if let Some((i, (n, t))) =
+                            generics.iter_mut().enumerate().find(|(_, g)| g.0 == ident)
+                        {
+                            // Caller type matches the generic constraint
+                            if caller_arg_typ == &ctx.ts.resolve(t.to_owned())? {
+                                generics[i] = (n.to_owned(), caller_arg_typ.clone());
+                            } else {
+                                return Err(Error::type_mismatch(
+                                    ctx.ts.resolve(args[caller_arg_idx].1.clone())?,
+                                    caller_arg_typ,
+                                )
+                                .with_span(args_with_types[caller_arg_idx].0.span())
+                                .with_help(format!(
+                                    "the generic argument `{}` is constrained to a different type",
+                                    args[caller_arg_idx].0
+                                ))
+                                .into());
+                            }
                         }
                     }
                 }
@@ -226,7 +450,7 @@ fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>,
             // Cache it
             let impl_idx = ctx.cache_fn(impl_bytecode);
             // Emit a getfunction OP to put it on the stack
-            bc = vec![];
+            bc = Bytecode::new();
             bc.push(Op::GetFunction as u8);
             bc.push((ctx.vtable_offset() + impl_idx) as u8);
 
@@ -236,17 +460,12 @@ fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>,
         }
     }
 
-    // If this is being called on a Member expression, that means a method call.
-    // Method call means we have an implicit `self` variable as a first argument.
-    // Let's stick the Entry object on the stack before the arguments then.
-    if let Ast::Member { container, .. } = *expr.clone() {
-        if let Type::Function { is_method, .. } = &called_type {
-            if *is_method {
-                let (mut self_bc, ty) = compile(ctx, &container)?;
-                bytecode.append(&mut self_bc);
-                arity += 1;
-            }
-        }
+    // Method call means we have an implicit `self` variable. Methods of structs and enums find
+    // it after their arguments, where `member` leaves it, but builtins take it as their first
+    // argument, so let's stick the Entry object on the stack before the arguments for those.
+    if let Some(mut self_bc) = receiver {
+        bytecode.append(&mut self_bc);
+        arity += 1;
     }
 
     bytecode.append(&mut args_bytecode);
@@ -258,9 +477,9 @@ fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>,
     // If the LET expression is annotated, the Expr::Var evaluation will return the correct type as that information
     // is tracked during initialization. If not, the type information for our current function declaration is tracked
     // within the current context object.
-    if let (Type::Unknown, Ast::Var(ident), Some((assign_ident, _)), Some(function_type)) = (
+    if let (Type::Unknown, Ast::Var { ident, .. }, Some((assign_ident, _)), Some(function_type)) = (
         called_type.clone(),
-        &*(expr.clone()),
+        expr,
         &ctx.assignment_ctx,
         ctx.current_function_type.clone(),
     ) {
@@ -278,30 +497,53 @@ fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>,
             generics,
             is_method,
         } => {
+            // Generics which the arguments are declared with take on the types they are called with
+            let generics = generics
+                .into_iter()
+                .map(|(name, ty)| {
+                    let bound = arg_types
+                        .iter()
+                        .zip(args_with_types.iter())
+                        .flat_map(|(wanted, (_, supplied))| generic_args(wanted, supplied))
+                        .find_map(|(wanted, supplied)| match wanted {
+                            Type::TypeVar(ident)
+                                if ident == name
+                                    && !matches!(supplied, Type::Any | Type::Unknown) =>
+                            {
+                                Some(supplied)
+                            }
+                            _ => None,
+                        });
+                    (name, bound.unwrap_or(ty))
+                })
+                .collect();
             ctx.push_type_scope(&generics);
 
             // Compare arguments length against the type.
             // If the type is not variadic, they len's should be the same.
             if args.len() != arg_types.len() {
                 if !is_variadic {
-                    panic!(
-                        "wrong amount of arguments. want: {:?}, got: {:?}",
-                        &arg_types.len(),
-                        &args.len()
-                    );
+                    return Err(Error::new(
+                        "wrong_arity",
+                        format!(
+                            "function takes {} arguments but {} were supplied",
+                            arg_types.len(),
+                            args.len()
+                        ),
+                    )
+                    .with_label(format!("expected {} arguments", arg_types.len()))
+                    .into());
                 // If it is, we ensure that all arguments after the last arg type are the same
                 } else {
                     for (idx, (_, ty)) in args_with_types[(arg_types.len() - 1)..args.len() - 1]
                         .iter()
                         .enumerate()
                     {
-                        if arg_types.last().unwrap() != ty {
-                            panic!(
-                                "wrong type in arg nr {}! wants {:?}, got {:?}",
-                                idx,
-                                arg_types.last(),
-                                ty
-                            )
+                        if arg_types.last() != Some(ty) {
+                            return Err(Error::type_mismatch(&arg_types[arg_types.len() - 1], ty)
+                                .with_span(args[arg_types.len() - 1 + idx].span())
+                                .with_help("all variadic arguments must be of the same type")
+                                .into());
                         }
                     }
                 }
@@ -314,10 +556,9 @@ fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>,
                 let resolved_wanted_type = ctx.ts.resolve(wanted_type.clone())?;
 
                 if resolved_wanted_type != supplied_type {
-                    panic!(
-                        "type error in function call, wanted: {:?}, got: {:?}",
-                        wanted_type, supplied_type
-                    );
+                    return Err(Error::type_mismatch(resolved_wanted_type, supplied_type)
+                        .with_span(args[idx].span())
+                        .into());
                 }
             }
 
@@ -327,21 +568,103 @@ fn call(ctx: &mut Context, expr: &Box<Ast>, args: &Vec<Ast>) -> Result<(Vec<u8>,
             let return_type = ctx.ts.resolve(*returns)?;
             ctx.pop_type_scope();
 
-            // +9 to account for the length itself (8 bytes) and advance the IP to the byte after
-            let length: [u8; std::mem::size_of::<usize>()] = (bytecode.len() + 9).to_ne_bytes();
-            Ok((
-                vec![Op::SetReturn as u8]
-                    .into_iter()
-                    .chain(length)
-                    .chain(bytecode)
-                    .collect(),
-                return_type.clone(),
-            ))
+            // Account for the SetReturn instruction itself and advance the IP to the byte after
+            let length = util::operand(bytecode.len() + 1 + OPERAND_SIZE)?;
+            let mut call_bytecode = Bytecode::from(vec![Op::SetReturn as u8]);
+            call_bytecode.extend_from_slice(&length);
+            call_bytecode.append(&mut bytecode);
+            Ok((call_bytecode, return_type.clone()))
         }
-        _ => {
-            dbg!(called_type);
-            panic!("attempted to call a non function???");
+        _ => Err(Error::new(
+            "not_callable",
+            format!("expected a function, found `{}`", called_type),
+        )
+        .with_span(expr.span())
+        .with_label("not a function")
+        .into()),
+    }
+}
+
+/// Finds the prelude function implementing a method of a list which is not a builtin, if `ty` is
+/// a list and there is one.
+fn prelude_list_method(ctx: &mut Context, ty: &Type, name: &str) -> Option<String> {
+    if !matches!(ty, Type::List(_)) || ty.builtin_methods().contains_key(name) {
+        return None;
+    }
+    let function = format!("list_{}", name);
+    let prelude = ctx.get_module(&PathBuf::from("<prelude>"))?;
+    prelude
+        .locals
+        .iter()
+        .any(|local| local.name == function)
+        .then_some(function)
+}
+
+/// Pairs the type variables a generic function argument is declared with up with the types it is
+/// called with, looking through lists, enums and functions: an argument `List[T]` called with a
+/// `List[usize]` binds `T` to `usize`.
+fn generic_args(arg: &Type, caller: &Type) -> Vec<(Type, Type)> {
+    match (arg, caller) {
+        (Type::WithSubstitutions(ty, subs), Type::List(item))
+            if **ty == Type::TypeVar("List".to_string()) && subs.len() == 1 =>
+        {
+            generic_args(&subs[0], item)
+        }
+        (Type::List(arg), Type::List(item)) => generic_args(arg, item),
+        (Type::WithSubstitutions(ty, subs), Type::Map(key, value))
+            if matches!(&**ty, Type::TypeVar(name) if name == "Map") && subs.len() == 2 =>
+        {
+            let mut args = generic_args(&subs[0], key);
+            args.extend(generic_args(&subs[1], value));
+            args
+        }
+        (Type::Map(k1, v1), Type::Map(k2, v2)) => {
+            let mut args = generic_args(k1, k2);
+            args.extend(generic_args(v1, v2));
+            args
+        }
+        (Type::WithSubstitutions(ty, subs), Type::Enum { name, generics, .. })
+            if **ty == Type::TypeVar(name.clone()) && subs.len() == generics.len() =>
+        {
+            subs.iter()
+                .zip(generics)
+                .flat_map(|(arg, (_, caller))| generic_args(arg, caller))
+                .collect()
         }
+        (
+            Type::Enum {
+                name: n1,
+                generics: g1,
+                ..
+            },
+            Type::Enum {
+                name: n2,
+                generics: g2,
+                ..
+            },
+        ) if n1 == n2 => g1
+            .iter()
+            .zip(g2)
+            .flat_map(|((_, arg), (_, caller))| generic_args(arg, caller))
+            .collect(),
+        (
+            Type::Function {
+                args: a1,
+                returns: r1,
+                ..
+            },
+            Type::Function {
+                args: a2,
+                returns: r2,
+                ..
+            },
+        ) if a1.len() == a2.len() => a1
+            .iter()
+            .zip(a2)
+            .chain(std::iter::once((&**r1, &**r2)))
+            .flat_map(|(arg, caller)| generic_args(arg, caller))
+            .collect(),
+        _ => vec![(arg.clone(), caller.clone())],
     }
 }
 
@@ -351,18 +674,17 @@ fn function(
     is_variadic: &bool,
     args: &Vec<(String, Type)>,
     returns: &Type,
+    returns_span: &Option<Range<usize>>,
     body: &Box<Ast>,
     generics: &Vec<(String, Type)>,
-) -> Result<(Vec<u8>, Type)> {
-    let is_method = if let Ast::Type {
-        decl: TypeDecl::Struct { .. },
-        ..
-    } = &ctx.lineage[ctx.lineage.len() - 2]
-    {
-        true
-    } else {
-        false
-    };
+) -> Result<(Bytecode, Type)> {
+    let is_method = matches!(
+        &ctx.lineage[ctx.lineage.len() - 2],
+        Ast::Type {
+            decl: TypeDecl::Struct { .. } | TypeDecl::Enum { .. },
+            ..
+        }
+    );
 
     ctx.push_type_scope(generics);
 
@@ -376,12 +698,13 @@ fn function(
                 is_variadic: *is_variadic,
                 args: args.to_vec(),
                 returns: returns.clone(),
+                returns_span: returns_span.clone(),
                 body: body.clone(),
                 generics: generics.to_vec(),
             },
         ));
         return Ok((
-            vec![],
+            Bytecode::new(),
             Type::GenericFunctionStub {
                 scope: ctx.scopes.len() - 1,
                 idx: ctx.scope()?.generic_functions.len() - 1,
@@ -401,36 +724,46 @@ fn function(
 
     let current_function_type_copy = ctx.current_function_type.clone();
     ctx.current_function_type = Some(ty.clone());
+    let current_returns_span_copy = ctx.current_returns_span.clone();
+    ctx.current_returns_span = returns_span.clone();
 
     let mut scope = Scope::default();
 
-    // If the parent expression is a struct declaration, that means this is a method and so should have
-    // an implicit `self` variable injected
-    if let Ast::Type {
-        name,
-        decl: TypeDecl::Struct { .. },
-        ..
-    } = &ctx.lineage[ctx.lineage.len() - 2]
-    {
-        arity += 2;
+    // If the parent expression is a struct or enum declaration, that means this is a method and so
+    // should have an implicit `self` variable injected
+    let self_type = match &ctx.lineage[ctx.lineage.len() - 2] {
+        Ast::Type {
+            name,
+            decl: TypeDecl::Struct { .. } | TypeDecl::Enum { .. },
+            ..
+        } => Some(Type::TypeVar(name.clone())),
+        _ => None,
+    };
 
+    for (arg_name, arg_type) in args.iter() {
         scope.locals.push(LocalVariable {
-            name: "self".to_string(),
+            name: arg_name.clone(),
             is_captured: false,
-            r#type: Type::TypeVar(name.clone()),
+            r#type: arg_type.clone(),
         })
     }
 
-    for (arg_name, arg_type) in args.iter() {
+    // Method calls leave the object they are called on above the arguments, see `member`
+    if let Some(self_type) = self_type {
+        arity += 1;
         scope.locals.push(LocalVariable {
-            name: arg_name.clone(),
+            name: "self".to_string(),
             is_captured: false,
-            r#type: arg_type.clone(),
+            r#type: self_type,
         })
     }
     ctx.scopes.push(scope);
 
-    let (func_bytecode, _actually_returns) = compile(ctx, body)?;
+    // Loops outside of the function can not be broken out of from within it
+    let loops = std::mem::take(&mut ctx.loops);
+    let compiled = compile(ctx, body);
+    ctx.loops = loops;
+    let (mut func_bytecode, _actually_returns) = compiled?;
 
     let mut upvalues_bytecode = vec![];
     // If the parent scope is the root scope (i.e. we have a scope len of 2), take the previous modules stacks into account for the
@@ -455,6 +788,7 @@ fn function(
     // if the provided return type is more loose than the actually_returns type, overwrite it
 
     ctx.current_function_type = current_function_type_copy;
+    ctx.current_returns_span = current_returns_span_copy;
 
     let function = Value::Function(Function {
         is_variadic: *is_variadic,
@@ -464,51 +798,598 @@ fn function(
 
     let (mut function_bytecode, _) = compile(ctx, &Ast::Value(function))?;
 
-    let length: [u8; std::mem::size_of::<usize>()] = func_bytecode.len().to_ne_bytes();
-    function_bytecode.append(&mut length.to_vec());
-    function_bytecode.append(&mut func_bytecode.to_vec());
+    function_bytecode.extend_from_slice(&util::operand(func_bytecode.len())?);
+    let start = function_bytecode.len();
+    function_bytecode.functions.push(FunctionBody {
+        range: start..start + func_bytecode.len(),
+        name: function_name(ctx),
+        arity,
+    });
+    function_bytecode.append(&mut func_bytecode);
 
     Ok((function_bytecode, ty))
 }
 
+/// Deduces the name of the function currently being compiled from its parent expression.
+/// For methods this is the name of the struct or enum, which `decl_type` then qualifies with the method name.
+fn function_name(ctx: &Context) -> Option<String> {
+    match &ctx.lineage[..ctx.lineage.len() - 1].last()? {
+        Ast::Let { ident, .. } => Some(ident.clone()),
+        Ast::Assignment { lhs, .. } => match &**lhs {
+            Ast::Var { ident, .. } => Some(ident.clone()),
+            _ => None,
+        },
+        Ast::Type {
+            name,
+            decl: TypeDecl::Struct { .. } | TypeDecl::Enum { .. },
+            ..
+        } => Some(name.clone()),
+        _ => None,
+    }
+}
+
 /// If is implemented by utilizing the Jump and JumpIfFalse opcodes. We evaluate the two expressions and
 /// get the length of their bytecode instructions. If the Predicate expression is false, we jump over the
 /// Then expression length straight to the Else statement. If the Predicate is true, we fall through to the
 /// Then expression and subsequently Jump over the Else expression.
+/// Both branches are blocks, so whichever runs leaves the stack as it was.
 fn if_(
     ctx: &mut Context,
     predicate: &Box<Ast>,
     then_: &Box<Ast>,
     else_: &Box<Ast>,
-) -> Result<(Vec<u8>, Type)> {
+) -> Result<(Bytecode, Type)> {
     let (mut predicate_bytecode, predicate_ty) = compile(ctx, predicate)?;
     if !matches!(predicate_ty, Type::Bool) {
-        panic!("predicate expression must return a boolean value");
+        return Err(Error::type_mismatch(Type::Bool, predicate_ty)
+            .with_span(predicate.span())
+            .with_help("the condition of an `if` expression must be a boolean")
+            .into());
     }
-    let (mut then_bytecode, _then_ty) = compile(ctx, then_)?;
-    let (mut else_bytecode, _else_ty) = compile(ctx, else_)?;
+    let mut then_bytecode = block(ctx, then_)?;
+    let mut else_bytecode = block(ctx, else_)?;
 
-    let mut bytecode = vec![];
+    let mut bytecode = Bytecode::new();
 
     bytecode.append(&mut predicate_bytecode);
     bytecode.push(Op::JumpIfFalse as u8);
 
-    // Jump the size of the Then statement + this len value + one more instruction
-    let mut then_len = (then_bytecode.len() + 8 + 1).to_ne_bytes().to_vec();
-    bytecode.append(&mut then_len);
+    // Jump the size of the Then statement + the Jump instruction which follows it
+    let then_len = util::operand(then_bytecode.len() + 1 + OPERAND_SIZE)?;
+    bytecode.extend_from_slice(&then_len);
 
     bytecode.append(&mut then_bytecode);
     bytecode.push(Op::Jump as u8);
-    let mut else_len = (else_bytecode.len() + 8).to_ne_bytes().to_vec();
+    let else_len = util::operand(else_bytecode.len() + OPERAND_SIZE)?;
 
-    bytecode.append(&mut else_len);
+    bytecode.extend_from_slice(&else_len);
     bytecode.append(&mut else_bytecode);
 
     Ok((bytecode, Type::Void))
 }
 
-fn import(ctx: &mut Context, path: &PathBuf, _span: &Range<usize>) -> Result<(Vec<u8>, Type)> {
-    panic!("DEPRECATED??");
+/// Compiles statements which run in a scope of their own, such as the body of a loop or the branches
+/// of an `if`. The stack is left the way it was found: the value of the last statement is popped,
+/// along with the locals declared within the block.
+fn block(ctx: &mut Context, body: &Ast) -> Result<Bytecode> {
+    let locals = ctx.scope()?.locals.len();
+    let (mut bytecode, _) = compile(ctx, body)?;
+    if leaves_value(last_statement(body)) {
+        bytecode.push(Op::Pop as u8);
+    }
+    bytecode.append(&mut drop_locals(ctx, locals)?);
+    ctx.scope()?.locals.truncate(locals);
+    Ok(bytecode)
+}
+
+/// Pops the locals declared since the scope held `len` of them off the stack.
+fn drop_locals(ctx: &mut Context, len: usize) -> Result<Bytecode> {
+    let dropped = &ctx.scope()?.locals[len..];
+    if dropped.iter().any(|local| local.is_captured) {
+        return Err(Error::unsupported(
+            "closures can not capture variables declared within a block or loop",
+        )
+        .into());
+    }
+    Ok(vec![Op::Pop as u8; dropped.len()].into())
+}
+
+/// Loops by jumping back to the predicate after every run of the body, until it is false:
+///
+/// ```text
+/// start: <predicate> JumpIfFalse -> end
+///        <body> JumpBack -> start
+/// end:
+/// ```
+///
+/// `break` and `continue` jump to `end` and `start` respectively, see `break_`.
+fn while_(ctx: &mut Context, predicate: &Ast, expr: &Ast) -> Result<(Bytecode, Type)> {
+    let (mut bytecode, predicate_ty) = compile(ctx, predicate)?;
+    if !matches!(predicate_ty, Type::Bool) {
+        return Err(Error::type_mismatch(Type::Bool, predicate_ty)
+            .with_span(predicate.span())
+            .with_help("the condition of a `while` loop must be a boolean")
+            .into());
+    }
+
+    let locals = ctx.scope()?.locals.len();
+    ctx.loops.push(locals);
+    let body = block(ctx, expr);
+    ctx.loops.pop();
+    let mut body = body?;
+
+    // Jump over the body and the JumpBack which follows it
+    bytecode.push(Op::JumpIfFalse as u8);
+    bytecode.extend_from_slice(&util::operand(body.len() + 1 + OPERAND_SIZE)?);
+    bytecode.append(&mut body);
+    let back = bytecode.len();
+    bytecode.push(Op::JumpBack as u8);
+    bytecode.extend_from_slice(&util::operand(back)?);
+    let end = bytecode.len();
+
+    for at in std::mem::take(&mut bytecode.breaks) {
+        let operand = util::operand(end - at - 1)?;
+        bytecode[at + 1..at + 1 + OPERAND_SIZE].copy_from_slice(&operand);
+    }
+    for at in std::mem::take(&mut bytecode.continues) {
+        let operand = util::operand(at)?;
+        bytecode[at + 1..at + 1 + OPERAND_SIZE].copy_from_slice(&operand);
+    }
+
+    Ok((bytecode, Type::Void))
+}
+
+/// Jumps out of the innermost loop, or back to its predicate for `continue`, after popping the
+/// locals declared within the loop. The jump is emitted with a placeholder operand, which `while_`
+/// patches once the whole loop has been compiled.
+fn break_(ctx: &mut Context, is_continue: bool) -> Result<(Bytecode, Type)> {
+    let keyword = if is_continue { "continue" } else { "break" };
+    let Some(&locals) = ctx.loops.last() else {
+        return Err(Error::new(
+            "break_outside_loop",
+            format!("`{}` outside of a loop", keyword),
+        )
+        .with_label(format!("cannot `{}` here", keyword))
+        .with_help("`break` and `continue` can only be used within a `while`, `loop` or `for` loop")
+        .into());
+    };
+
+    let mut bytecode = drop_locals(ctx, locals)?;
+    let at = bytecode.len();
+    if is_continue {
+        bytecode.continues.push(at);
+        bytecode.push(Op::JumpBack as u8);
+    } else {
+        bytecode.breaks.push(at);
+        bytecode.push(Op::Jump as u8);
+    }
+    bytecode.extend_from_slice(&[0; OPERAND_SIZE]);
+    Ok((bytecode, Type::Void))
+}
+
+/// Matches a value against the patterns of a `match` in turn. The value is kept in a local of its
+/// own while the arms check it, and bindings become locals of the arm they are declared in:
+///
+/// ```text
+/// <value>
+/// arm:  Get value GetMember.. [Discriminant] Push <expected> Binary Eq JumpIfFalse -> next
+///       Get value GetMember..  (for every binding)
+///       <guard> JumpIfFalse -> fail
+///       <body> Set value Pop.. Jump -> end
+/// fail: Pop..
+/// next: ...
+/// end:
+/// ```
+///
+/// Once an arm has run, its result takes the place of the matched value on the stack, so a `match`
+/// can be used wherever a value is expected.
+fn match_(
+    ctx: &mut Context,
+    expr: &Ast,
+    arms: &[MatchArm],
+    span: &Range<usize>,
+) -> Result<(Bytecode, Type)> {
+    // `let` declares its variable before its value is evaluated, but the stack only gets to hold
+    // it afterwards, when the matched value has taken its slot
+    let declared = if ctx.scope()?.pending_let {
+        ctx.scope()?.pending_let = false;
+        ctx.scope()?.locals.pop()
+    } else {
+        None
+    };
+    let result = match_arms(ctx, expr, arms, span);
+    if let Some(local) = declared {
+        let scope = ctx.scope()?;
+        scope.locals.push(local);
+        scope.pending_let = true;
+    }
+    result
+}
+
+fn match_arms(
+    ctx: &mut Context,
+    expr: &Ast,
+    arms: &[MatchArm],
+    span: &Range<usize>,
+) -> Result<(Bytecode, Type)> {
+    let (mut bytecode, ty) = compile(ctx, expr)?;
+    let ty = ctx.ts.resolve(ty)?;
+
+    let name = format!("<match@{}>", span.start);
+    ctx.scope()?.matches += 1;
+    ctx.scope()?.locals.push(LocalVariable {
+        name: name.clone(),
+        is_captured: false,
+        r#type: ty.clone(),
+    });
+    let Some(Ok(matched)) = ctx.get_local(&name).map(|(idx, _)| u8::try_from(idx)) else {
+        return Err(Error::unsupported("too many local variables in scope").into());
+    };
+
+    let mut result_type = None;
+    let mut coverage = vec![];
+    let mut compiled = vec![];
+    for arm in arms {
+        let mut destructured = Destructured::default();
+        let covers = destructure(ctx, &arm.pattern, &ty, vec![], &mut destructured, &arm.span)?;
+        if arm.guard.is_none() {
+            coverage.push(covers);
+        }
+
+        let locals = ctx.scope()?.locals.len();
+        let mut bindings = Bytecode::new();
+        for (name, path, ty) in destructured.bindings {
+            bindings.append(&mut member_at(matched, &path)?);
+            ctx.scope()?.locals.push(LocalVariable {
+                name,
+                is_captured: false,
+                r#type: ty,
+            });
+        }
+
+        let guard = match &arm.guard {
+            Some(guard) => {
+                let (guard_bytecode, guard_ty) = compile(ctx, guard)?;
+                if !matches!(guard_ty, Type::Bool) {
+                    return Err(Error::type_mismatch(Type::Bool, guard_ty)
+                        .with_span(guard.span())
+                        .with_help("the guard of a match arm must be a boolean")
+                        .into());
+                }
+                Some(guard_bytecode)
+            }
+            None => None,
+        };
+
+        let (mut body, body_ty) = arm_body(ctx, &arm.body)?;
+        if !diverges(last_statement(&arm.body)) {
+            match &result_type {
+                None => result_type = Some(body_ty),
+                Some(ty) if *ty != body_ty => {
+                    return Err(Error::type_mismatch(ty, body_ty)
+                        .with_span(Some(arm.span.clone()))
+                        .with_help("all arms of a match need to evaluate to the same type")
+                        .into());
+                }
+                Some(_) => {}
+            }
+        }
+
+        // The result takes the place of the matched value, and the locals of the arm are dropped
+        body.push(Op::Set as u8);
+        body.push(matched);
+        body.append(&mut drop_locals(ctx, locals)?);
+        let bound = ctx.scope()?.locals.len() - locals;
+        ctx.scope()?.locals.truncate(locals);
+
+        compiled.push((destructured.checks, bindings, guard, body, bound));
+    }
+
+    if let Some(missing) = missing_coverage(&ty, &coverage) {
+        return Err(Error::new(
+            "non_exhaustive_match",
+            format!("match does not cover {}", missing),
+        )
+        .with_span(Some(span.clone()))
+        .with_label("not every value is matched")
+        .with_help("add arms for the values which are missing, or a `_` arm to match anything else")
+        .into());
+    }
+
+    // Arms jump to the end once they have run, so they are put together starting with the last one
+    let mut arms_bytecode = Bytecode::new();
+    for (checks, bindings, guard, mut body, bound) in compiled.into_iter().rev() {
+        // A failing guard drops the bindings again before moving on to the next arm
+        let fail = if guard.is_some() {
+            vec![Op::Pop as u8; bound]
+        } else {
+            vec![]
+        };
+        body.push(Op::Jump as u8);
+        body.extend_from_slice(&util::operand(
+            fail.len() + arms_bytecode.len() + OPERAND_SIZE,
+        )?);
+
+        let mut arm = bindings;
+        if let Some(mut guard) = guard {
+            guard.push(Op::JumpIfFalse as u8);
+            guard.extend_from_slice(&util::operand(body.len())?);
+            arm.append(&mut guard);
+        }
+        arm.append(&mut body);
+        arm.extend_from_slice(&fail);
+
+        // Every check skips the rest of the arm if it fails
+        for (path, expected) in checks.into_iter().rev() {
+            let mut check = member_at(matched, &path)?;
+            let expected = match expected {
+                Check::Literal(value) => value,
+                Check::Variant(discriminant) => {
+                    check.push(Op::Discriminant as u8);
+                    Value::Usize(discriminant)
+                }
+            };
+            check.append(&mut value(ctx, &expected)?.0);
+            check.push(Op::Binary as u8);
+            check.push(InfixOp::Eq as u8);
+            check.push(Op::JumpIfFalse as u8);
+            check.extend_from_slice(&util::operand(arm.len())?);
+            check.append(&mut arm);
+            arm = check;
+        }
+
+        arm.append(&mut arms_bytecode);
+        arms_bytecode = arm;
+    }
+    bytecode.append(&mut arms_bytecode);
+
+    // The local of the matched value now holds the result, which is not a local itself
+    ctx.scope()?.locals.pop();
+
+    Ok((bytecode, result_type.unwrap_or(Type::Void)))
+}
+
+/// Compiles the body of a match arm. A block evaluates to the value of its last statement, or to
+/// void if that does not leave one.
+fn arm_body(ctx: &mut Context, body: &Ast) -> Result<(Bytecode, Type)> {
+    match body {
+        Ast::Statement { stmt, rest, .. } if !matches!(**rest, Ast::Nop) => {
+            let (mut bytecode, _) = compile(ctx, stmt)?;
+            if leaves_value(stmt) {
+                bytecode.push(Op::Pop as u8);
+            }
+            let (mut rest_bytecode, ty) = arm_body(ctx, rest)?;
+            bytecode.append(&mut rest_bytecode);
+            Ok((bytecode, ty))
+        }
+        Ast::Statement { stmt, .. } => arm_body(ctx, stmt),
+        stmt if leaves_value(stmt) => compile(ctx, stmt),
+        stmt => {
+            let (mut bytecode, _) = compile(ctx, stmt)?;
+            bytecode.append(&mut value(ctx, &Value::Void)?.0);
+            Ok((bytecode, Type::Void))
+        }
+    }
+}
+
+/// Whether a statement leaves the code which follows it unreachable.
+fn diverges(stmt: &Ast) -> bool {
+    matches!(
+        stmt,
+        Ast::Return { .. } | Ast::Break { .. } | Ast::Continue { .. }
+    )
+}
+
+/// Gets the value found by following the member indices of `path` from local `idx`.
+fn member_at(idx: u8, path: &[usize]) -> Result<Bytecode> {
+    let mut bytecode: Bytecode = vec![Op::Get as u8, idx].into();
+    for member in path {
+        let Ok(member) = u8::try_from(*member) else {
+            return Err(Error::unsupported("too many fields to match").into());
+        };
+        bytecode.extend_from_slice(&[Op::GetMember as u8, 1, member]);
+    }
+    Ok(bytecode)
+}
+
+/// What it takes for a value to match a pattern. Paths are the member indices which lead from the
+/// matched value to the value a check or binding concerns.
+#[derive(Default)]
+struct Destructured {
+    /// What has to be found at each path
+    checks: Vec<(Vec<usize>, Check)>,
+
+    /// The variables to declare, and the path of the value they get
+    bindings: Vec<(String, Vec<usize>, Type)>,
+}
+
+/// What a value has to be for a pattern to match it.
+enum Check {
+    /// Equal to a literal
+    Literal(Value),
+
+    /// The variant with this discriminant
+    Variant(usize),
+}
+
+/// The values of its type a pattern matches, as far as exhaustiveness is concerned.
+#[derive(Debug, PartialEq)]
+enum Coverage {
+    All,
+    Variant(String),
+    Bool(bool),
+    Partial,
+}
+
+/// Type checks a pattern against the type of the value it matches, and works out the checks and
+/// bindings it takes to match it. The members of an enum variant are the values of its payload.
+fn destructure(
+    ctx: &mut Context,
+    pattern: &Pattern,
+    ty: &Type,
+    path: Vec<usize>,
+    out: &mut Destructured,
+    span: &Range<usize>,
+) -> Result<Coverage> {
+    let ty = ctx.ts.resolve(ty.clone())?;
+    let invalid = |msg: String, label: &str| {
+        Err(Error::new("invalid_pattern", msg)
+            .with_span(Some(span.clone()))
+            .with_label(label)
+            .into())
+    };
+
+    match pattern {
+        Pattern::Wildcard => Ok(Coverage::All),
+
+        Pattern::Binding(name) => {
+            if let Type::Enum { variants, .. } = &ty
+                && variants.iter().any(|variant| variant.name == *name)
+            {
+                let variant = Pattern::Variant {
+                    enum_name: None,
+                    name: name.clone(),
+                    fields: vec![],
+                };
+                return destructure(ctx, &variant, &ty, path, out, span);
+            }
+            out.bindings.push((name.clone(), path, ty));
+            Ok(Coverage::All)
+        }
+
+        Pattern::Literal(value) => {
+            let value_ty = Type::from(value);
+            if !matches!(ty, Type::Any) && value_ty != ty {
+                return Err(Error::type_mismatch(&ty, value_ty)
+                    .with_span(Some(span.clone()))
+                    .with_help("a pattern must be of the type of the value it matches")
+                    .into());
+            }
+            out.checks.push((path, Check::Literal(value.clone())));
+            match value {
+                Value::Bool(b) => Ok(Coverage::Bool(*b)),
+                _ => Ok(Coverage::Partial),
+            }
+        }
+
+        Pattern::Variant {
+            enum_name,
+            name,
+            fields,
+        } => {
+            let Type::Enum {
+                name: matched,
+                variants,
+                ..
+            } = &ty
+            else {
+                return invalid(
+                    format!("expected `{}`, found enum variant `{}`", ty, name),
+                    "not an enum",
+                );
+            };
+            if let Some(enum_name) = enum_name
+                && enum_name != matched
+            {
+                return invalid(
+                    format!("`{}` is not the enum being matched", enum_name),
+                    "wrong enum",
+                );
+            }
+            let Some(variant) = variants.iter().find(|variant| variant.name == *name) else {
+                return invalid(format!("no variant `{}` on enum", name), "unknown variant");
+            };
+            let types = variant.types.clone().unwrap_or_default();
+            if types.len() != fields.len() {
+                return invalid(
+                    format!(
+                        "variant `{}` has {} fields, but the pattern has {}",
+                        name,
+                        types.len(),
+                        fields.len()
+                    ),
+                    "wrong number of fields",
+                );
+            }
+
+            out.checks
+                .push((path.clone(), Check::Variant(variant.discriminant)));
+            let mut coverage = Coverage::Variant(name.clone());
+            for (i, (field, ty)) in fields.iter().zip(types.iter()).enumerate() {
+                let field_path = [path.as_slice(), &[i]].concat();
+                if destructure(ctx, field, ty, field_path, out, span)? != Coverage::All {
+                    coverage = Coverage::Partial;
+                }
+            }
+            Ok(coverage)
+        }
+
+        Pattern::Struct { name, fields } => {
+            let Type::Struct {
+                name: Some(struct_name),
+                fields: struct_fields,
+                ..
+            } = &ty
+            else {
+                return invalid(
+                    format!("expected `{}`, found struct `{}`", ty, name),
+                    "not a struct",
+                );
+            };
+            if struct_name != name {
+                return invalid(
+                    format!("expected struct `{}`, found `{}`", struct_name, name),
+                    "wrong struct",
+                );
+            }
+
+            let mut coverage = Coverage::All;
+            for (field, pattern) in fields {
+                let Some(idx) = struct_fields.iter().position(|(name, _)| name == field) else {
+                    return invalid(format!("no field `{}` on struct", field), "unknown field");
+                };
+                let field_path = [path.as_slice(), &[idx]].concat();
+                let ty = struct_fields[idx].1.clone();
+                if destructure(ctx, pattern, &ty, field_path, out, span)? != Coverage::All {
+                    coverage = Coverage::Partial;
+                }
+            }
+            Ok(coverage)
+        }
+    }
+}
+
+/// Describes the values of `ty` which none of the arms of a match cover, if any.
+fn missing_coverage(ty: &Type, coverage: &[Coverage]) -> Option<String> {
+    if coverage.contains(&Coverage::All) {
+        return None;
+    }
+
+    let missing: Vec<String> = match ty {
+        Type::Enum { variants, .. } => variants
+            .iter()
+            .filter(|variant| !coverage.contains(&Coverage::Variant(variant.name.clone())))
+            .map(|variant| format!("`{}`", variant.name))
+            .collect(),
+        Type::Bool => [true, false]
+            .into_iter()
+            .filter(|b| !coverage.contains(&Coverage::Bool(*b)))
+            .map(|b| format!("`{}`", b))
+            .collect(),
+        ty => return Some(format!("every `{}`", ty)),
+    };
+
+    if missing.is_empty() {
+        None
+    } else {
+        Some(missing.join(", "))
+    }
+}
+
+fn import(_ctx: &mut Context, _path: &PathBuf, _span: &Range<usize>) -> Result<(Bytecode, Type)> {
+    // Imports are resolved by the parser before compilation even starts.
+    Err(Error::unsupported("imports are only allowed at the top of a module").into())
+
     // Make sure the module is available to us
     //let module = ctx.resolve_import(path.clone())?;
 
@@ -518,21 +1399,27 @@ fn import(ctx: &mut Context, path: &PathBuf, _span: &Range<usize>) -> Result<(Ve
     //     is_captured: false,
     //     r#type: module.r#type(),
     // });
-
-    Ok((vec![], Type::Unknown))
 }
 
 /// Expresses a binary operation such as 1 + 1, a == b, 9 > 8, etc.
 /// Requres the two expressions to be of the same type.
-fn infix(ctx: &mut Context, a: &Ast, op: &Operator, b: &Ast) -> Result<(Vec<u8>, Type)> {
+fn infix(ctx: &mut Context, a: &Ast, op: &Operator, b: &Ast) -> Result<(Bytecode, Type)> {
     let (mut bytecode, a_type) = compile(ctx, a)?;
-    let (mut bytecode_b, b_type) = compile(ctx, b)?;
+    // The logical operators have consumed the left hand side by the time they evaluate the right one
+    let held = usize::from(!matches!(op, Operator::And | Operator::Or));
+    let (mut bytecode_b, b_type) = compile_held(ctx, b, held)?;
 
     if !a_type.allowed_infix_operators(&b_type).contains(op) {
-        panic!(
-            "infix op {:?} not allowed between {:?} and {:?}",
-            op, a_type, b_type
-        );
+        return Err(Error::new(
+            "invalid_operands",
+            format!(
+                "operator {:?} is not allowed between `{}` and `{}`",
+                op, a_type, b_type
+            ),
+        )
+        .with_label(format!("{} {:?} {}", a_type, op, b_type))
+        .with_help("both sides of a binary operation need to be of the same type")
+        .into());
     }
 
     // if !a_type.is_numeric() || !b_type.is_numeric() {
@@ -541,15 +1428,145 @@ fn infix(ctx: &mut Context, a: &Ast, op: &Operator, b: &Ast) -> Result<(Vec<u8>,
     //     todo!();
     // }
 
+    if matches!(op, Operator::And | Operator::Or) {
+        return logical(ctx, bytecode, op, bytecode_b);
+    }
+
+    let infix_op =
+        infix_op(op).ok_or_else(|| Error::unsupported(format!("{:?} as a binary operator", op)))?;
     let return_type = op.resulting_type(a_type);
 
     bytecode.append(&mut bytecode_b);
     bytecode.push(Op::Binary as u8);
-    bytecode.push(op.to_owned() as u8);
+    bytecode.push(infix_op as u8);
 
     Ok((bytecode, return_type))
 }
 
+/// The instruction of the VM which evaluates a binary operator.
+fn infix_op(op: &Operator) -> Option<InfixOp> {
+    let op = match op {
+        Operator::Add => InfixOp::Add,
+        Operator::Sub => InfixOp::Sub,
+        Operator::Mul => InfixOp::Mul,
+        Operator::Div => InfixOp::Div,
+        Operator::Mod => InfixOp::Mod,
+        Operator::Eq => InfixOp::Eq,
+        Operator::NotEq => InfixOp::NotEq,
+        Operator::Lt => InfixOp::Lt,
+        Operator::Lte => InfixOp::Lte,
+        Operator::Gt => InfixOp::Gt,
+        Operator::Gte => InfixOp::Gte,
+        Operator::And => InfixOp::And,
+        Operator::Or => InfixOp::Or,
+        Operator::Pow => InfixOp::Pow,
+        Operator::Bang => return None,
+    };
+    Some(op)
+}
+
+/// Compiles `&&` and `||`, which only evaluate their right hand side if the left one does not
+/// already decide the result:
+///
+/// ```text
+/// a && b: <a> JumpIfFalse -> false          a || b: <a> JumpIfFalse -> rhs
+///         <b> Jump -> end                           Push true Jump -> end
+///  false: Push false                           rhs: <b>
+///    end:                                      end:
+/// ```
+fn logical(
+    ctx: &mut Context,
+    mut a: Bytecode,
+    op: &Operator,
+    b: Bytecode,
+) -> Result<(Bytecode, Type)> {
+    let (mut then_, mut else_) = match op {
+        Operator::And => (b, value(ctx, &Value::Bool(false))?.0),
+        _ => (value(ctx, &Value::Bool(true))?.0, b),
+    };
+
+    a.push(Op::JumpIfFalse as u8);
+    a.extend_from_slice(&util::operand(then_.len() + 1 + OPERAND_SIZE)?);
+    a.append(&mut then_);
+    a.push(Op::Jump as u8);
+    a.extend_from_slice(&util::operand(else_.len() + OPERAND_SIZE)?);
+    a.append(&mut else_);
+
+    Ok((a, Type::Bool))
+}
+
+/// Negates a boolean with `!`, or a signed number with `-`.
+fn prefix(
+    ctx: &mut Context,
+    op: &Operator,
+    rhs: &Ast,
+    span: &Range<usize>,
+) -> Result<(Bytecode, Type)> {
+    let (mut bytecode, ty) = compile(ctx, rhs)?;
+    let unary = match op {
+        Operator::Bang if matches!(ty, Type::Bool) => UnaryOp::Not,
+        Operator::Sub if ty.is_signed() => UnaryOp::Neg,
+        _ => {
+            return Err(Error::new(
+                "invalid_operand",
+                format!("operator {:?} is not allowed for `{}`", op, ty),
+            )
+            .with_span(Some(span.clone()))
+            .with_label(format!("{:?} {}", op, ty))
+            .with_help("`!` negates a bool, and `-` a signed integer or a float")
+            .into());
+        }
+    };
+
+    bytecode.push(Op::Unary as u8);
+    bytecode.push(unary as u8);
+    Ok((bytecode, ty))
+}
+
+/// Converts a number to another numeric type. Whether the value fits its new type is checked at
+/// runtime.
+fn cast(ctx: &mut Context, expr: &Ast, to: &Type, span: &Range<usize>) -> Result<(Bytecode, Type)> {
+    let (mut bytecode, ty) = compile(ctx, expr)?;
+    let numeric = match to {
+        Type::I8 => Numeric::I8,
+        Type::U8 => Numeric::U8,
+        Type::I16 => Numeric::I16,
+        Type::U16 => Numeric::U16,
+        Type::I32 => Numeric::I32,
+        Type::U32 => Numeric::U32,
+        Type::I64 => Numeric::I64,
+        Type::U64 => Numeric::U64,
+        Type::I128 => Numeric::I128,
+        Type::U128 => Numeric::U128,
+        Type::Isize => Numeric::Isize,
+        Type::Usize => Numeric::Usize,
+        Type::F32 => Numeric::F32,
+        Type::F64 => Numeric::F64,
+        _ => {
+            return Err(
+                Error::new("invalid_cast", format!("cannot cast to `{}`", to))
+                    .with_span(Some(span.clone()))
+                    .with_label("not a numeric type")
+                    .with_help("only numbers can be cast, to one of the integer or float types")
+                    .into(),
+            )
+        }
+    };
+    if !ty.is_numeric() {
+        return Err(
+            Error::new("invalid_cast", format!("cannot cast `{}` to `{}`", ty, to))
+                .with_span(expr.span())
+                .with_label("not a number")
+                .with_help("only numbers can be cast, to one of the integer or float types")
+                .into(),
+        );
+    }
+
+    bytecode.push(Op::Cast as u8);
+    bytecode.push(numeric as u8);
+    Ok((bytecode, to.clone()))
+}
+
 /// Accesses a member within a Struct, Module or Enum.
 /// E.g. foo.bar
 fn member(
@@ -557,11 +1574,25 @@ fn member(
     container: &Box<Ast>,
     key: &Key,
     _span: &Range<usize>,
-) -> Result<(Vec<u8>, Type)> {
+) -> Result<(Bytecode, Type)> {
+    // Enums have no value of their own, their members are the variants they construct
+    if let Some(enum_) = enum_type(ctx, container) {
+        return variant(ctx, enum_, key, &[]);
+    }
+
     // Put the containing object on the stack
-    // NOTE: In the case of Enums, `bc` will be empty and we just care about `expr_type`.
-    let (mut bytecode, container_type) = compile(ctx, container)?;
+    let (bytecode, container_type) = compile(ctx, container)?;
+    member_of(ctx, container, bytecode, container_type, key)
+}
 
+/// Gets a member of a container which has already been compiled to `bytecode`.
+fn member_of(
+    ctx: &mut Context,
+    container: &Ast,
+    mut bytecode: Bytecode,
+    container_type: Type,
+    key: &Key,
+) -> Result<(Bytecode, Type)> {
     match ctx.ts.resolve(container_type.clone())? {
         Type::Struct {
             fields, methods, ..
@@ -579,17 +1610,53 @@ fn member(
                 for (name, (ty, idx)) in methods.iter() {
                     if name == key {
                         bytecode.push(Op::GetFunction as u8);
-                        bytecode.push((ctx.vtable_offset() + *idx) as u8);
+                        bytecode.push(*idx as u8);
                         return Ok((bytecode, ty.clone()));
                     }
                 }
 
-                panic!("invalid struct field or method: {}", key)
+                Err(Error::new(
+                    "unknown_field",
+                    format!("no field or method `{}` on struct", key),
+                )
+                .with_label("unknown field")
+                .into())
             } else {
-                panic!("cant use non string key for struct access");
+                Err(
+                    Error::new("invalid_key", "structs can only be accessed by field name")
+                        .with_label("expected a field name")
+                        .into(),
+                )
             }
         }
 
+        Type::Enum {
+            methods, generics, ..
+        } => {
+            let Key::String(key) = key else {
+                return Err(
+                    Error::new("invalid_key", "enums can only be accessed by method name")
+                        .with_label("expected a method name")
+                        .into(),
+                );
+            };
+            let Some((ty, idx)) = methods.get(key) else {
+                return Err(
+                    Error::new("unknown_field", format!("no method `{}` on enum", key))
+                        .with_label("unknown method")
+                        .into(),
+                );
+            };
+            bytecode.push(Op::GetFunction as u8);
+            bytecode.push(*idx as u8);
+
+            // Methods get resolved only once they are accessed, see `Type::Enum`
+            ctx.push_type_scope(&generics);
+            let ty = ctx.ts.resolve(ty.clone());
+            ctx.pop_type_scope();
+            Ok((bytecode, ty?))
+        }
+
         Type::List(ty) => match key {
             Key::Usize(idx) => {
                 bytecode.push(Op::GetMember as u8);
@@ -598,16 +1665,19 @@ fn member(
                 Ok((bytecode, *ty.clone()))
             }
             Key::Expression(expr) => {
-                let (mut key_bytecode, key_type) = compile(ctx, expr)?;
+                let (mut key_bytecode, key_type) = compile_held(ctx, expr, 1)?;
                 if key_type != Type::Usize {
-                    panic!("lists can only be indexed by usize");
+                    return Err(Error::type_mismatch(Type::Usize, key_type)
+                        .with_span(expr.span())
+                        .with_help("lists can only be indexed by usize")
+                        .into());
                 }
                 bytecode.append(&mut key_bytecode);
                 bytecode.push(Op::GetMember as u8);
                 bytecode.push(0_u8);
-                Ok((bytecode, Type::Unknown))
+                Ok((bytecode, *ty.clone()))
             }
-            x => todo!("{:?}", x),
+            Key::String(name) => builtin_method(ctx, &container_type, name),
         },
 
         Type::Module { path } => match key {
@@ -618,48 +1688,311 @@ fn member(
                         if local.name == *ident {
                             if let Ok(idx) = u8::try_from(module.stack_offset + i) {
                                 let return_type = local.r#type.clone();
-                                return Ok((vec![Op::Get as u8, idx], return_type));
+                                return Ok((vec![Op::GetGlobal as u8, idx].into(), return_type));
                             }
-                            unreachable!()
+                            return Err(
+                                Error::unsupported("too many local variables in module").into()
+                            );
                         }
                     }
-                    panic!("unknown variable in module {:?}", path);
+                    Err(Error::new(
+                        "unknown_variable",
+                        format!("cannot find `{}` in module {:?}", ident, path),
+                    )
+                    .with_label("not found in module")
+                    .into())
                 } else {
-                    panic!("unknown module {:?}", path);
+                    Err(
+                        Error::new("unknown_module", format!("unknown module {:?}", path))
+                            .with_label("unknown module")
+                            .into(),
+                    )
                 }
             }
-            _ => panic!("cant retrieve module symbol by kkey other than string"),
+            _ => Err(
+                Error::new("invalid_key", "modules can only be accessed by name")
+                    .with_label("expected a name")
+                    .into(),
+            ),
         },
 
-        // TODO this can probably be handled in a nicer way than being hardcoded here...
-        Type::Interface { name, .. } if name == "Index" => match key {
-            Key::Expression(expr) => {
-                let (mut key_bytecode, _key_type) = compile(ctx, expr)?;
-                bytecode.push(Op::GetMember as u8);
-                panic!();
-                bytecode.append(&mut key_bytecode);
-                Ok((bytecode, Type::Unknown))
+        Type::Map(key_type, value_type) => match key {
+            Key::Usize(_) | Key::Expression(_) => {
+                let (mut key_bytecode, ty) = match key {
+                    Key::Expression(expr) => compile_held(ctx, expr, 1)?,
+                    _ => (Bytecode::new(), Type::Usize),
+                };
+                if ty != *key_type {
+                    let span = match key {
+                        Key::Expression(expr) => expr.span(),
+                        _ => None,
+                    };
+                    return Err(Error::type_mismatch(*key_type, ty)
+                        .with_span(span)
+                        .with_help("maps can only be indexed by their key type")
+                        .into());
+                }
+                match key {
+                    Key::Usize(idx) => {
+                        bytecode.extend_from_slice(&[Op::GetMember as u8, 1_u8, *idx as u8])
+                    }
+                    _ => {
+                        bytecode.append(&mut key_bytecode);
+                        bytecode.extend_from_slice(&[Op::GetMember as u8, 0_u8]);
+                    }
+                }
+                Ok((bytecode, *value_type))
             }
-            x => todo!("{:?}", x),
+            Key::String(name) => builtin_method(ctx, &container_type, name),
         },
-        x => todo!("{:?}", x),
+
+        // TODO this can probably be handled in a nicer way than being hardcoded here...
+        Type::Interface { name, .. } if name == "Index" => {
+            Err(Error::unsupported("indexing into an `Index` interface is not supported").into())
+        }
+        // Such as the methods of strings and numbers
+        x if !x.builtin_methods().is_empty() && matches!(key, Key::String(_)) => {
+            let Key::String(name) = key else {
+                return Err(Error::fatal().into());
+            };
+            builtin_method(ctx, &x, name)
+        }
+        x => Err(Error::new(
+            "invalid_member_access",
+            format!("`{}` does not have any members", x),
+        )
+        .with_span(container.span())
+        .with_label("has no members")
+        .into()),
+    }
+}
+
+/// Gets a method which a type has through a builtin, see `Type::builtin_methods`. Only the builtin
+/// itself is emitted, as `call` puts the value it is called on on the stack as its first argument.
+fn builtin_method(ctx: &mut Context, ty: &Type, name: &str) -> Result<(Bytecode, Type)> {
+    let ty = ctx.ts.resolve(ty.clone())?;
+    let Some((method_type, builtin)) = ty.builtin_methods().remove(name) else {
+        return Err(
+            Error::new("unknown_field", format!("no method `{}` on `{}`", name, ty))
+                .with_label("unknown method")
+                .into(),
+        );
+    };
+    let Some((idx, _)) = ctx.get_builtin(builtin) else {
+        return Err(Error::fatal().into());
+    };
+    Ok((vec![Op::GetBuiltin as u8, idx as u8].into(), method_type))
+}
+
+/// Finds the enum a container names, unless a variable of the same name shadows it.
+fn enum_type(ctx: &mut Context, container: &Ast) -> Option<Type> {
+    let Ast::Var { ident: name, .. } = container else {
+        return None;
+    };
+    if ctx.get_local(name).is_some() {
+        return None;
     }
+    ctx.ts
+        .get_type(name)
+        .filter(|ty| matches!(ty, Type::Enum { .. }))
+}
+
+/// Constructs an enum variant, with the enum's generics taking on the types of the payload.
+fn variant(ctx: &mut Context, enum_: Type, key: &Key, args: &[Ast]) -> Result<(Bytecode, Type)> {
+    let Type::Enum {
+        name: enum_name,
+        variants,
+        methods,
+        generics,
+    } = enum_
+    else {
+        return Err(Error::fatal().into());
+    };
+    let Key::String(name) = key else {
+        return Err(
+            Error::new("invalid_key", "enums can only be accessed by variant name")
+                .with_label("expected a variant name")
+                .into(),
+        );
+    };
+    let Some(variant) = variants.iter().find(|variant| variant.name == *name) else {
+        return Err(
+            Error::new("unknown_variant", format!("no variant `{}` on enum", name))
+                .with_label("unknown variant")
+                .into(),
+        );
+    };
+
+    let types = variant.types.clone().unwrap_or_default();
+    if args.len() != types.len() {
+        return Err(Error::new(
+            "wrong_arity",
+            format!(
+                "variant `{}` takes {} values but {} were supplied",
+                name,
+                types.len(),
+                args.len()
+            ),
+        )
+        .with_label(format!("expected {} values", types.len()))
+        .into());
+    }
+
+    let mut bytecode = Bytecode::new();
+    let mut arg_types = vec![];
+    for (i, arg) in args.iter().enumerate() {
+        let (mut arg_bytecode, arg_type) = compile_held(ctx, arg, i)?;
+        bytecode.append(&mut arg_bytecode);
+        arg_types.push(arg_type);
+    }
+
+    ctx.push_type_scope(&generics);
+    let mut substitutions = vec![];
+    for ((arg, ty), arg_type) in args.iter().zip(types).zip(arg_types) {
+        let resolved = ctx.ts.resolve(ty.clone())?;
+        if resolved != arg_type {
+            return Err(Error::type_mismatch(resolved, arg_type)
+                .with_span(arg.span())
+                .with_help(format!(
+                    "variant `{}` is declared with a different type",
+                    name
+                ))
+                .into());
+        }
+        if let Type::TypeVar(ident) = ty
+            && !matches!(arg_type, Type::Unknown)
+        {
+            substitutions.push((ident, arg_type));
+        }
+    }
+    ctx.pop_type_scope();
+
+    bytecode.push(Op::Variant as u8);
+    bytecode.extend_from_slice(&util::operand(variant.discriminant)?);
+    bytecode.extend_from_slice(&util::operand(args.len())?);
+
+    let generics = generics
+        .into_iter()
+        .map(
+            |(n, t)| match substitutions.iter().find(|(ident, _)| *ident == n) {
+                Some((_, bound)) => (n, bound.clone()),
+                None => (n, t),
+            },
+        )
+        .collect();
+    Ok((
+        bytecode,
+        Type::Enum {
+            name: enum_name,
+            variants,
+            methods,
+            generics,
+        },
+    ))
 }
 
 /// Pops the current call frame
-fn return_(ctx: &mut Context, expr: &Box<Ast>) -> Result<(Vec<u8>, Type)> {
+fn return_(ctx: &mut Context, expr: &Box<Ast>) -> Result<(Bytecode, Type)> {
     let (mut bytecode, ty) = compile(ctx, expr)?;
     bytecode.push(Op::Return as u8);
     Ok((bytecode, ty))
 }
 
+/// Compiles `expr?`, which evaluates to the value of an `Ok` result and returns an `Err` one from
+/// the current function. An `Err` is laid out the same whatever the type of its `Ok` value, so it
+/// gets returned as it is:
+/// ```text
+/// <result> Dup Discriminant Push <Ok> Binary Eq JumpIfFalse -> err
+///          GetMember 1 0 Jump -> end
+/// err:     Return
+/// end:
+/// ```
+fn try_(ctx: &mut Context, expr: &Ast, span: &Range<usize>) -> Result<(Bytecode, Type)> {
+    let (mut bytecode, ty) = compile(ctx, expr)?;
+    let ty = ctx.ts.resolve(ty)?;
+    let Some((ok, ok_type, err_type)) = result_variants(&ty) else {
+        return Err(Error::type_mismatch("Result", ty)
+            .with_span(expr.span())
+            .with_help("`?` can only be applied to a Result")
+            .into());
+    };
+
+    let returns = match &ctx.current_function_type {
+        Some(Type::Function { returns, .. }) => ctx.ts.resolve(*returns.clone())?,
+        _ => {
+            return Err(Error::new(
+                "invalid_try",
+                "`?` can only be used within a function returning a Result",
+            )
+            .with_span(Some(span.clone()))
+            .with_label("not within a function")
+            .into())
+        }
+    };
+    if !matches!(result_variants(&returns), Some((_, _, ref e)) if *e == err_type) {
+        // The span of `Ast::Try` only covers the `?` itself
+        let try_span = expr
+            .span()
+            .map_or(span.clone(), |expr| expr.start..span.end);
+        let mut error = Error::new(
+            "type_mismatch",
+            format!(
+                "`?` cannot convert `{}` into the function's return type `{}`",
+                ty, returns
+            ),
+        )
+        .with_span(Some(try_span))
+        .with_label("returns the error of this result")
+        .with_help("the function needs to return a Result with the same error type");
+        if let Some(returns_span) = ctx.current_returns_span.clone() {
+            error = error.with_secondary_label(returns_span, "the function returns this type");
+        }
+        return Err(error.into());
+    }
+
+    bytecode.extend_from_slice(&[Op::Dup as u8, Op::Discriminant as u8]);
+    bytecode.append(&mut value(ctx, &Value::Usize(ok))?.0);
+    bytecode.extend_from_slice(&[Op::Binary as u8, InfixOp::Eq as u8]);
+
+    let unwrap = [Op::GetMember as u8, 1, 0, Op::Jump as u8];
+    bytecode.push(Op::JumpIfFalse as u8);
+    bytecode.extend_from_slice(&util::operand(unwrap.len() + OPERAND_SIZE)?);
+    bytecode.extend_from_slice(&unwrap);
+    bytecode.extend_from_slice(&util::operand(OPERAND_SIZE + 1)?);
+    bytecode.push(Op::Return as u8);
+
+    Ok((bytecode, ok_type))
+}
+
+/// If `ty` is a `Result`, gets the discriminant of its `Ok` variant along with the types of its
+/// `Ok` and `Err` values.
+fn result_variants(ty: &Type) -> Option<(usize, Type, Type)> {
+    let Type::Enum { name, variants, .. } = ty else {
+        return None;
+    };
+    if name != "Result" {
+        return None;
+    }
+    let value = |variant: &str| {
+        variants
+            .iter()
+            .find(|v| v.name == variant)
+            .and_then(|v| match v.types.as_deref() {
+                Some([ty]) => Some((v.discriminant, ty.clone())),
+                _ => None,
+            })
+    };
+    let ((ok, ok_type), (_, err_type)) = (value("Ok")?, value("Err")?);
+    Some((ok, ok_type, err_type))
+}
+
 /// Compiles a statement and optionally the rest of the program.
 fn statement(
     ctx: &mut Context,
     stmt: Box<Ast>,
     rest: Box<Ast>,
     _span: &Range<usize>,
-) -> Result<(Vec<u8>, Type)> {
+) -> Result<(Bytecode, Type)> {
     if let Ast::Nop = *rest {
         let (bytecode, ty) = compile(ctx, &stmt)?;
         let return_type = if let Ast::Return { .. } = *stmt {
@@ -674,17 +2007,7 @@ fn statement(
     let (mut bytecode, _) = compile(ctx, &stmt)?;
 
     // If the statement is not an assigment or declaration, pop it off the stack afterwards
-    if !matches!(
-        *stmt,
-        Ast::Let { .. }
-            | Ast::Assignment { .. }
-            | Ast::Type { .. }
-            | Ast::Block(_)
-            | Ast::If { .. }
-            | Ast::Break
-            | Ast::Continue
-            | Ast::While { .. }
-    ) {
+    if leaves_value(&stmt) {
         bytecode.push(Op::Pop as u8);
     }
 
@@ -693,13 +2016,39 @@ fn statement(
     Ok((bytecode, ty))
 }
 
+/// Whether a statement leaves a value on the stack which does not belong to a local variable.
+fn leaves_value(stmt: &Ast) -> bool {
+    !matches!(
+        stmt,
+        Ast::Let { .. }
+            | Ast::Assignment { .. }
+            | Ast::Type { .. }
+            | Ast::Block { .. }
+            | Ast::If { .. }
+            | Ast::While { .. }
+            | Ast::Break { .. }
+            | Ast::Continue { .. }
+            | Ast::Return { .. }
+            | Ast::Nop
+    )
+}
+
+/// The statement a chain of statements ends with.
+fn last_statement(ast: &Ast) -> &Ast {
+    match ast {
+        Ast::Statement { stmt, rest, .. } if matches!(**rest, Ast::Nop) => stmt,
+        Ast::Statement { rest, .. } => last_statement(rest),
+        ast => ast,
+    }
+}
+
 fn struct_literal(
     ctx: &mut Context,
     ident: &Option<String>,
     fields: &HashMap<String, Ast>,
     _span: &Range<usize>,
-) -> Result<(Vec<u8>, Type)> {
-    let mut bytecode = vec![];
+) -> Result<(Bytecode, Type)> {
+    let mut bytecode = Bytecode::new();
     let mut field_types = vec![];
     let mut methods: HashMap<String, (Type, usize)> = HashMap::default();
     let mut generics = vec![];
@@ -718,9 +2067,11 @@ fn struct_literal(
             methods = m.clone();
             generics = g.clone();
         } else {
-            panic!(
-                "struct of name {} is not defined at this point or is of the wrong type",
-                name
+            return Err(
+                Error::new("unknown_struct", format!("cannot find struct `{}`", name))
+                    .with_label("not a struct")
+                    .with_help("structs need to be declared before they are constructed")
+                    .into(),
             );
         }
     }
@@ -728,14 +2079,25 @@ fn struct_literal(
     ctx.push_type_scope(&generics);
 
     let mut substitutions = vec![];
-    for (name, field_ty) in field_types.iter_mut() {
-        let (mut bc, actual_field_type) = compile(ctx, fields.get(name).unwrap())?;
-
-        if ctx.ts.resolve(field_ty.clone())? != actual_field_type {
-            panic!(
-                "type error: field {} expected type {:?}, got {:?}",
-                name, field_ty, actual_field_type
+    for (i, (name, field_ty)) in field_types.iter_mut().enumerate() {
+        let Some(field) = fields.get(name) else {
+            return Err(
+                Error::new("missing_field", format!("missing field `{}`", name))
+                    .with_label(format!("`{}` is not initialized", name))
+                    .into(),
             );
+        };
+        let (mut bc, actual_field_type) = compile_held(ctx, field, i)?;
+
+        let resolved_field_type = ctx.ts.resolve(field_ty.clone())?;
+        if resolved_field_type != actual_field_type {
+            return Err(Error::type_mismatch(resolved_field_type, actual_field_type)
+                .with_span(field.span())
+                .with_help(format!(
+                    "field `{}` is declared with a different type",
+                    name
+                ))
+                .into());
         }
         if let Type::TypeVar(ident) = field_ty.clone() {
             substitutions.push((ident, actual_field_type.clone()));
@@ -748,12 +2110,12 @@ fn struct_literal(
 
     let field_types = field_types
         .into_iter()
-        .map(|(n, t)| (n, ctx.ts.resolve(t).unwrap()))
-        .collect();
+        .map(|(n, t)| Ok((n, ctx.ts.resolve(t)?)))
+        .collect::<Result<_>>()?;
     let methods = methods
         .into_iter()
-        .map(|(n, (t, idx))| (n, (ctx.ts.resolve(t).unwrap(), idx)))
-        .collect();
+        .map(|(n, (t, idx))| Ok((n, (ctx.ts.resolve(t)?, idx))))
+        .collect::<Result<_>>()?;
 
     ctx.pop_type_scope();
     ctx.pop_type_scope();
@@ -766,8 +2128,7 @@ fn struct_literal(
     };
 
     bytecode.push(Op::Collect as u8);
-    let length: [u8; std::mem::size_of::<usize>()] = fields.len().to_ne_bytes();
-    bytecode.append(&mut length.to_vec());
+    bytecode.extend_from_slice(&util::operand(fields.len())?);
     Ok((bytecode, return_type))
 }
 
@@ -778,15 +2139,28 @@ fn decl_type(
     name: &str,
     decl: &TypeDecl,
     _span: &Range<usize>,
-) -> Result<(Vec<u8>, Type)> {
+) -> Result<(Bytecode, Type)> {
     match decl {
         TypeDecl::Enum {
+            generics,
             variants,
-            generics: _,
+            methods,
         } => {
-            let typ = Type::Enum(variants.clone());
+            ctx.push_type_scope(generics);
+
+            let (method_types, method_vtable_idxs) = reserve_methods(ctx, methods);
+            let typ = Type::Enum {
+                name: name.to_string(),
+                variants: variants.clone(),
+                methods: method_types,
+                generics: generics.clone(),
+            };
             ctx.add_type(name.to_string(), typ.clone())?;
-            Ok((vec![], typ))
+            compile_methods(ctx, methods, &method_vtable_idxs)?;
+
+            ctx.pop_type_scope();
+
+            Ok((Bytecode::new(), typ))
         }
 
         TypeDecl::Struct {
@@ -796,47 +2170,19 @@ fn decl_type(
         } => {
             ctx.push_type_scope(generics);
 
-            let mut method_vtable_idxs: HashMap<String, usize> = HashMap::default();
+            let (method_types, method_vtable_idxs) = reserve_methods(ctx, methods);
             let typ = Type::Struct {
                 name: Some(name.to_string()),
                 fields: fields.clone(),
-                methods: methods
-                    .iter()
-                    .map(|(name, ast)| {
-                        // Reserve an index in the vtable with empty bytecode
-                        let vtable_idx = ctx.cache_fn(vec![]);
-                        method_vtable_idxs.insert(name.clone(), vtable_idx);
-
-                        // The AST dont know whether the function is a method or not. Make sure it is.
-                        let mut mtype = ast.into();
-                        if let Type::Function {
-                            ref mut is_method, ..
-                        } = mtype
-                        {
-                            *is_method = true;
-                        }
-
-                        (name.clone(), (mtype, vtable_idx))
-                    })
-                    .collect(),
+                methods: method_types,
                 generics: generics.clone(),
             };
-
             ctx.add_type(name.to_string(), typ.clone())?;
-
-            for (method_name, ast) in methods.iter() {
-                let (fn_bytecode, ty) = compile(ctx, ast)?;
-
-                if Type::from(ast) != ty {
-                    panic!("compiled method to different type than ast???");
-                }
-
-                ctx.cache_fn_at(*method_vtable_idxs.get(method_name).unwrap(), fn_bytecode);
-            }
+            compile_methods(ctx, methods, &method_vtable_idxs)?;
 
             ctx.pop_type_scope();
 
-            Ok((vec![], typ))
+            Ok((Bytecode::new(), typ))
         }
 
         TypeDecl::Interface {
@@ -849,11 +2195,68 @@ fn decl_type(
                 generics: generics.clone(),
             };
             ctx.add_type(name.to_string(), typ.clone())?;
-            Ok((vec![], typ))
+            Ok((Bytecode::new(), typ))
         }
     }
 }
 
+/// Reserves an index in the vtable for every method of a struct or enum. Returns the types of the
+/// methods along with their index within the whole vtable, as they may be used from other modules,
+/// and the indices within the functions cache of this module.
+fn reserve_methods(
+    ctx: &mut Context,
+    methods: &[(String, Ast)],
+) -> (HashMap<String, (Type, usize)>, HashMap<String, usize>) {
+    let mut method_vtable_idxs: HashMap<String, usize> = HashMap::default();
+    let method_types = methods
+        .iter()
+        .map(|(name, ast)| {
+            let vtable_idx = ctx.reserve_fn();
+            method_vtable_idxs.insert(name.clone(), vtable_idx);
+
+            // The AST dont know whether the function is a method or not. Make sure it is.
+            let mut mtype = ast.into();
+            if let Type::Function {
+                ref mut is_method, ..
+            } = mtype
+            {
+                *is_method = true;
+            }
+
+            (name.clone(), (mtype, ctx.vtable_offset() + vtable_idx))
+        })
+        .collect();
+    (method_types, method_vtable_idxs)
+}
+
+/// Compiles the methods of a struct or enum into the vtable indices `reserve_methods` reserved.
+fn compile_methods(
+    ctx: &mut Context,
+    methods: &[(String, Ast)],
+    method_vtable_idxs: &HashMap<String, usize>,
+) -> Result<()> {
+    for (method_name, ast) in methods.iter() {
+        let (mut fn_bytecode, ty) = compile(ctx, ast)?;
+        if let Some(Some(name)) = fn_bytecode.functions.first_mut().map(|f| &mut f.name) {
+            *name = format!("{}.{}", name, method_name);
+        }
+
+        let declared_type = ctx.ts.resolve(Type::from(ast))?;
+        if declared_type != ty {
+            return Err(Error::type_mismatch(declared_type, ty)
+                .with_span(ast.span())
+                .with_help(format!(
+                    "method `{}` does not match its declared signature",
+                    method_name
+                ))
+                .into());
+        }
+
+        ctx.cache_fn_at(*method_vtable_idxs.get(method_name).unwrap(), fn_bytecode);
+    }
+    Ok(())
+}
+
 /// Creates a new local variable.
 fn let_(
     ctx: &mut Context,
@@ -861,7 +2264,7 @@ fn let_(
     annotated_type: &Option<Type>,
     expr: &Ast,
     _span: &Range<usize>,
-) -> Result<(Vec<u8>, Type)> {
+) -> Result<(Bytecode, Type)> {
     // Create a new local var of type `ty`
     let ty = annotated_type.clone().unwrap_or(Type::Unknown);
     let old_assignment_ctx = ctx.assignment_ctx.clone();
@@ -871,14 +2274,18 @@ fn let_(
         is_captured: false,
         r#type: ty.clone(),
     };
-    ctx.scope()?.locals.push(local_variable);
+    let scope = ctx.scope()?;
+    scope.locals.push(local_variable);
+    let pending_let = std::mem::replace(&mut scope.pending_let, true);
 
-    let (assignment_bytes, mut assignment_type) = compile(ctx, expr)?;
+    let compiled = compile(ctx, expr);
+    ctx.scope()?.pending_let = pending_let;
+    let (assignment_bytes, mut assignment_type) = compiled?;
 
     if let Type::GenericFunctionStub { scope, idx } = assignment_type.clone() {
         ctx.scope_by_index(scope)?.generic_functions[idx].0 = ident.to_owned();
         ctx.scope()?.locals.pop();
-        return Ok((vec![], assignment_type));
+        return Ok((Bytecode::new(), assignment_type));
     }
 
     // If the original type is Unknown, update the local var with the assignment type
@@ -889,10 +2296,10 @@ fn let_(
             locals.last_mut().unwrap().r#type = assignment_type.clone();
         }
         ty if ty != assignment_type => {
-            panic!(
-                "attempted to assign value of type {:?} to a variable of type {:?}",
-                assignment_type, ty
-            );
+            return Err(Error::type_mismatch(&ty, assignment_type)
+                .with_span(expr.span())
+                .with_help(format!("`{}` is annotated as `{}`", ident, ty))
+                .into());
         }
         ty => {
             assignment_type = ty;
@@ -904,40 +2311,130 @@ fn let_(
 }
 
 /// Evaluates a list literal
-fn list(ctx: &mut Context, items: &Vec<Ast>, _span: &Range<usize>) -> Result<(Vec<u8>, Type)> {
-    let mut bytecode = vec![];
-    let length: [u8; std::mem::size_of::<usize>()] = items.len().to_ne_bytes();
+fn list(ctx: &mut Context, items: &[Ast], _span: &Range<usize>) -> Result<(Bytecode, Type)> {
+    let mut bytecode = Bytecode::new();
+    let length = util::operand(items.len())?;
 
-    let mut list_type = Type::Unknown;
-    for ast in items {
-        let (mut bc, item_type) = compile(ctx, ast)?;
+    // An empty list takes on the type it is annotated with
+    let mut list_type = if items.is_empty() {
+        Type::Any
+    } else {
+        Type::Unknown
+    };
+    for (i, ast) in items.iter().enumerate() {
+        let (mut bc, item_type) = compile_held(ctx, ast, i)?;
         bytecode.append(&mut bc);
         match list_type {
             Type::Unknown => list_type = item_type,
-            ty if ty != item_type => {
-                panic!(
-                    "attempted to use different types in vec! {:?}, {:?}",
-                    &ty, &item_type
-                );
+            ref ty if *ty != item_type => {
+                return Err(Error::type_mismatch(ty, item_type)
+                    .with_span(ast.span())
+                    .with_help("all items of a list must be of the same type")
+                    .into());
             }
             _ => {}
         }
     }
 
     bytecode.push(Op::Collect as u8);
-    bytecode.append(&mut length.to_vec());
+    bytecode.extend_from_slice(&length);
 
     Ok((bytecode, Type::List(Box::new(list_type))))
 }
 
-/// Raw values get emitted into the bytecode as <usize length><bytes>.
-fn value(_ctx: &mut Context, value: &Value) -> Result<(Vec<u8>, Type)> {
-    let mut value_bytecode = vec![];
-    let mut bytes = util::serialize_value(value.clone())?;
-    let length: [u8; std::mem::size_of::<usize>()] = bytes.len().to_ne_bytes();
+/// Collects pairs of keys and values into a map, emitted as <key><value>...<CollectMap><len>.
+/// Values of types which are not compared by value, or not equal to themselves, can not be keys.
+fn map(
+    ctx: &mut Context,
+    entries: &[(Ast, Ast)],
+    _span: &Range<usize>,
+) -> Result<(Bytecode, Type)> {
+    let mut bytecode = Bytecode::new();
+    let length = util::operand(entries.len())?;
+
+    // An empty map takes on the type it is annotated with
+    if entries.is_empty() {
+        bytecode.push(Op::CollectMap as u8);
+        bytecode.extend_from_slice(&length);
+        return Ok((
+            bytecode,
+            Type::Map(Box::new(Type::Any), Box::new(Type::Any)),
+        ));
+    }
+
+    let mut key_type = Type::Unknown;
+    let mut value_type = Type::Unknown;
+    for (i, (key, value)) in entries.iter().enumerate() {
+        for (j, (ast, ty)) in [(key, &mut key_type), (value, &mut value_type)]
+            .into_iter()
+            .enumerate()
+        {
+            let (mut bc, item_type) = compile_held(ctx, ast, 2 * i + j)?;
+            bytecode.append(&mut bc);
+            match ty {
+                Type::Unknown => *ty = item_type,
+                ty if *ty != item_type => {
+                    return Err(Error::type_mismatch(&*ty, item_type)
+                        .with_span(ast.span())
+                        .with_help("all keys and all values of a map must be of the same type")
+                        .into());
+                }
+                _ => {}
+            }
+        }
+        if !key_type.is_hashable() {
+            return Err(Error::new(
+                "invalid_key",
+                format!("`{}` can not be the key of a map", key_type),
+            )
+            .with_span(key.span())
+            .with_label("not a valid key")
+            .into());
+        }
+    }
+
+    bytecode.push(Op::CollectMap as u8);
+    bytecode.extend_from_slice(&length);
+
+    Ok((
+        bytecode,
+        Type::Map(Box::new(key_type), Box::new(value_type)),
+    ))
+}
+
+/// Joins the text of each segment of an interpolated string, emitted as <segment>...<Concat><len>.
+/// Segments must be of a type that converts to a string, which are those with a `to_string` method.
+fn interpolation(
+    ctx: &mut Context,
+    segments: &[Ast],
+    span: &Range<usize>,
+) -> Result<(Bytecode, Type)> {
+    let mut bytecode = Bytecode::new();
+    let length = util::operand(segments.len())?;
+
+    for (i, ast) in segments.iter().enumerate() {
+        let (mut bc, ty) = compile_held(ctx, ast, i)?;
+        if !ty.builtin_methods().contains_key("to_string") {
+            return Err(Error::type_mismatch(Type::String, ty)
+                .with_span(ast.span().or(Some(span.clone())))
+                .with_help("only strings, chars, bools and numbers can be interpolated")
+                .into());
+        }
+        bytecode.append(&mut bc);
+    }
+
+    bytecode.push(Op::Concat as u8);
+    bytecode.extend_from_slice(&length);
+
+    Ok((bytecode, Type::String))
+}
+
+/// Raw values get added to the constant pool, and emitted into the bytecode as <Push><constant index>.
+fn value(ctx: &mut Context, value: &Value) -> Result<(Bytecode, Type)> {
+    let mut value_bytecode = Bytecode::new();
+    let constant = ctx.add_constant(value.clone());
     value_bytecode.push(Op::Push as u8);
-    value_bytecode.append(&mut length.to_vec());
-    value_bytecode.append(&mut bytes);
+    value_bytecode.extend_from_slice(&util::operand(constant)?);
 
     let return_type = Type::from(value);
     Ok((value_bytecode, return_type))
@@ -945,50 +2442,44 @@ fn value(_ctx: &mut Context, value: &Value) -> Result<(Vec<u8>, Type)> {
 
 /// Resolves a local variable. Since scope.locals will match the runtime stack,
 /// we grab the index by the provided name and emit <Get><stack-index>.
-fn var(ctx: &mut Context, ident: &String) -> Result<(Vec<u8>, Type)> {
+fn var(ctx: &mut Context, ident: &String) -> Result<(Bytecode, Type)> {
     // Find a local var with the right name, get its index
-    let mut local_variable = ctx.get_local(ident);
+    let local_variable = ctx.get_local(ident);
 
     // If not found, check the <prelude> module as well. It has no stack offset since its.. the prelude.
-    if local_variable.is_none() {
-        let prelude = ctx
-            .get_module(&PathBuf::from("<prelude>"))
-            .expect("what no prelude");
-        for (i, local) in prelude.locals.iter().enumerate() {
-            if local.name == *ident {
-                local_variable = Some((i, local.to_owned()));
-                break;
-            }
+    // The prelude itself is compiled before it exists as a module.
+    if local_variable.is_none()
+        && let Some(prelude) = ctx.get_module(&PathBuf::from("<prelude>"))
+        && let Some((i, local)) = prelude
+            .locals
+            .iter()
+            .enumerate()
+            .find(|(_, local)| local.name == *ident)
+    {
+        if let Ok(idx) = u8::try_from(i) {
+            return Ok((vec![Op::GetGlobal as u8, idx].into(), local.r#type.clone()));
         }
+        return Err(Error::unsupported("too many local variables in module").into());
     }
 
     if let Some(local_variable) = local_variable {
         if let Ok(idx) = u8::try_from(local_variable.0) {
             let return_type = local_variable.1.r#type.clone();
-            return Ok((vec![Op::Get as u8, idx], return_type));
+            return Ok((vec![Op::Get as u8, idx].into(), return_type));
         }
-        unreachable!()
+        Err(Error::unsupported("too many local variables in scope").into())
     } else if let Some((scope, idx)) = ctx.resolve_generic_function(ident) {
-        return Ok((vec![], Type::GenericFunctionStub { scope, idx }));
+        return Ok((Bytecode::new(), Type::GenericFunctionStub { scope, idx }));
     } else if let Some((idx, return_type)) = ctx.resolve_upvalue(ident, ctx.scopes.len() - 1)? {
-        return Ok((vec![Op::GetUpvalue as u8, idx as u8], return_type));
+        return Ok((vec![Op::GetUpvalue as u8, idx as u8].into(), return_type));
     } else if let Some((idx, return_type)) = ctx.get_builtin(ident) {
-        return Ok((vec![Op::GetBuiltin as u8, idx as u8], return_type));
+        return Ok((vec![Op::GetBuiltin as u8, idx as u8].into(), return_type));
     } else {
-        dbg!(ident);
-        todo!();
-        // If we dont find a variable with this name, it may be an Enum type
-        // used in an Entry/Call expression.
-        // if let e @ Type::Enum(_) = resolve_type_alias(
-        //     &mut program.compiler_stack,
-        //     compiler,
-        //     &Type::Custom(ident.clone()),
-        // ) {
-        //     return_type = e;
-        //     // We dont emit any bytecode for this, its only used by the type checker.
-        // } else {
-        //     dbg!("unresolved var with ident", ident);
-        //     todo!();
-        // }
+        Err(Error::new(
+            "unknown_variable",
+            format!("cannot find variable `{}`", ident),
+        )
+        .with_label("not found in this scope")
+        .into())
     }
 }
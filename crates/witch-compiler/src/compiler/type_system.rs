@@ -11,7 +11,7 @@
 use crate::error::{Error, Result};
 use anyhow::anyhow;
 use std::collections::HashMap;
-use witch_parser::types::Type;
+use witch_parser::types::{EnumVariant, Type};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct TypeSystem {
@@ -32,10 +32,19 @@ impl Default for TypeSystem {
 impl TypeSystem {
     pub fn new() -> Self {
         Self {
-            types: vec![(
-                "List".to_string(),
-                Type::List(Box::new(Type::TypeVar("T".to_string()))),
-            )]
+            types: vec![
+                (
+                    "List".to_string(),
+                    Type::List(Box::new(Type::TypeVar("T".to_string()))),
+                ),
+                (
+                    "Map".to_string(),
+                    Type::Map(
+                        Box::new(Type::TypeVar("K".to_string())),
+                        Box::new(Type::TypeVar("V".to_string())),
+                    ),
+                ),
+            ]
             .into_iter()
             .collect(),
             substitutions: vec![HashMap::default()],
@@ -44,9 +53,13 @@ impl TypeSystem {
 
     /// Adds a type to the type library, unless it already exists
     pub fn add_type(&mut self, name: String, typ: Type) -> Result<()> {
-        self.types
-            .try_insert(name, typ)
-            .map_err(|_| Error::fatal())?;
+        self.types.try_insert(name.clone(), typ).map_err(|_| {
+            Error::new(
+                "duplicate_type",
+                format!("type `{}` is already defined", name),
+            )
+            .with_label("redefined here")
+        })?;
         Ok(())
     }
 
@@ -103,7 +116,17 @@ impl TypeSystem {
                 generics,
                 is_method,
             } => {
-                self.push_scope(generics.clone().into_iter().collect());
+                // Unconstrained generics stay type variables, which each call binds to the types
+                // of its arguments
+                self.push_scope(
+                    generics
+                        .iter()
+                        .map(|(name, ty)| match ty {
+                            Type::Any => (name.clone(), Type::TypeVar(name.clone())),
+                            ty => (name.clone(), ty.clone()),
+                        })
+                        .collect(),
+                );
                 let args: Vec<Type> = args
                     .into_iter()
                     .map(|t| self.resolve(t))
@@ -127,6 +150,11 @@ impl TypeSystem {
                 methods,
                 generics,
             } => {
+                // The types of the generics may refer to the generics of an outer scope
+                let generics = generics
+                    .into_iter()
+                    .map(|(n, t)| self.resolve(t).map(|t| (n, t)))
+                    .collect::<Result<Vec<(String, Type)>>>()?;
                 self.push_scope(generics.clone().into_iter().collect());
                 let fields = fields
                     .into_iter()
@@ -145,6 +173,43 @@ impl TypeSystem {
                 })
             }
 
+            Type::Enum {
+                name,
+                variants,
+                methods,
+                generics,
+            } => {
+                let generics = generics
+                    .into_iter()
+                    .map(|(n, t)| self.resolve(t).map(|t| (n, t)))
+                    .collect::<Result<Vec<(String, Type)>>>()?;
+                self.push_scope(generics.clone().into_iter().collect());
+                let variants = variants
+                    .into_iter()
+                    .map(|variant| {
+                        let types = variant
+                            .types
+                            .map(|types| types.into_iter().map(|t| self.resolve(t)).collect())
+                            .transpose()?;
+                        Ok(EnumVariant { types, ..variant })
+                    })
+                    .collect::<Result<Vec<EnumVariant>>>()?;
+                self.pop_scope();
+                Ok(Type::Enum {
+                    name,
+                    variants,
+                    methods,
+                    generics,
+                })
+            }
+
+            Type::List(item) => Ok(Type::List(Box::new(self.resolve(*item)?))),
+
+            Type::Map(key, value) => Ok(Type::Map(
+                Box::new(self.resolve(*key)?),
+                Box::new(self.resolve(*value)?),
+            )),
+
             Type::TypeVar(name) => {
                 // Look through substitution table first, then check our types library
                 if let Some(typ) = self.substitutions.last().unwrap().get(&name) {
@@ -152,8 +217,10 @@ impl TypeSystem {
                 }
 
                 return self.types.get(&name).cloned().ok_or_else(|| {
-                    dbg!(name);
-                    anyhow!(Error::fatal())
+                    anyhow!(
+                        Error::new("unknown_type", format!("cannot find type `{}`", name))
+                            .with_label("unknown type")
+                    )
                 });
             }
 
@@ -165,7 +232,7 @@ impl TypeSystem {
                         generics,
                     } => {
                         if subs.len() != generics.len() {
-                            panic!("generic params must be of same length as available generics");
+                            return Err(wrong_generics_count(generics.len(), subs.len()));
                         }
 
                         let generics = generics
@@ -192,8 +259,7 @@ impl TypeSystem {
                     } => {
                         // Todo how to handle nested generics???? Foo[A, B, C, D] where A: B[C, D] { thing: A }
                         if subs.len() != generics.len() {
-                            dbg!(&subs, &generics);
-                            panic!("generic params must be of same length as available generics");
+                            return Err(wrong_generics_count(generics.len(), subs.len()));
                         }
 
                         let generics = generics
@@ -209,13 +275,49 @@ impl TypeSystem {
                             generics,
                         })
                     }
+                    Type::Enum {
+                        name,
+                        variants,
+                        methods,
+                        generics,
+                    } => {
+                        if subs.len() != generics.len() {
+                            return Err(wrong_generics_count(generics.len(), subs.len()));
+                        }
+
+                        let generics = generics
+                            .into_iter()
+                            .zip(subs)
+                            .map(|((n, _), t)| (n, t))
+                            .collect();
+
+                        self.resolve(Type::Enum {
+                            name,
+                            variants,
+                            methods,
+                            generics,
+                        })
+                    }
                     Type::List(_) => {
                         if subs.len() != 1 {
-                            panic!("list type only allows one single generic param");
+                            return Err(wrong_generics_count(1, subs.len()));
                         }
                         Ok(Type::List(Box::new(self.resolve(subs[0].clone())?)))
                     }
-                    x => panic!("this type dont allow generics: {:?}", x),
+                    Type::Map(..) => {
+                        if subs.len() != 2 {
+                            return Err(wrong_generics_count(2, subs.len()));
+                        }
+                        Ok(Type::Map(
+                            Box::new(self.resolve(subs[0].clone())?),
+                            Box::new(self.resolve(subs[1].clone())?),
+                        ))
+                    }
+                    x => Err(anyhow!(Error::new(
+                        "unexpected_generics",
+                        format!("`{}` does not take generic parameters", x),
+                    )
+                    .with_label("unexpected generic parameters"))),
                 }
             }
 
@@ -229,6 +331,14 @@ impl TypeSystem {
     }
 }
 
+fn wrong_generics_count(expected: usize, found: usize) -> anyhow::Error {
+    anyhow!(Error::new(
+        "wrong_generics_count",
+        format!("expected {} generic parameters, found {}", expected, found),
+    )
+    .with_label(format!("expected {} generic parameters", expected)))
+}
+
 #[cfg(test)]
 mod tests {
 
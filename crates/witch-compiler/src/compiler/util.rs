@@ -1,7 +1,13 @@
-use crate::error::Result;
-use witch_runtime::value::Value;
+use crate::error::{Error, Result};
+use witch_runtime::image::{self, OPERAND_SIZE};
 
-/// Serializes a Value into bytecode
-pub fn serialize_value<'a>(value: Value) -> Result<Vec<u8>> {
-    Ok(bincode::serde::encode_to_vec(value, bincode::config::legacy()).unwrap())
+/// Encodes an instruction operand, which has a fixed width regardless of the target.
+pub fn operand(value: usize) -> Result<[u8; OPERAND_SIZE]> {
+    image::operand(value).ok_or_else(|| {
+        Error::new(
+            "limit_exceeded",
+            format!("{} does not fit into a bytecode operand", value),
+        )
+        .into()
+    })
 }
@@ -1,12 +1,23 @@
-use std::{fmt::Display, ops::Range};
+use std::{fmt::Display, ops::Range, path::Path};
+
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceCode};
 
 pub type Result<T> = anyhow::Result<T>;
 
+/// A compilation error which can be rendered as a `miette` diagnostic.
+/// Errors are usually raised without a span, in which case `compile` attaches the span
+/// of the AST node being compiled, and without source code, which gets attached once the
+/// error bubbles up to the module being compiled.
 #[derive(Debug)]
 pub struct Error {
     msg: String,
-    span: Range<usize>,
-    source: String,
+    code: &'static str,
+    span: Option<Range<usize>>,
+    label: Option<String>,
+    /// Further spans worth pointing at, along with their labels
+    secondary_labels: Vec<(Range<usize>, String)>,
+    help: Option<String>,
+    source: Option<NamedSource>,
 }
 
 impl Display for Error {
@@ -17,21 +28,95 @@ impl Display for Error {
 
 impl std::error::Error for Error {}
 
+impl Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(format!("witch::{}", self.code)))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.help
+            .as_ref()
+            .map(|help| Box::new(help) as Box<dyn Display>)
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.source.as_ref().map(|source| source as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let span = self.span.clone()?;
+        let primary = LabeledSpan::new_with_span(self.label.clone(), span);
+        let secondary = self
+            .secondary_labels
+            .iter()
+            .map(|(span, label)| LabeledSpan::new_with_span(Some(label.clone()), span.clone()));
+        Some(Box::new(std::iter::once(primary).chain(secondary)))
+    }
+}
+
 impl Error {
-    pub fn new(msg: &str, span: Range<usize>, source: &str) -> Self {
+    /// Creates an error with the given code, which gets prefixed with `witch::`.
+    pub fn new(code: &'static str, msg: impl Into<String>) -> Self {
         Self {
-            msg: msg.to_string(),
-            // span.start..span.end is copy, but span is only clone. whyyy
-            span: span.start..span.end,
-            source: source[span.start..span.end].to_string(),
+            msg: msg.into(),
+            code,
+            span: None,
+            label: None,
+            secondary_labels: vec![],
+            help: None,
+            source: None,
         }
     }
 
+    pub fn with_span(mut self, span: Option<Range<usize>>) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Points at another span, which is only shown along with the span of the error.
+    pub fn with_secondary_label(mut self, span: Range<usize>, label: impl Into<String>) -> Self {
+        self.secondary_labels.push((span, label.into()));
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn has_span(&self) -> bool {
+        self.span.is_some()
+    }
+
+    /// Attaches the source code of the module the error occurred in.
+    pub fn in_module(mut self, path: &Path, source: &str) -> Self {
+        self.source = Some(NamedSource::new(path.to_string_lossy(), source.to_string()));
+        self
+    }
+
     pub fn fatal() -> Self {
-        Self {
-            msg: "Something terrible happened. Code: 4545345".to_string(),
-            span: 0..0,
-            source: "".to_string(),
-        }
+        Self::new("fatal", "Something terrible happened. Code: 4545345")
+    }
+
+    /// A value of one type was used where another type was expected.
+    pub fn type_mismatch(expected: impl Display, found: impl Display) -> Self {
+        Self::new(
+            "type_mismatch",
+            format!(
+                "mismatched types: expected `{}`, found `{}`",
+                expected, found
+            ),
+        )
+        .with_label(format!("expected `{}`", expected))
+    }
+
+    /// A construct which the compiler does not support (yet).
+    pub fn unsupported(msg: impl Into<String>) -> Self {
+        Self::new("unsupported", msg).with_label("not supported")
     }
 }
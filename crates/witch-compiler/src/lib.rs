@@ -3,7 +3,9 @@
 //! can execute.
 //!
 //! It exports the `compile` function, which either returns the final bytecode or
-//! an error which can be written to stderr.
+//! an error which can be written to stderr, and `check`, which stops after type checking. Errors raised while parsing or compiling
+//! can be turned into a `miette::Diagnostic` with `diagnostic`, which renders them
+//! as annotated source snippets.
 #![feature(type_alias_impl_trait)]
 #![feature(iter_advance_by)]
 #![feature(assert_matches)]
@@ -12,6 +14,7 @@
 
 use anyhow::Context as AContext;
 use anyhow::Result;
+use miette::Diagnostic;
 
 use compiler::context::Context;
 use witch_parser::types::Type;
@@ -24,33 +27,83 @@ use std::{
 };
 
 use witch_parser::Parser;
+use witch_runtime::image::{FunctionEntry, Image};
+use witch_runtime::source_map::{FunctionInfo, Location, SourceMap};
 
+use crate::compiler::bytecode::Bytecode;
 use crate::compiler::context::Module;
 use crate::compiler::LocalVariable;
 use crate::module::resolve_dependencies;
 
 mod compiler;
-mod error;
+pub mod error;
 mod module;
+pub mod session;
 
-/// Takes a Witch source file and compiles it to bytecode, or returns `error::Error`.
+pub use session::Session;
+
+/// Takes a Witch source file and compiles it to an encoded bytecode `Image`, or returns `error::Error`.
 pub fn compile(file_path: PathBuf) -> Result<Vec<u8>> {
+    let (bc, _) = compile_with_source_map(file_path)?;
+    Ok(bc)
+}
+
+/// Like `compile`, but also returns a `SourceMap` which lets the runtime map instructions back to
+/// the Witch source they were compiled from.
+pub fn compile_with_source_map(file_path: PathBuf) -> Result<(Vec<u8>, SourceMap)> {
+    let (root_path, source) = resolve_file(None, file_path)?;
+    let mut parser = Parser::new(&source);
+    let module = parser.module(root_path.clone())?;
+
+    let mut modules = vec![prelude()];
+    resolve_dependencies(module, &mut modules);
+
+    let (image, source_map, _) = compile_modules(&modules, true)?;
+    let bc = image.encode().map_err(|kind| {
+        error::Error::new(
+            "limit_exceeded",
+            format!("failed to encode the bytecode image: {}", kind),
+        )
+    })?;
+    Ok((bc, source_map))
+}
+
+/// Parses and type checks a Witch source file and its imports. Types are checked while compiling,
+/// so this still compiles each module, but it stops before assembling and encoding an image.
+pub fn check(file_path: PathBuf) -> Result<()> {
     let (root_path, source) = resolve_file(None, file_path)?;
     let mut parser = Parser::new(&source);
-    let module = parser.module(root_path.clone()).unwrap();
+    let module = parser.module(root_path)?;
 
     let mut modules = vec![prelude()];
     resolve_dependencies(module, &mut modules);
 
-    let mut bc = vec![];
+    compile_modules(&modules, false)?;
+    Ok(())
+}
+
+/// The compiled modules a program (or REPL session) builds upon, along with the types they export.
+pub(crate) struct Library {
+    pub modules: Vec<(PathBuf, Module)>,
+    pub types: HashMap<String, Type>,
+}
+
+/// Compiles modules in order, each of which may only depend on the modules before it.
+/// Unless `emit` is set, only their types are checked and the image and source map stay empty.
+fn compile_modules(
+    modules: &[witch_parser::Module],
+    emit: bool,
+) -> Result<(Image, SourceMap, Library)> {
+    let mut image = Image::default();
+    let mut source_map = SourceMap::default();
     let mut module_library = vec![];
     let mut imported_types: HashMap<String, Type> = HashMap::default();
     for module in modules.iter() {
         let mut ctx = Context::new(module.path.clone(), &module_library);
+        ctx.constants = std::mem::take(&mut image.constants);
 
-        if !imported_types.is_empty() {
-            ctx.ts.types = imported_types.clone();
-        }
+        // Builtin types such as `List` stay available next to the imported ones
+        ctx.ts.types.extend(imported_types.clone());
 
         for (mod_path, _) in module.imports.iter() {
             ctx.scope()?.locals.push(LocalVariable {
@@ -62,7 +115,12 @@ pub fn compile(file_path: PathBuf) -> Result<Vec<u8>> {
             });
         }
 
-        let (mut bytecode, _) = compiler::compile(&mut ctx, &module.ast)?;
+        let (mut bytecode, _) = compiler::compile(&mut ctx, &module.ast).map_err(|err| {
+            match err.downcast::<error::Error>() {
+                Ok(err) => err.in_module(&module.path, &module.source).into(),
+                Err(err) => err,
+            }
+        })?;
 
         let mod_name = module
             .path
@@ -72,10 +130,20 @@ pub fn compile(file_path: PathBuf) -> Result<Vec<u8>> {
             .to_string();
         for (name, typ) in ctx.ts.types.iter() {
             imported_types.insert(format!("{}.{}", mod_name, name), typ.clone());
+
+            // Types of the prelude, such as `Option`, can be used without naming the module
+            if module.path == Path::new("<prelude>") {
+                imported_types.insert(name.clone(), typ.clone());
+            }
         }
 
-        bytecode = [ctx.flush(), bytecode].concat();
-        bc.append(&mut bytecode);
+        if emit {
+            let mut module_bytecode = ctx.flush()?;
+            module_bytecode.append(&mut bytecode);
+            add_to_source_map(&mut source_map, module, image.code.len(), &module_bytecode);
+            add_to_image(&mut image, module_bytecode);
+        }
+        image.constants = std::mem::take(&mut ctx.constants);
         module_library.push((
             module.path.clone(),
             Module {
@@ -94,7 +162,81 @@ pub fn compile(file_path: PathBuf) -> Result<Vec<u8>> {
         ));
     }
 
-    Ok(bc)
+    Ok((
+        image,
+        source_map,
+        Library {
+            modules: module_library,
+            types: imported_types,
+        },
+    ))
+}
+
+/// Returns the diagnostic of a parser or compiler error, if it is one.
+pub fn diagnostic(err: &anyhow::Error) -> Option<&dyn Diagnostic> {
+    if let Some(err) = err.downcast_ref::<error::Error>() {
+        return Some(err);
+    }
+    if let Some(err) = err.downcast_ref::<witch_parser::error::Error>() {
+        return Some(err);
+    }
+    None
+}
+
+/// Adds the debug information of a module's bytecode to the source map, given the offset at which
+/// the module's bytecode starts.
+pub(crate) fn add_to_source_map(
+    source_map: &mut SourceMap,
+    module: &witch_parser::Module,
+    offset: usize,
+    bytecode: &Bytecode,
+) {
+    let file = source_map.files.len();
+    source_map
+        .files
+        .push(module.path.to_string_lossy().to_string());
+
+    for (range, span) in bytecode.spans.iter() {
+        let (line, column) = line_and_column(&module.source, span.start);
+        source_map.locations.push(Location {
+            bytecode: range.start + offset..range.end + offset,
+            file,
+            span: span.clone(),
+            line,
+            column,
+        });
+    }
+
+    for function in bytecode.functions.iter() {
+        if let Some(name) = &function.name {
+            source_map.functions.push(FunctionInfo {
+                bytecode: function.range.start + offset..function.range.end + offset,
+                name: name.clone(),
+            });
+        }
+    }
+}
+
+/// Appends the instructions of a chunk to the code of the image, and its functions to the
+/// function table.
+pub(crate) fn add_to_image(image: &mut Image, bytecode: Bytecode) {
+    let offset = image.code.len();
+    for function in bytecode.functions.iter() {
+        image.functions.push(FunctionEntry {
+            addr: function.range.start + offset,
+            len: function.range.len(),
+            arity: function.arity,
+        });
+    }
+    image.code.append(&mut bytecode.into_bytes());
+}
+
+/// Returns the 1-based line and column of the byte offset `pos` within `source`.
+fn line_and_column(source: &str, pos: usize) -> (usize, usize) {
+    let before = &source[..pos.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    (line, column)
 }
 
 /// Canonicalizes a file path from our `start_path`, returning the new path as well as the file contents.
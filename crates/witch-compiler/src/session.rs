@@ -0,0 +1,147 @@
+//! Incremental compilation for interactive sessions.
+//!
+//! A `Session` compiles one snippet of source at a time against the state left behind by the
+//! previous snippets, so that variables, functions and types declared in one entry can be used in
+//! the next. The bytecode of each snippet is meant to be appended to the program of a `Vm` which
+//! is kept alive between entries, see `Vm::eval`.
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use witch_parser::Parser;
+use witch_runtime::image::Image;
+use witch_runtime::source_map::SourceMap;
+use witch_runtime::value::Value;
+use witch_std::prelude;
+
+use crate::compiler::context::{Cached, Context, Module, Scope};
+use crate::compiler::type_system::TypeSystem;
+use crate::error::Error;
+use crate::{add_to_image, add_to_source_map, compile_modules};
+
+/// The name under which snippets show up in diagnostics and backtraces.
+const REPL: &str = "<repl>";
+
+pub struct Session {
+    /// The modules every snippet can use, which for now is only the prelude
+    library: Vec<(PathBuf, Module)>,
+    ts: TypeSystem,
+    scope: Scope,
+    functions_cache: Vec<Cached>,
+    source_map: SourceMap,
+
+    /// The constant pool of every snippet compiled so far
+    constants: Vec<Value>,
+
+    /// The amount of bytecode handed out so far, which is where the next snippet will start
+    len: usize,
+}
+
+/// The compiled bytecode of a single entry, along with the compiler state after compiling it.
+/// The state only becomes part of the session once the snippet is passed to `Session::commit`.
+pub struct Snippet {
+    /// The compiled snippet, whose constant pool only holds the constants which were added
+    /// by the snippet. They are meant to be appended to the pool of previous snippets.
+    pub image: Image,
+
+    /// The number of global variables on the stack once the snippet has run. If there is
+    /// anything above them, it is the value of the snippet's last expression.
+    pub locals: usize,
+
+    ts: TypeSystem,
+    scope: Scope,
+}
+
+impl Session {
+    /// Starts a new session, returning it along with the image of the prelude which has to run
+    /// before any snippet.
+    pub fn new() -> Result<(Self, Image)> {
+        let (image, source_map, library) = compile_modules(&[prelude()], true)?;
+        let mut ctx = Context::new(PathBuf::from(REPL), &library.modules);
+        ctx.ts.types.extend(library.types);
+        let ts = ctx.ts;
+        let session = Self {
+            library: library.modules,
+            ts,
+            scope: Scope::default(),
+            functions_cache: vec![],
+            source_map,
+            constants: image.constants.clone(),
+            len: image.code.len(),
+        };
+        Ok((session, image))
+    }
+
+    /// Compiles a snippet of source. Returns `None` if the source ended before it was complete,
+    /// such as within an unclosed block, in which case the caller should ask for more input.
+    /// A snippet which fails to compile leaves the session untouched. Otherwise its functions,
+    /// constants and source map entries are kept straight away, as they are part of the program
+    /// once the snippet is appended to it, while its variables and types wait for `commit`.
+    pub fn compile(&mut self, source: &str) -> Result<Option<Snippet>> {
+        let path = PathBuf::from(REPL);
+        let (module, errors) = Parser::new(source).module_with_errors(path.clone());
+        let end = source.trim_end().len();
+        if errors.iter().any(|err| err.span().start >= end) {
+            return Ok(None);
+        }
+        if let Some(err) = witch_parser::error::Error::combine(errors) {
+            return Err(err.into());
+        }
+        let module = module.ok_or_else(|| Error::fatal().in_module(&path, source))?;
+        if !module.imports.is_empty() {
+            return Err(Error::unsupported("imports are not supported in the REPL")
+                .with_span(Some(0..end))
+                .in_module(&path, source)
+                .into());
+        }
+
+        let mut ctx = Context::new(path.clone(), &self.library);
+        ctx.ts = self.ts.clone();
+        ctx.scopes = vec![self.scope.clone()];
+        ctx.functions_cache = self.functions_cache.clone();
+        ctx.constants = self.constants.clone();
+
+        let (mut bytecode, _) = crate::compiler::compile(&mut ctx, &module.ast)
+            .map_err(|err| in_repl(err, &path, source))?;
+        let scope = ctx.scope()?.clone();
+
+        // Functions are set up as soon as the snippet runs, so they are kept even if it fails
+        let mut snippet_bytecode = ctx.flush()?;
+        snippet_bytecode.append(&mut bytecode);
+        self.functions_cache = std::mem::take(&mut ctx.functions_cache);
+
+        add_to_source_map(&mut self.source_map, &module, self.len, &snippet_bytecode);
+        self.len += snippet_bytecode.len();
+
+        let mut image = Image {
+            constants: ctx.constants.split_off(self.constants.len()),
+            ..Default::default()
+        };
+        self.constants.extend(image.constants.iter().cloned());
+        add_to_image(&mut image, snippet_bytecode);
+
+        Ok(Some(Snippet {
+            image,
+            locals: ctx.stack_offset() + scope.locals.len(),
+            ts: ctx.ts,
+            scope,
+        }))
+    }
+
+    /// Keeps the variables and types declared by a snippet which ran successfully.
+    pub fn commit(&mut self, snippet: Snippet) {
+        self.ts = snippet.ts;
+        self.scope = snippet.scope;
+    }
+
+    /// The source map of the prelude and every snippet compiled so far.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+}
+
+fn in_repl(err: anyhow::Error, path: &Path, source: &str) -> anyhow::Error {
+    match err.downcast::<Error>() {
+        Ok(err) => err.in_module(path, source).into(),
+        Err(err) => err,
+    }
+}
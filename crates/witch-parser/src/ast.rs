@@ -90,6 +90,11 @@ impl Operator {
 pub enum Ast {
     Nop,
 
+    // A statement which failed to parse. The error itself is recorded by the parser.
+    Error {
+        span: Range<usize>,
+    },
+
     // A compiler directive
     Annotation {
         name: String,
@@ -115,6 +120,8 @@ pub enum Ast {
         is_variadic: bool,
         args: Vec<(String, Type)>,
         returns: Type,
+        /// Where the return type is written, unless it is inferred
+        returns_span: Option<Range<usize>>,
         body: Box<Self>,
         generics: Vec<(String, Type)>,
     },
@@ -141,7 +148,10 @@ pub enum Ast {
     },
 
     // Resolves a variable by name.
-    Var(String),
+    Var {
+        ident: String,
+        span: Range<usize>,
+    },
 
     // A member access expression allows us to access entries within objects,
     // such as items in lists, functions in modules, variants in enums or fields in structs.
@@ -164,6 +174,20 @@ pub enum Ast {
         span: Range<usize>,
     },
 
+    // Resolves pairs of key and value expressions into a map, e.g.
+    // { "one": 1, "two": get2() }
+    Map {
+        entries: Vec<(Self, Self)>,
+        span: Range<usize>,
+    },
+
+    // Joins string literals and embedded expressions into a single string, e.g.
+    // "hello {name}, you are {age}"
+    Interpolation {
+        segments: Vec<Self>,
+        span: Range<usize>,
+    },
+
     // Expresses a binary operation, such as 1 <op> 1.
     Infix {
         lhs: Box<Self>,
@@ -172,6 +196,27 @@ pub enum Ast {
         span: Range<usize>,
     },
 
+    // Expresses a unary operation, such as !a or -a.
+    Prefix {
+        op: Operator,
+        rhs: Box<Self>,
+        span: Range<usize>,
+    },
+
+    /// Converts a number to another numeric type, e.g. `x as i32`
+    Cast {
+        expr: Box<Self>,
+        to: Type,
+        span: Range<usize>,
+    },
+
+    /// Evaluates to the value of an `Ok` result, or returns an `Err` from the current function,
+    /// e.g. `parse(input)?`
+    Try {
+        expr: Box<Self>,
+        span: Range<usize>,
+    },
+
     /// Calls a function expresion with the provided values as arguments.
     Call {
         expr: Box<Self>,
@@ -187,7 +232,10 @@ pub enum Ast {
     },
 
     // A block is a wrapper around a number of expressions with their own scope.
-    Block(Box<Spanned<Self>>),
+    Block {
+        expr: Box<Self>,
+        span: Range<usize>,
+    },
 
     // An if expression conditionally runs the `then_` or `else_` branch depending on the predicate expression.
     If {
@@ -197,17 +245,31 @@ pub enum Ast {
         span: Range<usize>,
     },
 
-    // Expresses a `while` loop. (Condition, Block)
+    // Expresses a `while` loop, which runs `expr` for as long as the predicate holds.
+    // `loop` and `for ... in` loops are desugared into `while` loops by the parser.
     While {
-        predicate: Box<Spanned<Self>>,
-        expr: Box<Spanned<Self>>,
+        predicate: Box<Self>,
+        expr: Box<Self>,
+        span: Range<usize>,
+    },
+
+    // Matches a value against the pattern of each arm in turn, and evaluates to the body of the
+    // first arm which fits.
+    Match {
+        expr: Box<Self>,
+        arms: Vec<MatchArm>,
+        span: Range<usize>,
     },
 
     // Breaks the current loop
-    Break,
+    Break {
+        span: Range<usize>,
+    },
 
     // Jumps to the next iteration of the current loop
-    Continue,
+    Continue {
+        span: Range<usize>,
+    },
 
     // A type declaration. Registers a custom type with the compiler.
     Type {
@@ -222,3 +284,74 @@ pub enum Ast {
         expr: Box<Spanned<Self>>,
     },
 }
+
+impl Ast {
+    /// The span of source text this node was parsed from, if it keeps track of one.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Ast::Annotation { span, .. }
+            | Ast::Assignment { span, .. }
+            | Ast::Import { span, .. }
+            | Ast::Let { span, .. }
+            | Ast::Struct { span, .. }
+            | Ast::Var { span, .. }
+            | Ast::Member { span, .. }
+            | Ast::Return { span, .. }
+            | Ast::List { span, .. }
+            | Ast::Map { span, .. }
+            | Ast::Interpolation { span, .. }
+            | Ast::Infix { span, .. }
+            | Ast::Prefix { span, .. }
+            | Ast::Cast { span, .. }
+            | Ast::Try { span, .. }
+            | Ast::Call { span, .. }
+            | Ast::Statement { span, .. }
+            | Ast::If { span, .. }
+            | Ast::Type { span, .. }
+            | Ast::Block { span, .. }
+            | Ast::While { span, .. }
+            | Ast::Match { span, .. }
+            | Ast::Break { span }
+            | Ast::Continue { span }
+            | Ast::Error { span } => Some(span.clone()),
+            Ast::Mod { expr, .. } => Some(expr.0 .1.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// An arm of a `match` expression: `pattern if guard -> body`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Ast>,
+    pub body: Ast,
+    pub span: Range<usize>,
+}
+
+/// What a value is matched against in a `match` arm.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    // `_` matches anything
+    Wildcard,
+
+    // Matches anything and binds it to a variable. If the name is one of the variants of the
+    // matched enum, it matches that variant instead.
+    Binding(String),
+
+    // A literal value, e.g. `1`, `"a"` or `true`
+    Literal(Value),
+
+    // An enum variant, e.g. `Some(x)`, or `Option.Some(x)` if qualified with the enum name
+    Variant {
+        enum_name: Option<String>,
+        name: String,
+        fields: Vec<Pattern>,
+    },
+
+    // A struct with patterns for some of its fields, e.g. `Point { x, y: 0 }`
+    Struct {
+        name: String,
+        fields: Vec<(String, Pattern)>,
+    },
+}
@@ -1,30 +1,135 @@
-use std::ops::Range;
+use std::{fmt::Display, ops::Range, path::Path};
+
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceCode};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+const UNNAMED: &str = "<input>";
+
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct Error {
     msg: String,
+    code: &'static str,
     span: Range<usize>,
-    source: String,
+    source: Box<NamedSource>,
+
+    /// Further errors which the parser recovered from after this one
+    related: Vec<Error>,
+
+    /// The IO error behind an import which could not be read
+    cause: Option<std::io::Error>,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause
+            .as_ref()
+            .map(|cause| cause as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(format!("witch::{}", self.code)))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(self.source.as_ref())
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(Box::new(std::iter::once(LabeledSpan::new_with_span(
+            Some("here".to_string()),
+            self.span.clone(),
+        ))))
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        if self.related.is_empty() {
+            return None;
+        }
+        Some(Box::new(self.related.iter().map(|e| e as &dyn Diagnostic)))
+    }
 }
 
 impl Error {
     pub fn new(msg: &str, span: Range<usize>, source: &str) -> Self {
         Self {
             msg: msg.to_string(),
-            // span.start..span.end is copy, but span is only clone. whyyy
-            span: span.start..span.end,
-            source: source[span.start..span.end].to_string(),
+            code: "parse",
+            span,
+            source: Box::new(NamedSource::new(UNNAMED, source.to_string())),
+            related: vec![],
+            cause: None,
+        }
+    }
+
+    /// Replaces the default `parse` code, which gets prefixed with `witch::`.
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn with_cause(mut self, cause: std::io::Error) -> Self {
+        self.cause = Some(cause);
+        self
+    }
+
+    /// Names the source the error occurred in after the module it was parsed from.
+    /// Errors which already carry the name of an imported module are left untouched.
+    pub fn in_module(self, path: &Path, source: &str) -> Self {
+        if self.source.name() != UNNAMED {
+            return self;
+        }
+        Self {
+            source: Box::new(NamedSource::new(path.to_string_lossy(), source.to_string())),
+            related: self
+                .related
+                .into_iter()
+                .map(|e| e.in_module(path, source))
+                .collect(),
+            ..self
         }
     }
 
+    /// Combines a list of errors into the first one, with the rest as its related errors.
+    pub fn combine(mut errors: Vec<Error>) -> Option<Self> {
+        if errors.is_empty() {
+            return None;
+        }
+        let mut first = errors.remove(0);
+        first.related.extend(errors);
+        Some(first)
+    }
+
+    /// The error itself followed by its related errors.
+    pub fn flatten(mut self) -> Vec<Error> {
+        let related = std::mem::take(&mut self.related);
+        std::iter::once(self).chain(related).collect()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
     pub fn fatal() -> Self {
         Self {
             msg: "Something terrible happened. Code: 4545345".to_string(),
+            code: "parse",
             span: 0..0,
-            source: "".to_string(),
+            source: Box::new(NamedSource::new(UNNAMED, String::new())),
+            related: vec![],
+            cause: None,
         }
     }
 }
@@ -1,18 +1,25 @@
 use crate::types::Type;
-use crate::{ast::Key, error::Result};
+use crate::{
+    ast::Key,
+    error::{Error, Result},
+};
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::ops::Range;
 use witch_runtime::value::Value;
 
 use super::{
-    ast::{Ast, Operator},
+    ast::{Ast, MatchArm, Operator, Pattern},
     either,
-    lexer::{Kind, Lexer},
+    lexer::{scan_string, Kind, Lexer, Segment},
+    literal,
     r#type::{properties, type_literal},
     statement::statement,
     Parser,
 };
 
+/// The binding power of `as`, see `Operator::infix_binding`.
+const CAST_BINDING: u8 = 13;
+
 pub fn expression<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
     expression_inner(p, 0)
 }
@@ -21,35 +28,34 @@ pub fn expression_inner<'input>(
     p: &mut Parser<'input, Lexer<'input>>,
     binding_power: u8,
 ) -> Result<Ast> {
-    let start = p.cursor;
+    // Spans start at the first token rather than the cursor, which precedes any whitespace
+    let start = p.peek_span().start;
     let mut expr = match p.peek() {
-        Some(lit @ Kind::Int)
-        | Some(lit @ Kind::String)
-        | Some(lit @ Kind::CString)
-        | Some(lit @ Kind::Float) => {
-            let token = p.consume(&lit)?;
-            let txt = p.text(&token);
-            match lit {
-                Kind::Int => Ast::Value(Value::Usize(txt.parse().expect("invalid integer"))),
-                Kind::Float => Ast::Value(Value::F32(txt.parse().expect("invalid 32bit float"))),
-                Kind::String => Ast::Value(Value::String(
-                    txt.strip_suffix('\"')
-                        .unwrap()
-                        .strip_prefix('\"')
-                        .unwrap()
-                        .to_string(),
-                )),
-                Kind::CString => Ast::Value(Value::CString(
-                    CString::new(
-                        txt.strip_suffix('\"')
-                            .unwrap()
-                            .strip_prefix("c\"")
-                            .unwrap()
-                            .to_string(),
-                    )
-                    .expect("bad string to cstring conversion"),
-                )),
-                _ => unreachable!(),
+        Some(
+            Kind::Int
+            | Kind::String
+            | Kind::CString
+            | Kind::RawString
+            | Kind::Float
+            | Kind::KwTrue
+            | Kind::KwFalse,
+        ) => Ast::Value(literal(p)?),
+        Some(Kind::InterpolatedString) => interpolated_string(p)?,
+        Some(Kind::KwMatch) => match_expression(p)?,
+        Some(kind @ Kind::Bang) | Some(kind @ Kind::Minus) => {
+            // A prefix operation, such as !a or -a
+            p.consume(&kind)?;
+            let op = if kind == Kind::Bang {
+                Operator::Bang
+            } else {
+                Operator::Sub
+            };
+            let ((), right_binding) = op.prefix_binding();
+            let rhs = expression_inner(p, right_binding)?;
+            Ast::Prefix {
+                op,
+                rhs: Box::new(rhs),
+                span: start..p.cursor,
             }
         }
         Some(Kind::KwNew) => {
@@ -84,7 +90,10 @@ pub fn expression_inner<'input>(
             let token = p.consume(&Kind::Ident)?;
             let ident = p.text(&token).to_string();
 
-            Ast::Var(ident)
+            Ast::Var {
+                ident,
+                span: token.span,
+            }
         }
         Some(Kind::LParen) => {
             // An expression starting with a left paren can be
@@ -98,14 +107,25 @@ pub fn expression_inner<'input>(
             // - A generic function expression: [T, U](a: T) -> U {}
             either(p, vec![function_expression, list_literal])?
         }
-        x => panic!("invalid expression start: {:?} at {}", x, p.cursor),
+        Some(Kind::LBrace) => {
+            // Blocks only ever follow keywords and struct expressions follow `new`,
+            // so a brace starting an expression starts a map literal: { "a": 1 }
+            map_literal(p)?
+        }
+        x => {
+            return Err(Error::new(
+                &format!("Invalid start of expression: {:?}", x),
+                p.peek_span(),
+                p.input,
+            ))
+        }
     };
 
     expr = member_or_func_call(p, expr)?;
 
     // If we find an = sign and we are a variable or member (e.g. foo.bar),
     // we evaluate the rest as a new expression and return it as an assignment.
-    if p.at(Kind::Eq) && matches!(expr, Ast::Var(_) | Ast::Member { .. }) {
+    if p.at(Kind::Eq) && matches!(expr, Ast::Var { .. } | Ast::Member { .. }) {
         p.consume(&Kind::Eq)?;
         let rhs = Box::new(expression(p)?);
         return Ok(Ast::Assignment {
@@ -116,6 +136,22 @@ pub fn expression_inner<'input>(
     }
 
     loop {
+        // Casts bind tighter than any infix operator but `^`
+        if p.at(Kind::KwAs) {
+            if CAST_BINDING < binding_power {
+                break;
+            }
+            p.consume(&Kind::KwAs)?;
+            let token = p.consume(&Kind::Ident)?;
+            let to = Type::from_str(p.text(&token), vec![]);
+            expr = Ast::Cast {
+                expr: Box::new(expr),
+                to,
+                span: start..p.cursor,
+            };
+            continue;
+        }
+
         if let Some((op, kind)) = peek_operator(p) {
             if let Some((left_binding, right_binding)) = op.infix_binding() {
                 // Previous operator binds us more than the upcoming one.
@@ -125,12 +161,12 @@ pub fn expression_inner<'input>(
                 }
 
                 p.consume(&kind)?;
-                let end = p.cursor;
+                let rhs = expression_inner(p, right_binding)?;
                 expr = Ast::Infix {
                     lhs: Box::new(expr),
                     op,
-                    rhs: Box::new(expression_inner(p, right_binding)?),
-                    span: start..end,
+                    rhs: Box::new(rhs),
+                    span: start..p.cursor,
                 };
                 continue;
             }
@@ -141,6 +177,254 @@ pub fn expression_inner<'input>(
     Ok(expr)
 }
 
+/// A literal value, such as `1`, `1.5`, `"string"`, `c"string"`, `r"string"` or `true`.
+fn literal<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Value> {
+    let lit = p.peek();
+    let token = match &lit {
+        Some(
+            lit @ (Kind::Int
+            | Kind::String
+            | Kind::CString
+            | Kind::RawString
+            | Kind::Float
+            | Kind::KwTrue
+            | Kind::KwFalse),
+        ) => p.consume(lit)?,
+        x => {
+            return Err(Error::new(
+                &format!("Expected a literal, found {:?}", x),
+                p.peek_span(),
+                p.input,
+            ))
+        }
+    };
+    let txt = p.text(&token);
+    let span = token.span;
+    let value = match token.kind {
+        // The lexer only produces digits, so parsing can only fail by being out of range
+        Kind::Int => Value::Usize(
+            txt.parse()
+                .map_err(|_| Error::new("Integer literal out of range", span, p.input))?,
+        ),
+        Kind::Float => match txt.parse::<f64>() {
+            Ok(f) if f.is_finite() => Value::F64(f),
+            _ => return Err(Error::new("Float literal out of range", span, p.input)),
+        },
+        // Skip the quotes
+        Kind::String => Value::String(literal::string(p.input, span.start + 1..span.end - 1)?),
+        Kind::CString => Value::CString(literal::c_string(p.input, span.start + 2..span.end - 1)?),
+        Kind::RawString => {
+            let hashes = txt.len() - txt.trim_end_matches('#').len();
+            Value::String(txt[2 + hashes..txt.len() - 1 - hashes].to_string())
+        }
+        kind => Value::Bool(kind == Kind::KwTrue),
+    };
+    Ok(value)
+}
+
+/// An interpolated string, such as `"hello {name}, you are {age}"`. Literal text becomes string
+/// values, while each `{...}` segment is parsed as an embedded expression. Braces which are
+/// escaped or doubled, as in `"{{literal}}"`, are part of the text.
+fn interpolated_string<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
+    let token = p.consume(&Kind::InterpolatedString)?;
+    // Skip the quotes
+    let (start, end) = (token.span.start + 1, token.span.end - 1);
+    let (_, parts) = scan_string(&p.input[start..]).expect("the lexer scanned the string");
+
+    let mut segments = vec![];
+    for part in parts {
+        let segment = match part {
+            Segment::Text(range) => {
+                let range = start + range.start..start + range.end;
+                Ast::Value(Value::String(literal::string(p.input, range)?))
+            }
+            Segment::Expression(range) => {
+                embedded_expression(p, start + range.start..start + range.end)?
+            }
+            Segment::Unclosed(open) => {
+                return Err(Error::new(
+                    "Unclosed '{' in interpolated string, use '{{' for a literal brace",
+                    start + open..end,
+                    p.input,
+                ));
+            }
+        };
+        segments.push(segment);
+    }
+
+    Ok(Ast::Interpolation {
+        segments,
+        span: token.span,
+    })
+}
+
+/// Parses the expression within a `{...}` segment of an interpolated string, which must span
+/// the entire segment.
+fn embedded_expression<'input>(
+    p: &mut Parser<'input, Lexer<'input>>,
+    range: Range<usize>,
+) -> Result<Ast> {
+    let mut embedded = p.embedded(range.clone());
+    if embedded.peek().is_none() {
+        return Err(Error::new(
+            "Expected an expression in interpolated string, use '{{' for a literal brace",
+            range.start - 1..range.end + 1,
+            p.input,
+        ));
+    }
+    let expr = expression(&mut embedded)?;
+    if embedded.peek().is_some() {
+        return Err(Error::new(
+            "Unexpected token in interpolated string",
+            embedded.peek_span(),
+            p.input,
+        ));
+    }
+    Ok(expr)
+}
+
+/// Parses a match expression. Arms are separated by commas or newlines, and their bodies are
+/// either an expression or a block of statements.
+/// ## Example
+/// ```no
+/// match shape {
+///     Circle(radius) if radius > 10 -> "large circle",
+///     Rectangle { width: 0 } -> "line",
+///     _ -> {
+///         let name = "other shape"
+///         name
+///     }
+/// }
+/// ```
+fn match_expression<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
+    let start = p.cursor;
+    p.consume(&Kind::KwMatch)?;
+    let expr = expression(p)?;
+    p.consume(&Kind::LBrace)?;
+
+    let mut arms = vec![];
+    loop {
+        while let Some(kind @ (Kind::Comma | Kind::Semicolon)) = p.peek() {
+            p.consume(&kind)?;
+        }
+        if p.at(Kind::RBrace) {
+            break;
+        }
+
+        let arm_start = p.cursor;
+        let pattern = pattern(p)?;
+        let guard = if p.at(Kind::KwIf) {
+            p.consume(&Kind::KwIf)?;
+            Some(expression(p)?)
+        } else {
+            None
+        };
+        p.consume(&Kind::Arrow)?;
+        let body = if p.at(Kind::LBrace) {
+            p.consume(&Kind::LBrace)?;
+            let body = statement(p)?;
+            p.consume(&Kind::RBrace)?;
+            body
+        } else {
+            expression(p)?
+        };
+
+        arms.push(MatchArm {
+            pattern,
+            guard,
+            body,
+            span: arm_start..p.cursor,
+        });
+    }
+    p.consume(&Kind::RBrace)?;
+
+    Ok(Ast::Match {
+        expr: Box::new(expr),
+        arms,
+        span: start..p.cursor,
+    })
+}
+
+/// A pattern within a match arm.
+/// ## Example
+/// ```no
+/// _
+/// "literal"
+/// binding
+/// Some(x)
+/// Option.None
+/// Point { x, y: 0 }
+/// ```
+fn pattern<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Pattern> {
+    match p.peek() {
+        Some(Kind::Under) => {
+            p.consume(&Kind::Under)?;
+            Ok(Pattern::Wildcard)
+        }
+        Some(Kind::Ident) => {
+            let token = p.consume(&Kind::Ident)?;
+            let mut name = p.text(&token).to_string();
+
+            // A variant may be qualified by the name of its enum
+            let mut enum_name = None;
+            if p.at(Kind::Dot) {
+                p.consume(&Kind::Dot)?;
+                let token = p.consume(&Kind::Ident)?;
+                enum_name = Some(std::mem::replace(&mut name, p.text(&token).to_string()));
+            }
+
+            match p.peek() {
+                Some(Kind::LParen) => {
+                    p.consume(&Kind::LParen)?;
+                    let mut fields = vec![];
+                    while !p.at(Kind::RParen) {
+                        fields.push(pattern(p)?);
+                        if p.at(Kind::Comma) {
+                            p.consume(&Kind::Comma)?;
+                        }
+                    }
+                    p.consume(&Kind::RParen)?;
+                    Ok(Pattern::Variant {
+                        enum_name,
+                        name,
+                        fields,
+                    })
+                }
+                Some(Kind::LBrace) if enum_name.is_none() => {
+                    p.consume(&Kind::LBrace)?;
+                    let mut fields = vec![];
+                    loop {
+                        while let Some(kind @ (Kind::Comma | Kind::Semicolon)) = p.peek() {
+                            p.consume(&kind)?;
+                        }
+                        if p.at(Kind::RBrace) {
+                            break;
+                        }
+                        let token = p.consume(&Kind::Ident)?;
+                        let field = p.text(&token).to_string();
+                        let pattern = if p.at(Kind::Colon) {
+                            p.consume(&Kind::Colon)?;
+                            pattern(p)?
+                        } else {
+                            Pattern::Binding(field.clone())
+                        };
+                        fields.push((field, pattern));
+                    }
+                    p.consume(&Kind::RBrace)?;
+                    Ok(Pattern::Struct { name, fields })
+                }
+                _ if enum_name.is_some() => Ok(Pattern::Variant {
+                    enum_name,
+                    name,
+                    fields: vec![],
+                }),
+                _ => Ok(Pattern::Binding(name)),
+            }
+        }
+        _ => Ok(Pattern::Literal(literal(p)?)),
+    }
+}
+
 pub fn peek_operator<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Option<(Operator, Kind)> {
     let kind = p.peek();
     let op = match &kind {
@@ -226,6 +510,18 @@ pub fn member_or_func_call<'input>(
             let fn_call = function_call(p, Box::new(expr))?;
             member_or_func_call(p, fn_call)
         }
+
+        // Propagates the error of a result: parse(input)?
+        Some(Kind::Question) => {
+            p.consume(&Kind::Question)?;
+            member_or_func_call(
+                p,
+                Ast::Try {
+                    expr: Box::new(expr),
+                    span: start..p.cursor,
+                },
+            )
+        }
         _ => Ok(expr),
     }
 }
@@ -246,7 +542,10 @@ pub fn map_values<'input>(
         p.consume(&Kind::Colon)?;
         expression(p)?
     } else {
-        Ast::Var(name.clone())
+        Ast::Var {
+            ident: name.clone(),
+            span: token.span,
+        }
     };
 
     properties_.insert(name, expr);
@@ -284,7 +583,11 @@ fn nested_expression<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<As
 fn list_literal<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
     let start = p.cursor;
     p.consume(&Kind::LSquare)?;
-    let items = list_expressions(p, vec![])?;
+    let items = if p.at(Kind::RSquare) {
+        vec![]
+    } else {
+        list_expressions(p, vec![])?
+    };
     p.consume(&Kind::RSquare)?;
 
     Ok(Ast::List {
@@ -293,6 +596,38 @@ fn list_literal<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
     })
 }
 
+/// A map literal of key and value expressions
+/// ## Example
+/// ```no
+/// { "one": 1, "two": 1 + 1 }
+/// ```
+fn map_literal<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
+    let start = p.cursor;
+    p.consume(&Kind::LBrace)?;
+    let mut entries = vec![];
+    while !p.at(Kind::RBrace) {
+        let key = expression(p)?;
+        p.consume(&Kind::Colon)?;
+        let value = expression(p)?;
+        entries.push((key, value));
+
+        // May have an automatic semicolon. Disregard it.
+        if p.at(Kind::Semicolon) {
+            p.consume(&Kind::Semicolon)?;
+        }
+        if !p.at(Kind::Comma) {
+            break;
+        }
+        p.consume(&Kind::Comma)?;
+    }
+    p.consume(&Kind::RBrace)?;
+
+    Ok(Ast::Map {
+        entries,
+        span: start..p.cursor,
+    })
+}
+
 fn function_call<'input>(p: &mut Parser<'input, Lexer<'input>>, expr: Box<Ast>) -> Result<Ast> {
     let mut args = vec![];
     let start = p.cursor;
@@ -342,17 +677,20 @@ pub fn function_expression<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Res
     // -> type: expr       <-- lambda with return type
     // -> type { stmt }    <-- full function with return type
     // -> expr             <-- lambda with inferred return type
+    let returns_start = p.peek_span().start;
     let mut fork = p.fork();
     let maybe_type_literal = type_literal(&mut fork);
 
-    let (returns, body) = match maybe_type_literal {
+    let (returns, returns_span, body) = match maybe_type_literal {
         Ok(ty) if fork.at(Kind::Colon) => {
-            *p = fork;
+            p.join(fork);
+            let returns_span = returns_start..p.cursor;
             p.consume(&Kind::Colon)?;
             let start = p.cursor;
             let expr = Box::new(expression(p)?);
             (
                 ty,
+                Some(returns_span),
                 Ast::Return {
                     expr,
                     span: start..p.cursor,
@@ -360,7 +698,8 @@ pub fn function_expression<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Res
             )
         }
         Ok(ty) => {
-            *p = fork;
+            p.join(fork);
+            let returns_span = returns_start..p.cursor;
 
             let constraints = where_constraints(p)?;
             for v in type_vars.into_iter() {
@@ -373,13 +712,14 @@ pub fn function_expression<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Res
             p.consume(&Kind::LBrace)?;
             let body = statement(p)?;
             p.consume(&Kind::RBrace)?;
-            (ty, body)
+            (ty, Some(returns_span), body)
         }
         _ => {
             let start = p.cursor;
             let expr = Box::new(expression(p)?);
             (
                 Type::Unknown,
+                None,
                 Ast::Return {
                     expr,
                     span: start..p.cursor,
@@ -391,6 +731,7 @@ pub fn function_expression<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Res
     Ok(Ast::Function {
         args,
         returns,
+        returns_span,
         body: Box::new(body),
         is_variadic,
         generics,
@@ -476,7 +817,7 @@ mod tests {
     fn it_parses_basic_expressions() {
         let mut p = Parser::new("some_variable");
         let result = expression(&mut p).unwrap();
-        assert_matches!(result, Ast::Var(_));
+        assert_matches!(result, Ast::Var { .. });
 
         let mut p = Parser::new("1");
         let result = expression(&mut p).unwrap();
@@ -484,7 +825,7 @@ mod tests {
 
         let mut p = Parser::new("1.0");
         let result = expression(&mut p).unwrap();
-        assert_matches!(result, Ast::Value(Value::F32(_)));
+        assert_matches!(result, Ast::Value(Value::F64(_)));
 
         let mut p = Parser::new("\"a string literal\"");
         let result = expression(&mut p).unwrap();
@@ -503,6 +844,19 @@ mod tests {
         assert_matches!(result, Ast::Value(Value::Usize(1)));
     }
 
+    #[test]
+    fn it_rejects_out_of_range_literals() {
+        let mut p = Parser::new("99999999999999999999999");
+        let err = expression(&mut p).unwrap_err();
+        assert_eq!(err.message(), "Integer literal out of range");
+        assert_eq!(err.span(), 0..23);
+
+        let mut p = Parser::new("1 + 1e999");
+        let err = expression(&mut p).unwrap_err();
+        assert_eq!(err.message(), "Float literal out of range");
+        assert_eq!(err.span(), 4..9);
+    }
+
     #[test]
     fn it_parses_infixes() {
         let mut p = Parser::new("1 + 1");
@@ -525,4 +879,181 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn it_parses_casts() {
+        let mut p = Parser::new("1.5 as f32");
+        let result = expression(&mut p).unwrap();
+        assert_matches!(
+            result,
+            Ast::Cast { expr, to: Type::F32, .. } if *expr == Ast::Value(Value::F64(1.5))
+        );
+
+        // Casts bind tighter than arithmetic
+        let mut p = Parser::new("1 + 2 as u8 * 3");
+        let result = expression(&mut p).unwrap();
+        let Ast::Infix { rhs, .. } = result else {
+            panic!("expected an infix expression");
+        };
+        assert_matches!(
+            *rhs,
+            Ast::Infix { lhs, op: Operator::Mul, .. } if matches!(*lhs, Ast::Cast { to: Type::U8, .. })
+        );
+    }
+
+    #[test]
+    fn it_parses_prefix_operators() {
+        let mut p = Parser::new("!true");
+        let result = expression(&mut p).unwrap();
+        assert_matches!(
+            result,
+            Ast::Prefix { op: Operator::Bang, rhs, .. } if *rhs == Ast::Value(Value::Bool(true))
+        );
+
+        // Prefix operators bind tighter than infix ones
+        let mut p = Parser::new("!a && b");
+        let result = expression(&mut p).unwrap();
+        assert_matches!(
+            result,
+            Ast::Infix { lhs, op: Operator::And, .. } if matches!(*lhs, Ast::Prefix { .. })
+        );
+
+        let mut p = Parser::new("-x as i32");
+        let result = expression(&mut p).unwrap();
+        assert_matches!(
+            result,
+            Ast::Cast { expr, .. } if matches!(*expr, Ast::Prefix { op: Operator::Sub, .. })
+        );
+    }
+
+    #[test]
+    fn it_parses_try() {
+        // The operator applies to the call, and members can be accessed on what it unwraps
+        let mut p = Parser::new("parse(a)?.len + 1");
+        let result = expression(&mut p).unwrap();
+        let Ast::Infix {
+            lhs,
+            op: Operator::Add,
+            ..
+        } = result
+        else {
+            panic!("expected an infix expression");
+        };
+        assert_matches!(
+            *lhs,
+            Ast::Member { container, .. } if matches!(
+                *container,
+                Ast::Try { ref expr, .. } if matches!(**expr, Ast::Call { .. })
+            )
+        );
+    }
+
+    #[test]
+    fn it_parses_maps() {
+        let mut p = Parser::new("{ \"a\": 1, key: 1 + 1 }[\"a\"]");
+        let result = expression(&mut p).unwrap();
+        let Ast::Member { container, .. } = result else {
+            panic!("expected a member expression");
+        };
+        assert_matches!(*container, Ast::Map { ref entries, .. } if entries.len() == 2);
+
+        // Entries may span several lines, with or without a trailing comma
+        let mut p = Parser::new("{\n  \"a\": 1,\n  \"b\": 2\n}");
+        let result = expression(&mut p).unwrap();
+        assert_matches!(result, Ast::Map { ref entries, .. } if entries.len() == 2);
+
+        let mut p = Parser::new("{}");
+        let result = expression(&mut p).unwrap();
+        assert_matches!(result, Ast::Map { ref entries, .. } if entries.is_empty());
+    }
+
+    #[test]
+    fn it_parses_interpolated_strings() {
+        let mut p = Parser::new("\"hello {name}, you are {age as u8}!\"");
+        let result = expression(&mut p).unwrap();
+        let Ast::Interpolation { segments, .. } = result else {
+            panic!("expected an interpolated string");
+        };
+        assert_eq!(segments.len(), 5);
+        assert_eq!(segments[0], Ast::Value(Value::String("hello ".to_string())));
+        assert_eq!(
+            segments[1],
+            Ast::Var {
+                ident: "name".to_string(),
+                span: 8..12
+            }
+        );
+        // Spans of embedded expressions point into the whole input
+        assert_eq!(segments[3].span(), Some(24..33));
+        assert_eq!(segments[4], Ast::Value(Value::String("!".to_string())));
+
+        // Embedded expressions may contain braces of their own
+        let mut p = Parser::new("\"{ {1: 2}.len() }\"");
+        let result = expression(&mut p).unwrap();
+        assert_matches!(result, Ast::Interpolation { ref segments, .. } if segments.len() == 1);
+
+        // As may strings
+        let mut p = Parser::new("\"{m[\"k\"]}!\"");
+        let result = expression(&mut p).unwrap();
+        let Ast::Interpolation { segments, .. } = result else {
+            panic!("expected an interpolated string");
+        };
+        assert_matches!(segments[0], Ast::Member { .. });
+        assert_eq!(segments[1], Ast::Value(Value::String("!".to_string())));
+
+        // Escaped and doubled braces are literal, and leave strings uninterpolated
+        for (source, text) in [
+            ("\"json: {{}}\"", "json: {}"),
+            ("\"a \\{ b \\}\"", "a { b }"),
+            ("\"a } b\"", "a } b"),
+        ] {
+            let mut p = Parser::new(source);
+            assert_eq!(
+                expression(&mut p).unwrap(),
+                Ast::Value(Value::String(text.to_string())),
+                "{}",
+                source
+            );
+        }
+        let mut p = Parser::new("\"{{{a}}}\"");
+        let result = expression(&mut p).unwrap();
+        assert_matches!(result, Ast::Interpolation { ref segments, .. } if segments.len() == 3);
+
+        for source in ["\"{}\"", "\"{a\"", "\"{a b}\""] {
+            let mut p = Parser::new(source);
+            assert!(expression(&mut p).is_err(), "{}", source);
+        }
+        let err = expression(&mut Parser::new("\"a { b\"")).unwrap_err();
+        assert_eq!(err.span(), 3..6);
+        assert!(err.message().contains("'{{'"));
+    }
+
+    #[test]
+    fn it_parses_match() {
+        let mut p = Parser::new(
+            "match x { Option.Some(Point { x: 0, y }) if y > 1 -> y, None -> 0, _ -> 1 }",
+        );
+        let result = expression(&mut p).unwrap();
+        let Ast::Match { arms, .. } = result else {
+            panic!("expected a match expression");
+        };
+        assert_eq!(arms.len(), 3);
+        assert_eq!(
+            arms[0].pattern,
+            Pattern::Variant {
+                enum_name: Some("Option".to_string()),
+                name: "Some".to_string(),
+                fields: vec![Pattern::Struct {
+                    name: "Point".to_string(),
+                    fields: vec![
+                        ("x".to_string(), Pattern::Literal(Value::Usize(0))),
+                        ("y".to_string(), Pattern::Binding("y".to_string())),
+                    ],
+                }],
+            }
+        );
+        assert!(arms[0].guard.is_some());
+        assert_eq!(arms[1].pattern, Pattern::Binding("None".to_string()));
+        assert_eq!(arms[2].pattern, Pattern::Wildcard);
+    }
 }
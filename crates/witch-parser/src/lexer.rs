@@ -1,9 +1,11 @@
 use std::iter::Peekable;
+use std::ops::Range;
 
 use logos::{Logos, Span, SpannedIter};
 
 #[derive(Logos, Debug, Clone, PartialEq, Eq)]
 #[logos(subpattern ident = r"[A-Za-z]([A-Za-z]|_|\d)*")]
+#[logos(subpattern escape = r#"\\(u\{[^\\"{}]*\}|.)"#)]
 pub enum Kind {
     #[token(".")]
     Dot,
@@ -55,6 +57,8 @@ pub enum Kind {
     Under,
     #[token("->")]
     Arrow,
+    #[token("?")]
+    Question,
 
     // Brackets
     #[token("<")]
@@ -73,10 +77,18 @@ pub enum Kind {
     LBrace,
     #[token("}")]
     RBrace,
-    #[regex(r#""((\\"|\\\\)|[^\\"])*""#)]
+    #[token("\"", string)]
     String,
-    #[regex(r#"c"((\\"|\\\\)|[^\\"])*""#)]
+    /// A string containing `{expression}` segments, such as `"hello {name}"`. Both kinds of
+    /// strings are scanned by `scan_string`, after which `Lexer` tells them apart.
+    InterpolatedString,
+    #[regex(r#"c"((?&escape)|[^\\"])*""#)]
     CString,
+    /// A string without escape sequences or interpolation, such as `r"C:\path"`. Wrapping it
+    /// in hashes, as in `r#"say "hi""#`, allows it to contain quotes.
+    #[regex(r#"r"[^"]*""#)]
+    #[regex(r#"r#+""#, raw_string)]
+    RawString,
     #[regex(r"/\*([^*]|\**[^*/])*\*+/")]
     Comment,
     #[regex(r#"#[^\n]*\n"#)]
@@ -117,6 +129,14 @@ pub enum Kind {
     KwFor,
     #[token("in")]
     KwIn,
+    #[token("as")]
+    KwAs,
+    #[token("match")]
+    KwMatch,
+    #[token("true")]
+    KwTrue,
+    #[token("false")]
+    KwFalse,
     #[token("break")]
     KwBreak,
     #[token("continue")]
@@ -131,6 +151,113 @@ pub enum Kind {
     End,
 }
 
+/// Reads a raw string up to the quote followed by as many hashes as it was opened with.
+fn raw_string(lex: &mut logos::Lexer<Kind>) -> bool {
+    let hashes = lex.slice().len() - 2;
+    let end = format!("\"{}", "#".repeat(hashes));
+    match lex.remainder().find(&end) {
+        Some(len) => {
+            lex.bump(len + end.len());
+            true
+        }
+        None => false,
+    }
+}
+
+/// Reads a string up to its closing quote, which may follow embedded expressions that contain
+/// strings of their own.
+fn string(lex: &mut logos::Lexer<Kind>) -> bool {
+    match scan_string(lex.remainder()) {
+        Some((len, _)) => {
+            lex.bump(len);
+            true
+        }
+        None => false,
+    }
+}
+
+/// A part of the contents of a string literal, relative to just after its opening quote.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    /// Literal text, which may contain escape sequences and doubled braces
+    Text(Range<usize>),
+    /// The expression of a `{...}` segment, without its braces
+    Expression(Range<usize>),
+    /// A `{` which is not closed before the end of the string
+    Unclosed(usize),
+}
+
+/// Scans the contents of a string literal from just after its opening quote, returning their
+/// length up to and including the closing quote, along with their segments. Within text, an
+/// escaped or doubled brace stands for itself while a single `{` switches to scanning an
+/// embedded expression, which ends at its matching `}` and may contain braces and strings of
+/// its own. If that `}` never comes, the string ends at the first quote after the `{` instead.
+pub fn scan_string(text: &str) -> Option<(usize, Vec<Segment>)> {
+    let bytes = text.as_bytes();
+    let mut segments = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while let Some(&byte) = bytes.get(i) {
+        match byte {
+            b'"' => {
+                if start < i {
+                    segments.push(Segment::Text(start..i));
+                }
+                return Some((i + 1, segments));
+            }
+            b'\\' => {
+                // The braces of unicode escapes belong to the escape
+                if bytes.get(i + 1) == Some(&b'u') && bytes.get(i + 2) == Some(&b'{') {
+                    while bytes.get(i).is_some_and(|b| !matches!(b, b'}' | b'"')) {
+                        i += 1;
+                    }
+                } else {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'{' | b'}' if bytes.get(i + 1) == Some(&byte) => i += 2,
+            b'{' => {
+                if start < i {
+                    segments.push(Segment::Text(start..i));
+                }
+                match scan_expression(&text[i + 1..]) {
+                    Some(len) => {
+                        segments.push(Segment::Expression(i + 1..i + 1 + len));
+                        i += len + 2;
+                        start = i;
+                    }
+                    None => {
+                        segments.push(Segment::Unclosed(i));
+                        let len = i + text[i..].find('"')? + 1;
+                        return Some((len, segments));
+                    }
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Scans an expression embedded in a string up to its closing `}`, returning its length.
+fn scan_expression(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while let Some(&byte) = bytes.get(i) {
+        match byte {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Some(i),
+            b'}' => depth -= 1,
+            b'"' => i += scan_string(&text[i + 1..])?.0,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Token {
     pub kind: Kind,
@@ -138,15 +265,30 @@ pub struct Token {
 }
 
 pub struct Lexer<'input> {
+    input: &'input str,
     lexer: Peekable<SpannedIter<'input, Kind>>,
     prev_kind: Option<Kind>,
+
+    /// Added to the spans of tokens, when lexing a slice of a larger input
+    offset: usize,
 }
 
 impl<'input> Lexer<'input> {
     pub fn new(input: &'input str) -> Self {
         Self {
+            input,
             lexer: Kind::lexer(input).spanned().peekable(),
             prev_kind: None,
+            offset: 0,
+        }
+    }
+
+    /// Lexes the `span` of `input`, such as an expression embedded in a string literal,
+    /// with token spans still pointing into the whole input.
+    pub fn embedded(input: &'input str, span: Span) -> Self {
+        Self {
+            offset: span.start,
+            ..Self::new(&input[span])
         }
     }
 
@@ -162,7 +304,12 @@ impl<'input> Lexer<'input> {
                     | Kind::Int
                     | Kind::Float
                     | Kind::String
+                    | Kind::InterpolatedString
+                    | Kind::RawString
+                    | Kind::KwTrue
+                    | Kind::KwFalse
                     | Kind::RParen
+                    | Kind::Question
             )
         ) && !matches!(&mut self.lexer.peek(), Some((Ok(Kind::Dot), _))) // Dont ASI between chained method calls
     }
@@ -172,7 +319,16 @@ impl<'input> Iterator for Lexer<'input> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some((Ok(kind), span)) = self.lexer.next() {
+        if let Some((Ok(mut kind), span)) = self.lexer.next() {
+            // Strings are interpolated once they contain a single `{`
+            if kind == Kind::String {
+                let (_, segments) = scan_string(&self.input[span.start + 1..])?;
+                if segments.iter().any(|s| !matches!(s, Segment::Text(_))) {
+                    kind = Kind::InterpolatedString;
+                }
+            }
+            let span = span.start + self.offset..span.end + self.offset;
+
             // Filter away commments by skipping ahead
             if matches!(kind, Kind::Comment | Kind::LineComment) {
                 return self.next();
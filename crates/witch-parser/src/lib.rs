@@ -12,6 +12,7 @@ use std::path::PathBuf;
 use logos::Span;
 
 mod lexer;
+mod literal;
 use lexer::Token;
 
 use crate::error::{Error, Result};
@@ -20,7 +21,7 @@ use crate::types::Type;
 use self::lexer::{Kind, Lexer};
 pub mod ast;
 pub use ast::Ast;
-mod error;
+pub mod error;
 mod expression;
 mod statement;
 mod r#type;
@@ -31,6 +32,9 @@ pub struct Module {
     pub path: PathBuf,
     pub ast: ast::Ast,
     pub imports: HashMap<PathBuf, Self>,
+
+    /// The source text the module was parsed from
+    pub source: String,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -44,6 +48,15 @@ where
     tokens: Peekable<I>,
     read_count: usize,
     cursor: usize,
+
+    /// The part of the input being parsed, which is all of it unless the parser is embedded
+    range: Span,
+
+    /// Whether statements which fail to parse get recorded in `errors` and replaced by
+    /// `Ast::Error` nodes, instead of aborting the parse. Disabled for forks, as
+    /// speculative parses need to fail in order for the next alternative to be tried.
+    recover: bool,
+    errors: Vec<Error>,
 }
 
 impl<'input> Parser<'input, Lexer<'input>> {
@@ -53,21 +66,53 @@ impl<'input> Parser<'input, Lexer<'input>> {
             tokens: Lexer::new(input).peekable(),
             read_count: 0,
             cursor: 0,
+            range: 0..input.len(),
+            recover: true,
+            errors: vec![],
+        }
+    }
+
+    /// A parser for an expression embedded within the input, such as the `{...}` segments of
+    /// an interpolated string. Spans and errors still point into the whole input.
+    pub fn embedded(&self, range: Span) -> Self {
+        Self {
+            input: self.input,
+            tokens: Lexer::embedded(self.input, range.clone()).peekable(),
+            read_count: 0,
+            cursor: range.start,
+            range,
+            recover: false,
+            errors: vec![],
         }
     }
 
     pub fn fork(&mut self) -> Self {
         let mut fork = Self {
             input: self.input,
-            tokens: Lexer::new(self.input).peekable(),
+            tokens: Lexer::embedded(self.input, self.range.clone()).peekable(),
             read_count: self.read_count,
             cursor: self.cursor,
+            range: self.range.clone(),
+            recover: false,
+            errors: vec![],
         };
         let _ = fork.tokens.advance_by(self.read_count);
         assert_eq!(&self.peek(), &fork.peek());
         fork
     }
 
+    /// Moves forward to where a successful fork has parsed to.
+    pub fn join(&mut self, fork: Self) {
+        self.tokens = fork.tokens;
+        self.read_count = fork.read_count;
+        self.cursor = fork.cursor;
+    }
+
+    /// Errors recorded while recovering from failed statements.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
     /// Get the source text of a token.
     pub fn text(&self, token: &Token) -> &'input str {
         &self.input[token.span.start..token.span.end]
@@ -78,6 +123,15 @@ impl<'input> Parser<'input, Lexer<'input>> {
         self.tokens.peek().map(|t| t.kind.clone())
     }
 
+    /// The span of the next token, or an empty span at the cursor if the input has ended.
+    pub fn peek_span(&mut self) -> Span {
+        let cursor = self.cursor;
+        self.tokens
+            .peek()
+            .map(|t| t.span.clone())
+            .unwrap_or(cursor..cursor)
+    }
+
     /// Checks whether the next token is of a given kind
     pub fn at(&mut self, kind: Kind) -> bool {
         self.peek() == Some(kind)
@@ -91,38 +145,105 @@ impl<'input> Parser<'input, Lexer<'input>> {
 
     /// Move forward one token in the input and check
     /// that we pass the kind of token we expect.
+    /// An unexpected token is left in the input for error recovery to skip past.
     pub fn consume(&mut self, expected: &Kind) -> Result<Token> {
-        let token = self.tokens.next().ok_or(Error::new(
-            &format!("Unexpected end of input. Expected: {:?}", expected),
-            self.cursor..self.cursor,
-            self.input,
-        ))?;
-
-        if &token.kind != expected {
-            return Err(Error::new(
-                &format!(
-                    "Unexpected token. Expected {:?}, got {:?}",
-                    expected, token.kind
+        let Some(token) = self.tokens.next_if(|token| &token.kind == expected) else {
+            return Err(match self.tokens.peek() {
+                Some(token) => Error::new(
+                    &format!(
+                        "Unexpected token. Expected {:?}, got {:?}",
+                        expected, token.kind
+                    ),
+                    token.span.clone(),
+                    self.input,
                 ),
-                self.cursor..self.cursor,
-                self.input,
-            ));
-        }
+                None => Error::new(
+                    &format!("Unexpected end of input. Expected: {:?}", expected),
+                    self.cursor..self.cursor,
+                    self.input,
+                ),
+            });
+        };
 
         self.cursor = token.span.end;
         self.read_count += 1;
         Ok(token)
     }
 
+    /// Parses a module. If any statement fails to parse, the first error is returned
+    /// with every other error as its related errors.
     pub fn module(&mut self, path: PathBuf) -> Result<Module> {
+        match self.module_with_errors(path) {
+            (Some(module), errors) if errors.is_empty() => Ok(module),
+            (_, errors) => Err(Error::combine(errors).unwrap_or_else(Error::fatal)),
+        }
+    }
+
+    /// Parses a module, recovering from errors at statement boundaries. Returns the partial
+    /// module, where every statement which failed to parse is an `Ast::Error`, along with
+    /// all errors encountered. The module is `None` if its imports could not be resolved.
+    pub fn module_with_errors(&mut self, path: PathBuf) -> (Option<Module>, Vec<Error>) {
+        let input = self.input;
+        let module = self.module_inner(path.clone());
+        let mut errors: Vec<Error> = std::mem::take(&mut self.errors);
+        let module = match module {
+            Ok(module) => Some(module),
+            Err(e) => {
+                errors.extend(e.flatten());
+                None
+            }
+        };
+        let errors = errors
+            .into_iter()
+            .map(|e| e.in_module(&path, input))
+            .collect();
+        (module, errors)
+    }
+
+    fn module_inner(&mut self, path: PathBuf) -> Result<Module> {
         let mut imports = HashMap::default();
         statement::imports(self, path.clone(), &mut imports)?;
         let ast = self.file()?;
-        Ok(Module { path, ast, imports })
+        Ok(Module {
+            path,
+            ast,
+            imports,
+            source: self.input.to_string(),
+        })
     }
 
     pub fn file(&mut self) -> Result<ast::Ast> {
-        statement::statement(self)
+        let ast = statement::statement(self)?;
+
+        // A closing brace ends the statement list of a block, so one without
+        // a matching opening brace would silently end the file.
+        if self.peek().is_some() {
+            let err = Error::new("Unexpected token", self.peek_span(), self.input);
+            if !self.recover {
+                return Err(err);
+            }
+            self.errors.push(err);
+        }
+        Ok(ast)
+    }
+
+    /// Skips ahead to the next statement boundary after an error: past the next semicolon
+    /// (which includes inserted ones for newlines) or up to the closing brace of the current block.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        while let Some(kind) = self.peek() {
+            match kind {
+                Kind::Semicolon if depth == 0 => {
+                    let _ = self.consume(&kind);
+                    return;
+                }
+                Kind::RBrace if depth == 0 => return,
+                Kind::RBrace => depth -= 1,
+                Kind::LBrace => depth += 1,
+                _ => {}
+            }
+            let _ = self.consume(&kind);
+        }
     }
 
     /// Repeats a token until we peek something else. Optionally takes a separator between each repetition.
@@ -153,7 +274,7 @@ pub fn either<'input>(
     for f in choices.into_iter() {
         let mut fork = p.fork();
         if let Ok(ast) = f(&mut fork) {
-            *p = fork;
+            p.join(fork);
             return Ok(ast);
         }
     }
@@ -171,7 +292,7 @@ pub fn maybe_type<'input>(
     let mut fork = p.fork();
     let res = f(&mut fork);
     if let Ok(ast) = res {
-        *p = fork;
+        p.join(fork);
         return Ok(ast);
     }
     res
@@ -184,7 +305,7 @@ pub fn maybe<'input, T>(
     let mut fork = p.fork();
     let res = f(&mut fork);
     if let Ok(ast) = res {
-        *p = fork;
+        p.join(fork);
         return Ok(ast);
     }
     res
@@ -200,3 +321,39 @@ fn forked_parser() {
 
     assert_eq!(p.peek(), fork.peek());
 }
+
+#[test]
+fn recovers_from_errors() {
+    let source = "let a = 1
+let b = )
+let c = 3
+let = 4
+function f() -> usize {
+    let d = (
+}
+let e = 5
+";
+    let mut p = Parser::new(source);
+    let (module, errors) = p.module_with_errors(PathBuf::from("recover.witch"));
+    assert_eq!(errors.len(), 3);
+
+    // Statements after the errors are still parsed
+    let mut idents = vec![];
+    let mut error_count = 0;
+    let mut ast = module.unwrap().ast;
+    while let Ast::Statement { stmt, rest, .. } = ast {
+        match *stmt {
+            Ast::Let { ident, .. } => idents.push(ident),
+            Ast::Error { .. } => error_count += 1,
+            _ => {}
+        }
+        ast = *rest;
+    }
+    assert_eq!(idents, vec!["a", "c", "f", "e"]);
+    assert_eq!(error_count, 2);
+
+    let err = Parser::new("let b = )\nlet = 4\n")
+        .module(PathBuf::from("recover.witch"))
+        .unwrap_err();
+    assert_eq!(err.flatten().len(), 2);
+}
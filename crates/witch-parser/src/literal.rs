@@ -0,0 +1,152 @@
+//! Decodes the text of string literals into their values, by replacing escape sequences
+//! such as `\n`, `\x41` or `\u{1F600}` with the characters they stand for.
+use std::ffi::CString;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+use crate::error::{Error, Result};
+
+/// Decodes the contents of a string literal, found at `span` of the input. As a single `{`
+/// starts an interpolated expression, doubled braces stand for a single one.
+pub fn string(input: &str, span: Range<usize>) -> Result<String> {
+    let bytes = decode(input, span, false)?;
+    Ok(String::from_utf8(bytes).expect("string escapes produce valid utf-8"))
+}
+
+/// Decodes the contents of a C-string literal, found at `span` of the input. Unlike strings,
+/// hex escapes may produce any byte but nul.
+pub fn c_string(input: &str, span: Range<usize>) -> Result<CString> {
+    let bytes = decode(input, span.clone(), true)?;
+    // Escaped nul bytes are already rejected while decoding
+    CString::new(bytes).map_err(|_| Error::new("C-strings can not contain nul bytes", span, input))
+}
+
+fn decode(input: &str, span: Range<usize>, c_string: bool) -> Result<Vec<u8>> {
+    let text = &input[span.clone()];
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            if !c_string && matches!(c, '{' | '}') {
+                chars.next_if(|(_, next)| *next == c);
+            }
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+
+        // Errors span the escape sequence read so far
+        let start = span.start + i;
+        let error = |chars: &mut Peekable<CharIndices>, msg: &str| {
+            let end = chars.peek().map_or(text.len(), |(i, _)| *i);
+            Error::new(msg, start..span.start + end, input)
+        };
+
+        match chars.next().map(|(_, c)| c) {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('r') => bytes.push(b'\r'),
+            Some('0') => bytes.push(b'\0'),
+            Some(c @ ('\\' | '"' | '{' | '}')) => bytes.push(c as u8),
+            Some('x') => {
+                let digits = hex_digits(&mut chars, 2);
+                let byte = match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 => byte,
+                    _ => {
+                        return Err(error(
+                            &mut chars,
+                            "Invalid hex escape, expected two hex digits such as \\x41",
+                        ))
+                    }
+                };
+                if byte > 0x7F && !c_string {
+                    return Err(error(
+                        &mut chars,
+                        "Hex escapes in strings must be at most \\x7F, use \\u{...} instead",
+                    ));
+                }
+                bytes.push(byte);
+            }
+            Some('u') => {
+                if chars.next_if(|(_, c)| *c == '{').is_none() {
+                    return Err(error(
+                        &mut chars,
+                        "Invalid unicode escape, expected braces such as \\u{1F600}",
+                    ));
+                }
+                let digits = hex_digits(&mut chars, 6);
+                let closed = chars.next_if(|(_, c)| *c == '}').is_some();
+                let c = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .filter(|_| closed);
+                match c {
+                    Some(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                    None => {
+                        return Err(error(
+                            &mut chars,
+                            "Invalid unicode escape, expected up to six hex digits of a unicode scalar value",
+                        ))
+                    }
+                }
+            }
+            _ => return Err(error(&mut chars, "Unknown escape sequence")),
+        }
+        if c_string && bytes.last() == Some(&0) {
+            return Err(error(&mut chars, "C-strings can not contain nul bytes"));
+        }
+    }
+    Ok(bytes)
+}
+
+/// Reads up to `max` hex digits.
+fn hex_digits(chars: &mut Peekable<CharIndices>, max: usize) -> String {
+    let mut digits = String::new();
+    while digits.len() < max {
+        match chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+            Some((_, c)) => digits.push(c),
+            None => break,
+        }
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_string(text: &str) -> Result<String> {
+        string(text, 0..text.len())
+    }
+
+    #[test]
+    fn it_decodes_escapes() {
+        assert_eq!(
+            decode_string(r#"a\n\t\r\\\"\{\}b"#).unwrap(),
+            "a\n\t\r\\\"{}b"
+        );
+        assert_eq!(decode_string(r"\x41\u{48}\u{1F600}").unwrap(), "AH😀");
+        assert_eq!(decode_string("{{a}} } }}}").unwrap(), "{a} } }}");
+        assert_eq!(c_string(r"\xFFz", 0..5).unwrap().as_bytes(), &[0xFF, b'z']);
+    }
+
+    #[test]
+    fn it_rejects_invalid_escapes() {
+        for text in [
+            r"\q",
+            r"\x4",
+            r"\xG1",
+            r"\xFF",
+            r"\u41",
+            r"\u{}",
+            r"\u{D800}",
+            r"\u{1234567}",
+            r"\u{41",
+        ] {
+            assert!(decode_string(text).is_err(), "{}", text);
+        }
+        for text in [r"a\0", r"\x00", r"\u{0}"] {
+            assert!(c_string(text, 0..text.len()).is_err(), "{}", text);
+        }
+    }
+}
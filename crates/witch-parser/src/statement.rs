@@ -1,14 +1,16 @@
 use std::collections::HashMap;
 use std::ffi::OsString;
+use std::ops::Range;
 use std::path::{Component, PathBuf};
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::lexer::{Kind, Lexer};
 use crate::Module;
 
-use crate::ast::Ast;
+use crate::ast::{Ast, Key, MatchArm, Pattern};
 use crate::r#type::{enum_declaration, interface_declaration, struct_declaration};
 use crate::types::Type;
+use witch_runtime::value::Value;
 
 use super::expression::{expression, function_expression};
 use super::r#type::type_literal;
@@ -23,9 +25,11 @@ pub fn imports<'input>(
 ) -> Result<()> {
     match p.peek() {
         Some(Kind::KwImport) => {
+            let start = p.cursor;
             p.consume(&Kind::KwImport)?;
 
             let mut path = build_path(p, vec![])?.iter().collect::<PathBuf>();
+            let span = start..p.cursor;
 
             // Ensure its a .witch file
             if !path.ends_with(".witch") {
@@ -37,19 +41,35 @@ pub fn imports<'input>(
             // - If it starts with ./, its local
             // - If it just starts with an identifier, its a Grimoire package (TODO)
             let module_file_path = match path.components().next() {
-                Some(Component::Normal(x)) => todo!("{:?}", x),
-                Some(Component::CurDir) => root_path.parent().unwrap().join(path).canonicalize(),
-                _ => panic!("very bad module path"),
-            }
-            .expect("oh no");
+                Some(Component::CurDir) => root_path.parent().unwrap_or(&root_path).join(&path),
+                _ => {
+                    return Err(Error::new(
+                        &format!(
+                            "Cannot import {}. Only paths starting with ./ can be imported",
+                            path.display()
+                        ),
+                        span,
+                        p.input,
+                    )
+                    .with_code("unknown_module"))
+                }
+            };
 
-            let source =
-                std::fs::read_to_string(module_file_path.clone()).expect("file read error");
+            let (module_file_path, source) = module_file_path
+                .canonicalize()
+                .and_then(|path| Ok((path.clone(), std::fs::read_to_string(path)?)))
+                .map_err(|err| {
+                    Error::new(
+                        &format!("Failed to read module {}", path.display()),
+                        span,
+                        p.input,
+                    )
+                    .with_code("io")
+                    .with_cause(err)
+                })?;
 
             let mut parser = Parser::new(&source);
-            let module = parser
-                .module(module_file_path.clone())
-                .expect("module parser error");
+            let module = parser.module(module_file_path.clone())?;
 
             import_map.insert(module_file_path, module);
 
@@ -59,7 +79,27 @@ pub fn imports<'input>(
     }
 }
 
+/// Parses a statement followed by the rest of the statements in the block.
+/// A statement which fails to parse is recorded as an error and replaced by an `Ast::Error`,
+/// after which parsing picks up again at the next statement boundary.
 pub fn statement<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
+    let start = p.cursor;
+    match statement_inner(p) {
+        Err(err) if p.recover => {
+            p.errors.push(err);
+            p.synchronize();
+            let end = p.cursor;
+            Ok(Ast::Statement {
+                stmt: Box::new(Ast::Error { span: start..end }),
+                rest: Box::new(statement(p)?),
+                span: start..end,
+            })
+        }
+        result => result,
+    }
+}
+
+fn statement_inner<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
     let start = p.cursor;
     let stmt = match p.peek() {
         Some(Kind::RBrace) => Ast::Nop,
@@ -127,17 +167,9 @@ pub fn statement<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
         }
         Some(Kind::KwLet) => {
             p.consume(&Kind::KwLet)?;
-            let (annotated_type, assignment) = assignment(p)?;
+            let (ident, annotated_type, assignment) = assignment(p)?;
             let end = p.cursor;
 
-            let ident = match assignment.clone() {
-                Ast::Assignment { lhs, .. } => match *lhs {
-                    Ast::Var(ident) => ident,
-                    _ => unimplemented!(),
-                },
-                _ => unimplemented!(),
-            };
-
             let assignment = Ast::Let {
                 ident,
                 annotated_type,
@@ -188,6 +220,37 @@ pub fn statement<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
                 span: start..end,
             }
         }
+        Some(Kind::KwWhile | Kind::KwLoop | Kind::KwFor) => {
+            let loop_ = loop_(p)?;
+            let end = p.cursor;
+            Ast::Statement {
+                stmt: Box::new(loop_),
+                rest: Box::new(statement(p)?),
+                span: start..end,
+            }
+        }
+        Some(Kind::KwBreak | Kind::KwContinue) => {
+            let stmt = if p.at(Kind::KwBreak) {
+                p.consume(&Kind::KwBreak)?;
+                Ast::Break {
+                    span: start..p.cursor,
+                }
+            } else {
+                p.consume(&Kind::KwContinue)?;
+                Ast::Continue {
+                    span: start..p.cursor,
+                }
+            };
+            if p.at(Kind::Semicolon) {
+                p.consume(&Kind::Semicolon)?;
+            }
+            let end = p.cursor;
+            Ast::Statement {
+                stmt: Box::new(stmt),
+                rest: Box::new(statement(p)?),
+                span: start..end,
+            }
+        }
         Some(Kind::At) => annotation(p)?,
         Some(_) => {
             let expr = expression(p)?;
@@ -239,11 +302,18 @@ fn build_path<'input>(
             Ok(components)
         }
 
-        _ => panic!("oops"),
+        _ => Err(Error::new(
+            "Unexpected token in import path",
+            p.peek_span(),
+            p.input,
+        )),
     }
 }
 
-fn assignment<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<(Option<Type>, Ast)> {
+/// Parses `name = expr` with an optional type annotation, returning the name along with the assignment.
+fn assignment<'input>(
+    p: &mut Parser<'input, Lexer<'input>>,
+) -> Result<(String, Option<Type>, Ast)> {
     let start = p.cursor;
     let token = p.consume(&Kind::Ident)?;
     let name = p.text(&token).to_string();
@@ -260,9 +330,13 @@ fn assignment<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<(Option<T
     let rhs = Box::new(expression(p)?);
 
     Ok((
+        name.clone(),
         annotated_type,
         Ast::Assignment {
-            lhs: Box::new(Ast::Var(name)),
+            lhs: Box::new(Ast::Var {
+                ident: name,
+                span: token.span,
+            }),
             rhs,
             span: start..p.cursor,
         },
@@ -308,6 +382,160 @@ fn if_else<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
     })
 }
 
+/// Parses a `while`, `loop` or `for ... in` loop. The latter two are desugared into `while` loops:
+/// `loop` runs for as long as `true` holds, and `for` walks the `Iterator` which the prelude's
+/// `iter` function returns for the iterable until its `next` method returns `None`, within a block
+/// of its own.
+/// # Example
+/// ```no
+/// while i < 10 {
+///     i = i + 1
+/// }
+///
+/// loop {
+///     break
+/// }
+///
+/// for item in [1, 2, 3] {
+///     total = total + item
+/// }
+/// ```
+fn loop_<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
+    let start = p.cursor;
+    let (predicate, binding) = match p.peek() {
+        Some(Kind::KwLoop) => {
+            p.consume(&Kind::KwLoop)?;
+            (Ast::Value(Value::Bool(true)), None)
+        }
+        Some(Kind::KwFor) => {
+            p.consume(&Kind::KwFor)?;
+            let token = p.consume(&Kind::Ident)?;
+            let ident = p.text(&token).to_string();
+            p.consume(&Kind::KwIn)?;
+            let iterable = expression(p)?;
+            let span = start..p.cursor;
+
+            // The iterator is named such that it can not clash with any variable
+            let iterator = format!("<iterator@{}>", start);
+            let iter = Ast::Call {
+                expr: Box::new(Ast::Var {
+                    ident: "iter".to_string(),
+                    span: span.clone(),
+                }),
+                args: vec![iterable],
+                span: span.clone(),
+            };
+
+            // Loops until the iterator runs out of items:
+            // let item = match iterator.next() { Some(item) -> item, None -> { break } }
+            let next = Ast::Call {
+                expr: Box::new(Ast::Member {
+                    container: Box::new(Ast::Var {
+                        ident: iterator.clone(),
+                        span: span.clone(),
+                    }),
+                    key: Key::String("next".to_string()),
+                    span: span.clone(),
+                }),
+                args: vec![],
+                span: span.clone(),
+            };
+            let arm = |pattern, body| MatchArm {
+                pattern,
+                guard: None,
+                body,
+                span: span.clone(),
+            };
+            let item = Ast::Match {
+                expr: Box::new(next),
+                arms: vec![
+                    arm(
+                        Pattern::Variant {
+                            enum_name: None,
+                            name: "Some".to_string(),
+                            fields: vec![Pattern::Binding(ident.clone())],
+                        },
+                        Ast::Var {
+                            ident: ident.clone(),
+                            span: span.clone(),
+                        },
+                    ),
+                    arm(
+                        Pattern::Binding("None".to_string()),
+                        Ast::Break { span: span.clone() },
+                    ),
+                ],
+                span: span.clone(),
+            };
+            (
+                Ast::Value(Value::Bool(true)),
+                Some((
+                    let_binding(iterator, iter, span.clone()),
+                    let_binding(ident, item, span),
+                )),
+            )
+        }
+        _ => {
+            p.consume(&Kind::KwWhile)?;
+            (expression(p)?, None)
+        }
+    };
+
+    p.consume(&Kind::LBrace)?;
+    let mut expr = statement(p)?;
+    p.consume(&Kind::RBrace)?;
+    let span = start..p.cursor;
+
+    let Some((iterator, item)) = binding else {
+        return Ok(Ast::While {
+            predicate: Box::new(predicate),
+            expr: Box::new(expr),
+            span,
+        });
+    };
+
+    // Every iteration starts by advancing the iterator into the loop variable
+    expr = Ast::Statement {
+        stmt: Box::new(item),
+        rest: Box::new(expr),
+        span: span.clone(),
+    };
+    let while_ = Ast::While {
+        predicate: Box::new(predicate),
+        expr: Box::new(expr),
+        span: span.clone(),
+    };
+    Ok(Ast::Block {
+        expr: Box::new(Ast::Statement {
+            stmt: Box::new(iterator),
+            rest: Box::new(Ast::Statement {
+                stmt: Box::new(while_),
+                rest: Box::new(Ast::Nop),
+                span: span.clone(),
+            }),
+            span: span.clone(),
+        }),
+        span,
+    })
+}
+
+/// Declares a variable, the way `let ident = expr` does.
+fn let_binding(ident: String, expr: Ast, span: Range<usize>) -> Ast {
+    Ast::Let {
+        ident: ident.clone(),
+        annotated_type: None,
+        expr: Box::new(Ast::Assignment {
+            lhs: Box::new(Ast::Var {
+                ident,
+                span: span.clone(),
+            }),
+            rhs: Box::new(expr),
+            span: span.clone(),
+        }),
+        span,
+    }
+}
+
 fn annotation<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
     let start = p.cursor;
     p.consume(&Kind::At)?;
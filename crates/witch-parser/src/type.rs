@@ -167,13 +167,19 @@ pub fn properties<'input>(
     Ok(res)
 }
 
-/// Parses an enum declaration:
-/// enum MyEnum<T,U> where T: Iterator {
+/// Parses an enum declaration, which like a struct may declare methods after its variants:
+/// ## Example
+/// ```no
+/// enum MyEnum[T, U] where T: Iterator {
 ///   One(T),
 ///   Two,
 ///   Three(U)
-/// }
 ///
+///   function is_two() -> bool {
+///     return self == MyEnum.Two
+///   }
+/// }
+/// ```
 pub fn enum_declaration<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Ast> {
     let start = p.cursor;
     p.consume(&Kind::KwEnum)?;
@@ -183,11 +189,11 @@ pub fn enum_declaration<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result
     let name = p.text(&token).to_string();
 
     // Possibly type variables
-    // <T, U>
-    let type_vars = if let Some(Kind::LAngle) = p.peek() {
-        p.consume(&Kind::LAngle)?;
+    // [T, U]
+    let type_vars = if let Some(Kind::LSquare) = p.peek() {
+        p.consume(&Kind::LSquare)?;
         let vars = p.repeating(vec![], Kind::Ident, Some(Kind::Comma))?;
-        p.consume(&Kind::RAngle)?;
+        p.consume(&Kind::RSquare)?;
         vars.iter()
             .map(|t| p.text(t).to_string())
             .collect::<Vec<String>>()
@@ -198,12 +204,12 @@ pub fn enum_declaration<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result
     // Possibly constraints for the type variables
     let constraints = where_constraints(p)?;
 
-    let mut generics = HashMap::default();
+    let mut generics = vec![];
     for v in type_vars.into_iter() {
-        generics.insert(v, Type::Any);
-    }
-    for (k, v) in constraints.into_iter() {
-        generics.entry(k).and_modify(|e| *e = v);
+        generics.push((
+            v.clone(),
+            constraints.get(&v).unwrap_or(&Type::Any).to_owned(),
+        ));
     }
 
     // Start the block
@@ -212,9 +218,9 @@ pub fn enum_declaration<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result
     // List variants
     let variants = enum_variants(p, vec![])?;
 
-    // Last variant may have an automatic semicolon. Disregard it.
-    if p.at(Kind::Semicolon) {
-        p.consume(&Kind::Semicolon)?;
+    let mut methods = vec![];
+    while p.at(Kind::KwFn) {
+        methods.push(function_declaration(p)?);
     }
 
     // End block
@@ -222,7 +228,11 @@ pub fn enum_declaration<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result
 
     Ok(Ast::Type {
         name,
-        decl: TypeDecl::Enum { generics, variants },
+        decl: TypeDecl::Enum {
+            generics,
+            variants,
+            methods,
+        },
         span: (start..p.cursor),
     })
 }
@@ -293,7 +303,7 @@ pub fn type_literal<'input>(p: &mut Parser<'input, Lexer<'input>>) -> Result<Typ
         kind => {
             return Err(Error::new(
                 &format!("Unknown type error. Expected type literal, got: {:?}", kind),
-                p.cursor..p.cursor,
+                p.peek_span(),
                 p.input,
             ))
         }
@@ -380,27 +390,24 @@ pub fn enum_variants<'input>(
         Some(Kind::Ident) => {
             let token = p.consume(&Kind::Ident)?;
             let name = p.text(&token).to_string();
-            match p.peek() {
-                Some(Kind::Comma) => {
-                    p.consume(&Kind::Comma)?;
-                    enum_variants(p, variants)
-                }
-                Some(Kind::LParen) => {
-                    p.consume(&Kind::LParen)?;
-                    let types = list_types(p, vec![])?;
-                    variants.push(EnumVariant {
-                        name,
-                        discriminant: variants.len(),
-                        types: Some(types),
-                    });
-                    p.consume(&Kind::RParen)?;
-                    enum_variants(p, variants)
-                }
-                _ => Ok(variants),
-            }
+            let types = if p.at(Kind::LParen) {
+                p.consume(&Kind::LParen)?;
+                let types = list_types(p, vec![])?;
+                p.consume(&Kind::RParen)?;
+                Some(types)
+            } else {
+                None
+            };
+            variants.push(EnumVariant {
+                name,
+                discriminant: variants.len(),
+                types,
+            });
+            enum_variants(p, variants)
         }
-        Some(Kind::Comma) => {
-            p.consume(&Kind::Comma)?;
+        // Variants are separated by commas or newlines
+        Some(kind @ (Kind::Comma | Kind::Semicolon)) => {
+            p.consume(&kind)?;
             enum_variants(p, variants)
         }
         _ => Ok(variants),
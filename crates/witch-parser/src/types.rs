@@ -1,3 +1,4 @@
+use core::fmt;
 use core::hash::{Hash, Hasher};
 use core::mem::discriminant;
 use std::collections::HashMap;
@@ -19,8 +20,9 @@ pub enum TypeDecl {
         properties: HashMap<String, Type>,
     },
     Enum {
-        generics: HashMap<String, Type>,
+        generics: Vec<(String, Type)>,
         variants: Vec<EnumVariant>,
+        methods: Vec<(String, Ast)>,
     },
 }
 
@@ -37,6 +39,12 @@ pub struct EnumVariant {
     pub types: Option<Vec<Type>>,
 }
 
+impl EnumVariant {
+    fn is_hashable(&self) -> bool {
+        self.types.iter().flatten().all(Type::is_hashable)
+    }
+}
+
 #[derive(Debug, Clone)]
 #[repr(u8)]
 pub enum Type {
@@ -129,6 +137,9 @@ pub enum Type {
     /// A list of some type
     List(Box<Self>),
 
+    /// A map from keys of one type to values of another
+    Map(Box<Self>, Box<Self>),
+
     /// A custom struct type
     Struct {
         /// Name of the struct type, or
@@ -159,9 +170,19 @@ pub enum Type {
         generics: Vec<(String, Self)>,
     },
 
-    /// An enum is simply a list of its variants.
+    /// An enum is a list of its variants, which like a struct may have methods and generics.
     /// You can't instantiate an enum without a variant.
-    Enum(Vec<EnumVariant>),
+    Enum {
+        name: String,
+        variants: Vec<EnumVariant>,
+
+        /// Unlike those of structs, these are only resolved once a method is accessed, as they
+        /// tend to refer back to the enum itself
+        methods: HashMap<String, (Self, usize)>,
+
+        /// A list of defined type variables along with what they resolve to, e.g. [T: usize]
+        generics: Vec<(String, Self)>,
+    },
 
     /// An enum variant holds its name, discriminant, associated data types,
     /// as well as any generics used
@@ -200,6 +221,9 @@ impl PartialEq for Type {
             // Lists are equal based on their contained type
             (Type::List(v1), Type::List(v2)) => v1 == v2,
 
+            // Maps are equal based on their key and value types
+            (Type::Map(k1, v1), Type::Map(k2, v2)) => k1 == k2 && v1 == v2,
+
             // Functions are compared on their arguments and return types
             (
                 Type::Function {
@@ -280,11 +304,29 @@ impl PartialEq for Type {
                 t.implements(properties)
             }
 
+            // Enums are nominally typed, along with the types their variants hold
+            (
+                Type::Enum {
+                    name: n1,
+                    variants: v1,
+                    ..
+                },
+                Type::Enum {
+                    name: n2,
+                    variants: v2,
+                    ..
+                },
+            ) => n1 == n2 && v1 == v2,
+
             // Checks whether an Enum Variant is of type Enum.
             // E.g. MyEnum.One == MyEnum
-            (Type::Enum(variants), Type::EnumVariant(variant)) => variants.contains(variant),
-            (Type::EnumVariant(variant), Type::Enum(variants)) => variants.contains(variant),
+            (Type::Enum { variants, .. }, Type::EnumVariant(variant))
+            | (Type::EnumVariant(variant), Type::Enum { variants, .. }) => {
+                variants.contains(variant)
+            }
 
+            // Type variables which are yet to be resolved are only equal to themselves
+            (Type::TypeVar(n1), Type::TypeVar(n2)) if n1 == n2 => true,
             (Type::TypeVar(name), x) | (x, Type::TypeVar(name)) => {
                 panic!(
                     "cant compare type var {} with {:?}, need to be resolved",
@@ -310,11 +352,143 @@ impl Hash for Type {
     }
 }
 
+/// Formats types the way they are written in source, for diagnostics. Lists and maps are written
+/// like their literals, e.g. `[usize]` and `{string: usize}`.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Writes `items` separated by commas
+        fn join<T>(
+            f: &mut fmt::Formatter<'_>,
+            items: &[T],
+            write: impl Fn(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+        ) -> fmt::Result {
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                write(f, item)?;
+            }
+            Ok(())
+        }
+
+        /// Writes the generics of a named type, e.g. `[usize, string]`. Those which are not yet
+        /// known are written by their name.
+        fn generics(f: &mut fmt::Formatter<'_>, generics: &[(String, Type)]) -> fmt::Result {
+            if generics.is_empty() {
+                return Ok(());
+            }
+            write!(f, "[")?;
+            join(f, generics, |f, (name, ty)| match ty {
+                Type::Unknown | Type::Any => write!(f, "{}", name),
+                ty => write!(f, "{}", ty),
+            })?;
+            write!(f, "]")
+        }
+
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::String => write!(f, "string"),
+            Type::CString => write!(f, "cstring"),
+            Type::Bool => write!(f, "bool"),
+            Type::Any => write!(f, "any"),
+            Type::I8 => write!(f, "i8"),
+            Type::U8 => write!(f, "u8"),
+            Type::I16 => write!(f, "i16"),
+            Type::U16 => write!(f, "u16"),
+            Type::I32 => write!(f, "i32"),
+            Type::U32 => write!(f, "u32"),
+            Type::I64 => write!(f, "i64"),
+            Type::U64 => write!(f, "u64"),
+            Type::I128 => write!(f, "i128"),
+            Type::U128 => write!(f, "u128"),
+            Type::Isize => write!(f, "isize"),
+            Type::Usize => write!(f, "usize"),
+            Type::Char => write!(f, "char"),
+            Type::F32 => write!(f, "f32"),
+            Type::F64 => write!(f, "f64"),
+            Type::GenericFunctionStub { .. } => write!(f, "function"),
+            Type::Function {
+                args,
+                returns,
+                is_variadic,
+                generics: type_vars,
+                ..
+            } => {
+                generics(f, type_vars)?;
+                write!(f, "(")?;
+                join(f, args, |f, arg| write!(f, "{}", arg))?;
+                if *is_variadic {
+                    write!(f, "...")?;
+                }
+                write!(f, ") -> {}", returns)
+            }
+            Type::List(item) => write!(f, "[{}]", item),
+            Type::Map(key, value) => write!(f, "{{{}: {}}}", key, value),
+            Type::Struct {
+                name: Some(name),
+                generics: type_vars,
+                ..
+            }
+            | Type::Interface {
+                name,
+                generics: type_vars,
+                ..
+            }
+            | Type::Enum {
+                name,
+                generics: type_vars,
+                ..
+            } => {
+                write!(f, "{}", name)?;
+                generics(f, type_vars)
+            }
+            Type::Struct {
+                name: None, fields, ..
+            } => {
+                write!(f, "{{ ")?;
+                join(f, fields, |f, (name, ty)| write!(f, "{}: {}", name, ty))?;
+                write!(f, " }}")
+            }
+            Type::EnumVariant(variant) => write!(f, "{}", variant.name),
+            Type::TypeVar(name) | Type::Var(name) => write!(f, "{}", name),
+            Type::WithSubstitutions(ty, substitutions) => {
+                write!(f, "{}[", ty)?;
+                join(f, substitutions, |f, ty| write!(f, "{}", ty))?;
+                write!(f, "]")
+            }
+            Type::Intersection(types) => {
+                for (idx, ty) in types.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, " + ")?;
+                    }
+                    write!(f, "{}", ty)?;
+                }
+                Ok(())
+            }
+            Type::Module { path } => write!(f, "module {}", path.display()),
+            Type::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
 impl From<&Value> for Type {
     fn from(value: &Value) -> Type {
         match value {
+            Value::Bool(_) => Type::Bool,
+            Value::I8(_) => Type::I8,
+            Value::U8(_) => Type::U8,
+            Value::I16(_) => Type::I16,
+            Value::U16(_) => Type::U16,
+            Value::I32(_) => Type::I32,
+            Value::U32(_) => Type::U32,
+            Value::I64(_) => Type::I64,
+            Value::U64(_) => Type::U64,
+            Value::I128(_) => Type::I128,
+            Value::U128(_) => Type::U128,
             Value::Usize(_) => Type::Usize,
             Value::Isize(_) => Type::Isize,
+            Value::F32(_) => Type::F32,
+            Value::F64(_) => Type::F64,
             Value::List(vec) => {
                 if !vec.is_empty() {
                     Type::List(Box::new((&vec[0]).into()))
@@ -322,9 +496,18 @@ impl From<&Value> for Type {
                     Type::List(Box::new(Type::Any))
                 }
             }
+            Value::Map(map) => match map.iter().next() {
+                Some((key, value)) => Type::Map(
+                    Box::new((&Value::from(key.clone())).into()),
+                    Box::new(value.into()),
+                ),
+                None => Type::Map(Box::new(Type::Any), Box::new(Type::Any)),
+            },
             Value::String(_) => Type::String,
             Value::CString(_) => Type::CString,
-            Value::Function(_) => Type::Unknown,
+            // Values do not know which enum they are a variant of
+            Value::Function(_) | Value::Variant(_) => Type::Unknown,
+            Value::Void => Type::Void,
             x => todo!("{:?}", x),
         }
     }
@@ -333,12 +516,11 @@ impl From<&Value> for Type {
 impl From<&BuiltinInfo> for Type {
     fn from(info: &BuiltinInfo) -> Self {
         Type::Function {
-            args: info
-                .inputs
-                .split(',')
-                .map(|s| Type::from_str(s, vec![]))
+            args: split_rust_types(info.inputs)
+                .into_iter()
+                .map(Type::from_rust)
                 .collect(),
-            returns: Box::new(Type::from_str(info.output, vec![])),
+            returns: Box::new(Type::from_rust(info.output)),
             is_variadic: false,
             is_method: false,
             generics: vec![],
@@ -346,6 +528,26 @@ impl From<&BuiltinInfo> for Type {
     }
 }
 
+/// Splits a comma separated list of Rust types, leaving the commas within generic types be.
+fn split_rust_types(types: &str) -> Vec<&str> {
+    let mut depth = 0;
+    let mut start = 0;
+    let mut split = vec![];
+    for (i, c) in types.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(&types[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    split.push(&types[start..]);
+    split
+}
+
 impl From<&Ast> for Type {
     fn from(ast: &Ast) -> Type {
         match ast {
@@ -374,15 +576,47 @@ impl From<&Ast> for Type {
 impl Type {
     pub fn allowed_infix_operators(&self, rhs: &Type) -> Vec<Operator> {
         match (self, rhs) {
-            (Type::Usize, Type::Usize) => vec![
+            (a, b) if a.is_numeric() && a == b => vec![
                 Operator::Add,
                 Operator::Sub,
                 Operator::Div,
                 Operator::Mul,
                 Operator::Mod,
+                Operator::Pow,
+                Operator::Eq,
+                Operator::NotEq,
+                Operator::Lt,
+                Operator::Lte,
+                Operator::Gt,
+                Operator::Gte,
+            ],
+            (Type::String, Type::String) => vec![
+                Operator::Add,
+                Operator::Eq,
+                Operator::NotEq,
+                Operator::Lt,
+                Operator::Lte,
+                Operator::Gt,
+                Operator::Gte,
+            ],
+            (Type::Char, Type::Char) => vec![
+                Operator::Eq,
+                Operator::NotEq,
                 Operator::Lt,
+                Operator::Lte,
+                Operator::Gt,
+                Operator::Gte,
             ],
             (Type::String, Type::Usize) => vec![Operator::Mul],
+            (Type::List(_), _) if self == rhs => {
+                vec![Operator::Add, Operator::Eq, Operator::NotEq]
+            }
+            (Type::Bool, Type::Bool) => {
+                vec![Operator::Eq, Operator::NotEq, Operator::And, Operator::Or]
+            }
+            (Type::Map(..) | Type::Struct { .. } | Type::Enum { .. }, _) if self == rhs => {
+                vec![Operator::Eq, Operator::NotEq]
+            }
             _ => vec![],
         }
     }
@@ -439,23 +673,176 @@ impl Type {
         true
     }
 
-    /// Provides a map of builtin methods for types. TODO the actual methods...
-    pub fn builtin_methods(&self) -> HashMap<String, (Type, usize)> {
-        vec![(
-            "index".to_string(),
-            (
-                Type::Function {
-                    args: vec![Type::Usize],
-                    returns: Box::new(Type::Usize),
-                    is_variadic: false,
-                    generics: vec![],
-                    is_method: true,
-                },
-                0,
-            ),
-        )]
-        .into_iter()
-        .collect()
+    /// Provides a map of the methods a type has through builtins: <Name, (Type, builtin name)>.
+    /// The builtins take the value they are called on as their first argument.
+    pub fn builtin_methods(&self) -> HashMap<String, (Type, &'static str)> {
+        let method = |args: Vec<Type>, returns: Type| Type::Function {
+            args,
+            returns: Box::new(returns),
+            is_variadic: false,
+            generics: vec![],
+            is_method: true,
+        };
+        let option = |ty: &Type| {
+            Type::WithSubstitutions(
+                Box::new(Type::TypeVar("Option".to_string())),
+                vec![ty.clone()],
+            )
+        };
+        let result = |ty: Type| {
+            Type::WithSubstitutions(
+                Box::new(Type::TypeVar("Result".to_string())),
+                vec![ty, Type::String],
+            )
+        };
+        let methods = match self {
+            Type::String => vec![
+                ("len", method(vec![], Type::Usize), "witch_string_len"),
+                (
+                    "byte_len",
+                    method(vec![], Type::Usize),
+                    "witch_string_byte_len",
+                ),
+                (
+                    "slice",
+                    method(vec![Type::Usize, Type::Usize], Type::String),
+                    "witch_string_slice",
+                ),
+                (
+                    "split",
+                    method(vec![Type::String], Type::List(Box::new(Type::String))),
+                    "witch_string_split",
+                ),
+                ("trim", method(vec![], Type::String), "witch_string_trim"),
+                (
+                    "contains",
+                    method(vec![Type::String], Type::Bool),
+                    "witch_string_contains",
+                ),
+                (
+                    "replace",
+                    method(vec![Type::String, Type::String], Type::String),
+                    "witch_string_replace",
+                ),
+                (
+                    "to_upper",
+                    method(vec![], Type::String),
+                    "witch_string_to_upper",
+                ),
+                (
+                    "to_lower",
+                    method(vec![], Type::String),
+                    "witch_string_to_lower",
+                ),
+                (
+                    "to_string",
+                    method(vec![], Type::String),
+                    "witch_string_from",
+                ),
+                (
+                    "parse_usize",
+                    method(vec![], result(Type::Usize)),
+                    "witch_string_parse_usize",
+                ),
+                (
+                    "parse_isize",
+                    method(vec![], result(Type::Isize)),
+                    "witch_string_parse_isize",
+                ),
+                (
+                    "parse_f64",
+                    method(vec![], result(Type::F64)),
+                    "witch_string_parse_f64",
+                ),
+            ],
+            ty if ty.is_numeric() || matches!(ty, Type::Bool | Type::Char) => vec![(
+                "to_string",
+                method(vec![], Type::String),
+                "witch_string_from",
+            )],
+            Type::List(t) => vec![
+                ("len", method(vec![], Type::Usize), "witch_list_len"),
+                (
+                    "push",
+                    method(vec![*t.clone()], Type::Void),
+                    "witch_list_push",
+                ),
+                ("pop", method(vec![], option(t)), "witch_list_pop"),
+                (
+                    "insert",
+                    method(vec![Type::Usize, *t.clone()], Type::Void),
+                    "witch_list_insert",
+                ),
+                (
+                    "remove",
+                    method(vec![Type::Usize], *t.clone()),
+                    "witch_list_remove",
+                ),
+                (
+                    "set",
+                    method(vec![Type::Usize, *t.clone()], Type::Void),
+                    "witch_list_set",
+                ),
+                (
+                    "slice",
+                    method(vec![Type::Usize, Type::Usize], self.clone()),
+                    "witch_list_slice",
+                ),
+            ],
+            Type::Map(k, v) => vec![
+                ("len", method(vec![], Type::Usize), "witch_map_len"),
+                (
+                    "contains_key",
+                    method(vec![*k.clone()], Type::Bool),
+                    "witch_map_contains_key",
+                ),
+                ("get", method(vec![*k.clone()], option(v)), "witch_map_get"),
+                (
+                    "insert",
+                    method(vec![*k.clone(), *v.clone()], option(v)),
+                    "witch_map_insert",
+                ),
+                (
+                    "remove",
+                    method(vec![*k.clone()], option(v)),
+                    "witch_map_remove",
+                ),
+                (
+                    "keys",
+                    method(vec![], Type::List(k.clone())),
+                    "witch_map_keys",
+                ),
+                (
+                    "values",
+                    method(vec![], Type::List(v.clone())),
+                    "witch_map_values",
+                ),
+            ],
+            _ => vec![],
+        };
+        methods
+            .into_iter()
+            .map(|(name, ty, builtin)| (name.to_string(), (ty, builtin)))
+            .collect()
+    }
+
+    /// Whether values of the type can be the keys of a map. Floats can not, as they are not
+    /// equal to themselves when NaN, and neither can the types which are not compared by value.
+    /// Structs and enums can as long as all of their fields and payloads can.
+    pub fn is_hashable(&self) -> bool {
+        match self {
+            Type::F32 | Type::F64 | Type::List(_) | Type::Map(..) | Type::Function { .. } => false,
+            Type::Struct { fields, .. } => fields.iter().all(|(_, ty)| ty.is_hashable()),
+            Type::Enum { variants, .. } => variants.iter().all(EnumVariant::is_hashable),
+            Type::EnumVariant(variant) => variant.is_hashable(),
+            _ => true,
+        }
+    }
+
+    /// Whether the type is a number which can be negative, i.e. a signed integer or a float.
+    pub fn is_signed(&self) -> bool {
+        use Type::*;
+        matches!(self, Isize | I8 | I16 | I32 | I64 | I128 | F32 | F64)
     }
 
     pub fn is_numeric(&self) -> bool {
@@ -466,6 +853,40 @@ impl Type {
         )
     }
 
+    /// Parses the lowercased Rust type of a builtin's argument or return value, such as
+    /// `result < string , string >`. A Rust `Result` becomes the prelude's `Result` enum, unless
+    /// its error is an `ErrorKind`, which stops the VM rather than being returned.
+    pub fn from_rust(ty: &str) -> Type {
+        match ty
+            .trim()
+            .strip_suffix('>')
+            .and_then(|ty| ty.split_once('<'))
+        {
+            Some((name, inner)) => {
+                if let ("result", [ok, "errorkind"]) = (
+                    name.trim(),
+                    split_rust_types(inner)
+                        .iter()
+                        .map(|ty| ty.trim())
+                        .collect::<Vec<_>>()
+                        .as_slice(),
+                ) {
+                    return Type::from_rust(ok);
+                }
+                let name = match name.trim() {
+                    "result" => "Result",
+                    name => name,
+                };
+                let inner = split_rust_types(inner)
+                    .into_iter()
+                    .map(Type::from_rust)
+                    .collect();
+                Type::from_str(name, inner)
+            }
+            None => Type::from_str(ty.trim(), vec![]),
+        }
+    }
+
     pub fn from_str(str: &str, inner: Vec<Type>) -> Type {
         let ty = match str {
             "void" => Type::Void,
@@ -474,6 +895,10 @@ impl Type {
             "cstring" => Type::CString,
             "c_int" => Type::I32, // TODO are there any systems where this is not true???
             "any" => Type::Any,
+            // Builtins take `Value`s, or `Ref`s to values on the heap, of any type
+            "value" | "ref" => Type::Any,
+            "list" => Type::List(Box::new(Type::Any)),
+            "map" => Type::Map(Box::new(Type::Any), Box::new(Type::Any)),
             "i8" => Type::I8,
             "u8" => Type::U8,
             "i16" => Type::I16,
@@ -486,6 +911,8 @@ impl Type {
             "u128" => Type::U128,
             "isize" => Type::Isize,
             "usize" => Type::Usize,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
 
             name => Type::TypeVar(name.to_string()),
         };
@@ -2,15 +2,15 @@ use super::BuiltinInfo;
 use crate::vm::Vm;
 use witch_macro::builtin;
 
-use alloc::string::String;
 use alloc::ffi::CString;
+use alloc::string::{String, ToString};
 
 #[builtin]
-pub fn witch_conv_cstring_to_string(_vm: &mut Vm, string: CString) -> String {
-    string.into_string().unwrap()
+pub fn witch_conv_cstring_to_string(_vm: &mut Vm, string: CString) -> Result<String, String> {
+    string.into_string().map_err(|err| err.to_string())
 }
 
 #[builtin]
-pub fn witch_conv_string_to_cstring(_vm: &mut Vm, string: String) -> CString {
-    CString::new(string).unwrap()
+pub fn witch_conv_string_to_cstring(_vm: &mut Vm, string: String) -> Result<CString, String> {
+    CString::new(string).map_err(|err| err.to_string())
 }
@@ -0,0 +1,76 @@
+use super::{copy, BuiltinInfo, Ref};
+use crate::error::ErrorKind;
+use crate::value::{List, Value};
+use crate::vm::Vm;
+use witch_macro::builtin;
+
+#[builtin]
+pub fn witch_list_len(vm: &mut Vm, list: Ref) -> Result<usize, ErrorKind> {
+    Ok(vm.heap.list_mut(list.0)?.len())
+}
+
+#[builtin]
+pub fn witch_list_push(vm: &mut Vm, list: Ref, item: Value) -> Result<(), ErrorKind> {
+    let len = vm.heap.list_mut(list.0)?.len();
+    let ptr = vm.heap.insert(item)?;
+    vm.heap.list_insert(list.0, len, ptr)
+}
+
+#[builtin]
+pub fn witch_list_pop(vm: &mut Vm, list: Ref) -> Result<Option<Value>, ErrorKind> {
+    match vm.heap.list_mut(list.0)?.len() {
+        0 => Ok(None),
+        len => {
+            let ptr = vm.heap.list_remove(list.0, len - 1)?;
+            Ok(Some(copy(vm, ptr)?))
+        }
+    }
+}
+
+#[builtin]
+pub fn witch_list_insert(
+    vm: &mut Vm,
+    list: Ref,
+    index: usize,
+    item: Value,
+) -> Result<(), ErrorKind> {
+    let ptr = vm.heap.insert(item)?;
+    vm.heap.list_insert(list.0, index, ptr)
+}
+
+#[builtin]
+pub fn witch_list_remove(vm: &mut Vm, list: Ref, index: usize) -> Result<Value, ErrorKind> {
+    let ptr = vm.heap.list_remove(list.0, index)?;
+    copy(vm, ptr)
+}
+
+/// Replaces the item at `index`, which is what assigning to an index of a list compiles to.
+#[builtin]
+pub fn witch_list_set(vm: &mut Vm, list: Ref, index: usize, item: Value) -> Result<(), ErrorKind> {
+    // Fails before allocating if the index is out of bounds
+    vm.heap.get_list_item_ptr(list.0, index)?;
+    let ptr = vm.heap.insert(item)?;
+    vm.heap.list_mut(list.0)?[index] = ptr;
+    Ok(())
+}
+
+/// Copies the items from `start` up to but not including `end` into a new list.
+#[builtin]
+pub fn witch_list_slice(
+    vm: &mut Vm,
+    list: Ref,
+    start: usize,
+    end: usize,
+) -> Result<List, ErrorKind> {
+    let items = vm.heap.list_mut(list.0)?;
+    let Some(ptrs) = items.get(start..end).map(<[usize]>::to_vec) else {
+        return Err(ErrorKind::IndexOutOfBounds {
+            index: if start > end { start } else { end },
+            len: items.len(),
+        });
+    };
+    ptrs.into_iter()
+        .map(|ptr| copy(vm, ptr))
+        .collect::<Result<_, _>>()
+        .map(List)
+}
@@ -0,0 +1,64 @@
+use super::{copy, BuiltinInfo, Ref};
+use crate::error::ErrorKind;
+use crate::value::{List, MapKey, Value};
+use crate::vm::Vm;
+use witch_macro::builtin;
+
+#[builtin]
+pub fn witch_map_len(vm: &mut Vm, map: Ref) -> Result<usize, ErrorKind> {
+    Ok(vm.heap.map_mut(map.0)?.len())
+}
+
+#[builtin]
+pub fn witch_map_contains_key(vm: &mut Vm, map: Ref, key: Value) -> Result<bool, ErrorKind> {
+    let key = MapKey::try_from(key)?;
+    Ok(vm.heap.map_mut(map.0)?.contains_key(&key))
+}
+
+#[builtin]
+pub fn witch_map_get(vm: &mut Vm, map: Ref, key: Value) -> Result<Option<Value>, ErrorKind> {
+    let key = MapKey::try_from(key)?;
+    match vm.heap.map_mut(map.0)?.get(&key).copied() {
+        Some(ptr) => Ok(Some(copy(vm, ptr)?)),
+        None => Ok(None),
+    }
+}
+
+#[builtin]
+pub fn witch_map_insert(
+    vm: &mut Vm,
+    map: Ref,
+    key: Value,
+    val: Value,
+) -> Result<Option<Value>, ErrorKind> {
+    let key = MapKey::try_from(key)?;
+    let ptr = vm.heap.insert(val)?;
+    match vm.heap.map_insert(map.0, key, ptr)? {
+        Some(ptr) => Ok(Some(copy(vm, ptr)?)),
+        None => Ok(None),
+    }
+}
+
+#[builtin]
+pub fn witch_map_remove(vm: &mut Vm, map: Ref, key: Value) -> Result<Option<Value>, ErrorKind> {
+    let key = MapKey::try_from(key)?;
+    match vm.heap.map_remove(map.0, &key)? {
+        Some(ptr) => Ok(Some(copy(vm, ptr)?)),
+        None => Ok(None),
+    }
+}
+
+#[builtin]
+pub fn witch_map_keys(vm: &mut Vm, map: Ref) -> Result<List, ErrorKind> {
+    let keys = vm.heap.map_mut(map.0)?.keys().cloned();
+    Ok(List(keys.map(Value::from).collect()))
+}
+
+#[builtin]
+pub fn witch_map_values(vm: &mut Vm, map: Ref) -> Result<List, ErrorKind> {
+    let ptrs: alloc::vec::Vec<usize> = vm.heap.map_mut(map.0)?.values().copied().collect();
+    ptrs.into_iter()
+        .map(|ptr| copy(vm, ptr))
+        .collect::<Result<_, _>>()
+        .map(List)
+}
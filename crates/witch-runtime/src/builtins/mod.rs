@@ -1,14 +1,29 @@
-use crate::{stack::Entry, value::Value, vm::Vm};
+use crate::{
+    error::{ErrorKind, Result},
+    stack::{Entry, Pointer},
+    value::{List, Value},
+    vm::Vm,
+};
 use alloc::boxed::Box;
+use alloc::ffi::CString;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
-use core::convert::{From, Into};
+use core::convert::{Into, TryFrom};
+use core::hash::Hash;
+use hashbrown::HashMap;
 use paste::paste;
 mod sys;
 use sys::*;
 mod conv;
 use conv::*;
+mod list;
+use list::*;
+mod map;
+use map::*;
+mod string;
+use string::*;
 
 #[derive(Debug)]
 pub struct BuiltinInfo {
@@ -42,87 +57,194 @@ macro_rules! builtins {
 builtins! {
     witch_libc_puts,
     witch_conv_cstring_to_string,
-    witch_conv_string_to_cstring
+    witch_conv_string_to_cstring,
+    witch_list_len,
+    witch_list_push,
+    witch_list_pop,
+    witch_list_insert,
+    witch_list_remove,
+    witch_list_set,
+    witch_list_slice,
+    witch_map_len,
+    witch_map_contains_key,
+    witch_map_get,
+    witch_map_insert,
+    witch_map_remove,
+    witch_map_keys,
+    witch_map_values,
+    witch_string_len,
+    witch_string_byte_len,
+    witch_string_slice,
+    witch_string_split,
+    witch_string_trim,
+    witch_string_contains,
+    witch_string_replace,
+    witch_string_to_upper,
+    witch_string_to_lower,
+    witch_string_from,
+    witch_string_parse_usize,
+    witch_string_parse_isize,
+    witch_string_parse_f64
 }
 
 pub struct Builtin(pub Handler);
 impl Builtin {
     pub fn new<Args: 'static>(func: Box<dyn Function<Args>>) -> Self {
-        Self(Arc::new(move |vm| {
-            (*func).fn_call(vm);
-        }))
+        Self(Arc::new(move |vm| (*func).fn_call(vm)))
     }
 }
 
-type Handler = Arc<dyn Fn(&mut Vm) + Send + Sync>;
+type Handler = Arc<dyn Fn(&mut Vm) -> Result<()> + Send + Sync>;
 
 pub trait Function<Args>: 'static + Send + Sync {
-    fn fn_call(&self, vm: &mut Vm);
+    fn fn_call(&self, vm: &mut Vm) -> Result<()>;
+}
+
+/// Converts an entry popped off the stack into an argument of a builtin.
+pub trait Arg: Sized {
+    fn from_entry(vm: &mut Vm, entry: Entry) -> Result<Self>;
+}
+
+/// Arguments which are copies of the values they are called with.
+macro_rules! value_args {
+    ($($ty:ty),*) => {
+        $(impl Arg for $ty {
+            fn from_entry(vm: &mut Vm, entry: Entry) -> Result<Self> {
+                let value = vm.entry_to_value(entry)?;
+                <$ty>::try_from(value).map_err(|kind| vm.error(kind))
+            }
+        })*
+    };
+}
+
+value_args!(usize, String, CString, List);
+
+impl Arg for Value {
+    fn from_entry(vm: &mut Vm, entry: Entry) -> Result<Self> {
+        vm.entry_to_value(entry)
+    }
+}
+
+impl<K, V> Arg for HashMap<K, V>
+where
+    K: TryFrom<Value, Error = ErrorKind> + Eq + Hash,
+    V: TryFrom<Value, Error = ErrorKind>,
+{
+    fn from_entry(vm: &mut Vm, entry: Entry) -> Result<Self> {
+        let value = vm.entry_to_value(entry)?;
+        HashMap::try_from(value).map_err(|kind| vm.error(kind))
+    }
+}
+
+/// An argument which refers to an object on the heap rather than being a copy of it, for builtins
+/// which modify lists or maps in place. Any other value gets moved to the heap.
+pub struct Ref(pub usize);
+
+impl Arg for Ref {
+    fn from_entry(vm: &mut Vm, entry: Entry) -> Result<Self> {
+        match entry {
+            Entry::Pointer(Pointer::Heap(ptr)) => Ok(Ref(ptr)),
+            entry => {
+                let value = vm.entry_to_value(entry)?;
+                vm.heap
+                    .insert(value)
+                    .map(Ref)
+                    .map_err(|kind| vm.error(kind))
+            }
+        }
+    }
+}
+
+/// Copies the value of the heap object at `ptr`, such as an item of a list taken by `Ref`.
+fn copy(vm: &mut Vm, ptr: usize) -> core::result::Result<Value, ErrorKind> {
+    Ok(vm.heap.get(ptr)?.borrow().clone())
+}
+
+/// What a builtin returns: a value, or an `ErrorKind` which stops the VM, such as when a builtin
+/// runs into the limits of the heap.
+pub trait Output {
+    fn into_value(self) -> core::result::Result<Value, ErrorKind>;
+}
+
+impl<T: Into<Value>> Output for T {
+    fn into_value(self) -> core::result::Result<Value, ErrorKind> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Value>> Output for core::result::Result<T, ErrorKind> {
+    fn into_value(self) -> core::result::Result<Value, ErrorKind> {
+        self.map(Into::into)
+    }
+}
+
+/// Pushes the result of a builtin onto the stack.
+fn output(vm: &mut Vm, ret: impl Output) -> Result<()> {
+    let return_value = ret.into_value().map_err(|kind| vm.error(kind))?;
+    vm.push_value(return_value)
 }
 
 impl<Func, Return> Function<()> for Func
 where
     Func: 'static + Send + Sync + Fn(&mut Vm) -> Return,
-    Return: Into<Value>,
+    Return: Output,
 {
-    fn fn_call(&self, vm: &mut Vm) {
+    fn fn_call(&self, vm: &mut Vm) -> Result<()> {
         let ret = self(vm);
-
-        let return_value = Into::<Value>::into(ret);
-        vm.push_value(return_value);
+        output(vm, ret)
     }
 }
 
 impl<Func, A, Return> Function<A> for Func
 where
     Func: 'static + Send + Sync + Fn(&mut Vm, A) -> Return,
-    Return: Into<Value>,
-    A: From<Value>,
+    Return: Output,
+    A: Arg,
 {
-    fn fn_call(&self, vm: &mut Vm) {
-        let a = vm.pop_value().unwrap().into();
+    fn fn_call(&self, vm: &mut Vm) -> Result<()> {
+        let a = vm.pop()?;
+        let a = A::from_entry(vm, a)?;
         let ret = self(vm, a);
-
-        let return_value = Into::<Value>::into(ret);
-        vm.push_value(return_value);
+        output(vm, ret)
     }
 }
 
 impl<Func, A, B, Return> Function<(A, B)> for Func
 where
     Func: 'static + Send + Sync + Fn(&mut Vm, A, B) -> Return,
-    Return: Into<Value>,
-    A: From<Entry>,
-    B: From<Entry>,
+    Return: Output,
+    A: Arg,
+    B: Arg,
 {
-    fn fn_call(&self, vm: &mut Vm) {
-        let b = vm.stack.pop().unwrap().into();
-        let a = vm.stack.pop().unwrap().into();
+    fn fn_call(&self, vm: &mut Vm) -> Result<()> {
+        let b = vm.pop()?;
+        let a = vm.pop()?;
+        let a = A::from_entry(vm, a)?;
+        let b = B::from_entry(vm, b)?;
         let ret = self(vm, a, b);
-
-        let return_value = Into::<Value>::into(ret);
-        vm.push_value(return_value);
+        output(vm, ret)
     }
 }
 
-// impl<Func, A, B, C, Return> Function<(A, B, C)> for Func
-// where
-//     Func: 'static + Send + Sync + Fn(&mut Vm, A, B, C) -> Return,
-//     Return: Into<Value>,
-//     A: From<Value>,
-//     B: From<Value>,
-//     C: From<Value>,
-// {
-//     fn fn_call(&self, vm: &mut Vm) {
-//         let c = vm.stack.pop_value().into();
-//         let b = vm.stack.pop_value().into();
-//         let a = vm.stack.pop_value().into();
-//         let ret = self(vm, a, b, c);
-
-//         let return_value = Into::<Value>::into(ret);
-//         vm.push_value(return_value);
-//     }
-// }
+impl<Func, A, B, C, Return> Function<(A, B, C)> for Func
+where
+    Func: 'static + Send + Sync + Fn(&mut Vm, A, B, C) -> Return,
+    Return: Output,
+    A: Arg,
+    B: Arg,
+    C: Arg,
+{
+    fn fn_call(&self, vm: &mut Vm) -> Result<()> {
+        let c = vm.pop()?;
+        let b = vm.pop()?;
+        let a = vm.pop()?;
+        let a = A::from_entry(vm, a)?;
+        let b = B::from_entry(vm, b)?;
+        let c = C::from_entry(vm, c)?;
+        let ret = self(vm, a, b, c);
+        output(vm, ret)
+    }
+}
 
 // impl<Func, A, B, C, D, Return> Function<(A, B, C, D)> for Func
 // where
@@ -0,0 +1,109 @@
+use super::BuiltinInfo;
+use crate::error::ErrorKind;
+use crate::value::{List, Value};
+use crate::vm::Vm;
+use witch_macro::builtin;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt::Display;
+
+/// The length of a string in chars.
+#[builtin]
+pub fn witch_string_len(_vm: &mut Vm, string: String) -> usize {
+    string.chars().count()
+}
+
+/// The length of a string in bytes of UTF-8.
+#[builtin]
+pub fn witch_string_byte_len(_vm: &mut Vm, string: String) -> usize {
+    string.len()
+}
+
+/// Copies the chars from `start` up to but not including `end` into a new string.
+#[builtin]
+pub fn witch_string_slice(
+    _vm: &mut Vm,
+    string: String,
+    start: usize,
+    end: usize,
+) -> Result<String, ErrorKind> {
+    let len = string.chars().count();
+    if start > end || end > len {
+        return Err(ErrorKind::IndexOutOfBounds {
+            index: if start > end { start } else { end },
+            len,
+        });
+    }
+    Ok(string.chars().skip(start).take(end - start).collect())
+}
+
+#[builtin]
+pub fn witch_string_split(_vm: &mut Vm, string: String, separator: String) -> List {
+    List(
+        string
+            .split(separator.as_str())
+            .map(|part| Value::String(part.to_string()))
+            .collect(),
+    )
+}
+
+#[builtin]
+pub fn witch_string_trim(_vm: &mut Vm, string: String) -> String {
+    string.trim().to_string()
+}
+
+#[builtin]
+pub fn witch_string_contains(_vm: &mut Vm, string: String, pattern: String) -> bool {
+    string.contains(pattern.as_str())
+}
+
+#[builtin]
+pub fn witch_string_replace(_vm: &mut Vm, string: String, from: String, to: String) -> String {
+    string.replace(from.as_str(), &to)
+}
+
+#[builtin]
+pub fn witch_string_to_upper(_vm: &mut Vm, string: String) -> String {
+    string.to_uppercase()
+}
+
+#[builtin]
+pub fn witch_string_to_lower(_vm: &mut Vm, string: String) -> String {
+    string.to_lowercase()
+}
+
+/// Formats a number, bool, char or string as text, see `Value::to_text`.
+#[builtin]
+pub fn witch_string_from(_vm: &mut Vm, value: Value) -> Result<String, ErrorKind> {
+    value.to_text()
+}
+
+#[builtin]
+pub fn witch_string_parse_usize(_vm: &mut Vm, string: String) -> Result<usize, String> {
+    string
+        .trim()
+        .parse()
+        .map_err(|err| format_parse_error(&string, err))
+}
+
+#[builtin]
+pub fn witch_string_parse_isize(_vm: &mut Vm, string: String) -> Result<isize, String> {
+    string
+        .trim()
+        .parse()
+        .map_err(|err| format_parse_error(&string, err))
+}
+
+#[builtin]
+pub fn witch_string_parse_f64(_vm: &mut Vm, string: String) -> Result<f64, String> {
+    string
+        .trim()
+        .parse()
+        .map_err(|err| format_parse_error(&string, err))
+}
+
+/// The message of the `Err` a string which is not a number parses into.
+fn format_parse_error(string: &str, err: impl Display) -> String {
+    format!("could not parse {:?}: {}", string, err)
+}
@@ -0,0 +1,331 @@
+//! Decodes bytecode into instructions and renders them as a readable listing.
+//!
+//! The operand layout of every `Op` mirrors the one `Vm::run_frame` reads. Function constants are
+//! followed by the length and the instructions of their body, which get listed nested below them.
+use alloc::format;
+use alloc::string::String;
+use core::fmt::{self, Write};
+use core::ops::Range;
+
+use crate::error::ErrorKind;
+use crate::image::{self, Image, OPERAND_SIZE};
+use crate::numeric::Numeric;
+use crate::source_map::SourceMap;
+use crate::value::Value;
+use crate::vm::{InfixOp, Op, UnaryOp};
+
+/// A single decoded instruction. Jump targets are resolved to absolute offsets within the code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    SetupModule {
+        entries: u8,
+    },
+    SetupFunctionCache {
+        count: usize,
+    },
+    GetModuleSymbol {
+        module: u8,
+        symbol: u8,
+    },
+    GetFunction(u8),
+    GetBuiltin(u8),
+    Push {
+        constant: usize,
+    },
+
+    /// Pushes a function constant, whose body directly follows the instruction
+    PushFunction {
+        constant: usize,
+        body: Range<usize>,
+    },
+    Pop,
+
+    /// Pushes a copy of the topmost entry
+    Dup,
+    Get(u8),
+
+    /// Gets a slot counting from the bottom of the stack rather than the current frame, such as
+    /// a symbol of another module
+    GetGlobal(u8),
+    GetUpvalue(u8),
+
+    /// Gets a list item, either by a constant index or by an index popped off the stack, a value
+    /// of a map by a popped key, or a value of the payload of a variant by a constant index
+    GetMember {
+        index: Option<u8>,
+    },
+    Set(u8),
+    SetProperty {
+        slot: u8,
+        property: u8,
+    },
+    SetReturn {
+        target: usize,
+    },
+    Jump {
+        target: usize,
+    },
+    JumpIfFalse {
+        target: usize,
+    },
+    JumpBack {
+        target: usize,
+    },
+    Binary(InfixOp),
+    Unary(UnaryOp),
+    Cast(Numeric),
+    Return,
+    Call,
+    Collect {
+        len: usize,
+    },
+
+    /// Collects `len` key and value pairs into a map
+    CollectMap {
+        len: usize,
+    },
+
+    /// Joins the text of `len` values into a string
+    Concat {
+        len: usize,
+    },
+
+    /// Collects `len` values into the payload of a variant
+    Variant {
+        discriminant: usize,
+        len: usize,
+    },
+
+    /// Replaces a variant with its discriminant
+    Discriminant,
+    Debug,
+}
+
+/// Decodes the instruction at `pos`, returning it along with its length in bytes. For
+/// `Instruction::PushFunction`, the length does not include the function body.
+pub fn decode(
+    code: &[u8],
+    constants: &[Value],
+    pos: usize,
+) -> Result<(Instruction, usize), ErrorKind> {
+    let byte = |offset: usize| {
+        code.get(pos + offset)
+            .copied()
+            .ok_or(ErrorKind::UnexpectedEnd)
+    };
+    let operand =
+        |offset: usize| image::read_operand(code, pos + offset).ok_or(ErrorKind::UnexpectedEnd);
+
+    let opcode = byte(0)?;
+    let decoded = match Op::from(opcode) {
+        Op::SetupModule => (Instruction::SetupModule { entries: byte(1)? }, 2),
+        Op::SetupFunctionCache => (
+            Instruction::SetupFunctionCache { count: operand(1)? },
+            1 + OPERAND_SIZE,
+        ),
+        Op::GetModuleSymbol => (
+            Instruction::GetModuleSymbol {
+                module: byte(1)?,
+                symbol: byte(2)?,
+            },
+            3,
+        ),
+        Op::GetFunction => (Instruction::GetFunction(byte(1)?), 2),
+        Op::GetBuiltin => (Instruction::GetBuiltin(byte(1)?), 2),
+        Op::Push => {
+            let constant = operand(1)?;
+            match constants.get(constant) {
+                Some(Value::Function(_)) => {
+                    let len = 1 + 2 * OPERAND_SIZE;
+                    let start = pos + len;
+                    let end = start
+                        .checked_add(operand(1 + OPERAND_SIZE)?)
+                        .filter(|end| *end <= code.len())
+                        .ok_or(ErrorKind::UnexpectedEnd)?;
+                    (
+                        Instruction::PushFunction {
+                            constant,
+                            body: start..end,
+                        },
+                        len,
+                    )
+                }
+                Some(_) => (Instruction::Push { constant }, 1 + OPERAND_SIZE),
+                None => return Err(ErrorKind::InvalidConstant),
+            }
+        }
+        Op::Pop => (Instruction::Pop, 1),
+        Op::Dup => (Instruction::Dup, 1),
+        Op::Get => (Instruction::Get(byte(1)?), 2),
+        Op::GetGlobal => (Instruction::GetGlobal(byte(1)?), 2),
+        Op::GetUpvalue => (Instruction::GetUpvalue(byte(1)?), 2),
+        Op::GetMember => match byte(1)? {
+            1 => (
+                Instruction::GetMember {
+                    index: Some(byte(2)?),
+                },
+                3,
+            ),
+            _ => (Instruction::GetMember { index: None }, 2),
+        },
+        Op::Set => (Instruction::Set(byte(1)?), 2),
+        Op::SetProperty => (
+            Instruction::SetProperty {
+                slot: byte(1)?,
+                property: byte(2)?,
+            },
+            3,
+        ),
+        Op::SetReturn => (
+            Instruction::SetReturn {
+                target: pos + operand(1)?,
+            },
+            1 + OPERAND_SIZE,
+        ),
+        Op::Jump => (
+            Instruction::Jump {
+                target: pos + 1 + operand(1)?,
+            },
+            1 + OPERAND_SIZE,
+        ),
+        Op::JumpIfFalse => (
+            Instruction::JumpIfFalse {
+                target: pos + 1 + OPERAND_SIZE + operand(1)?,
+            },
+            1 + OPERAND_SIZE,
+        ),
+        Op::JumpBack => (
+            Instruction::JumpBack {
+                target: {
+                    let offset = operand(1)?;
+                    pos.checked_sub(offset)
+                        .ok_or(ErrorKind::JumpBeforeStart(offset))?
+                },
+            },
+            1 + OPERAND_SIZE,
+        ),
+        Op::Binary => (Instruction::Binary(InfixOp::try_from(byte(1)?)?), 2),
+        Op::Unary => (Instruction::Unary(UnaryOp::try_from(byte(1)?)?), 2),
+        Op::Cast => (Instruction::Cast(Numeric::try_from(byte(1)?)?), 2),
+        Op::Return => (Instruction::Return, 1),
+        Op::Call => (Instruction::Call, 1),
+        Op::Collect => (Instruction::Collect { len: operand(1)? }, 1 + OPERAND_SIZE),
+        Op::CollectMap => (
+            Instruction::CollectMap { len: operand(1)? },
+            1 + OPERAND_SIZE,
+        ),
+        Op::Concat => (Instruction::Concat { len: operand(1)? }, 1 + OPERAND_SIZE),
+        Op::Variant => (
+            Instruction::Variant {
+                discriminant: operand(1)?,
+                len: operand(1 + OPERAND_SIZE)?,
+            },
+            1 + 2 * OPERAND_SIZE,
+        ),
+        Op::Discriminant => (Instruction::Discriminant, 1),
+        Op::Debug => (Instruction::Debug, 1),
+        Op::GetValue | Op::Crash => return Err(ErrorKind::UnknownOp(opcode)),
+    };
+    Ok(decoded)
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::SetupModule { entries } => write!(f, "SetupModule {}", entries),
+            Instruction::SetupFunctionCache { count } => write!(f, "SetupFunctionCache {}", count),
+            Instruction::GetModuleSymbol { module, symbol } => {
+                write!(f, "GetModuleSymbol {} {}", module, symbol)
+            }
+            Instruction::GetFunction(idx) => write!(f, "GetFunction {}", idx),
+            Instruction::GetBuiltin(idx) => write!(f, "GetBuiltin {}", idx),
+            Instruction::Push { constant } => write!(f, "Push #{}", constant),
+            Instruction::PushFunction { constant, body } => write!(
+                f,
+                "Push #{} (body {:04}..{:04})",
+                constant, body.start, body.end
+            ),
+            Instruction::Pop => write!(f, "Pop"),
+            Instruction::Dup => write!(f, "Dup"),
+            Instruction::Get(slot) => write!(f, "Get {}", slot),
+            Instruction::GetGlobal(slot) => write!(f, "GetGlobal {}", slot),
+            Instruction::GetUpvalue(slot) => write!(f, "GetUpvalue {}", slot),
+            Instruction::GetMember { index: Some(idx) } => write!(f, "GetMember {}", idx),
+            Instruction::GetMember { index: None } => write!(f, "GetMember <popped>"),
+            Instruction::Set(slot) => write!(f, "Set {}", slot),
+            Instruction::SetProperty { slot, property } => {
+                write!(f, "SetProperty {} {}", slot, property)
+            }
+            Instruction::SetReturn { target } => write!(f, "SetReturn -> {:04}", target),
+            Instruction::Jump { target } => write!(f, "Jump -> {:04}", target),
+            Instruction::JumpIfFalse { target } => write!(f, "JumpIfFalse -> {:04}", target),
+            Instruction::JumpBack { target } => write!(f, "JumpBack -> {:04}", target),
+            Instruction::Binary(op) => write!(f, "Binary {:?}", op),
+            Instruction::Unary(op) => write!(f, "Unary {:?}", op),
+            Instruction::Cast(to) => write!(f, "Cast {}", to),
+            Instruction::Return => write!(f, "Return"),
+            Instruction::Call => write!(f, "Call"),
+            Instruction::Collect { len } => write!(f, "Collect {}", len),
+            Instruction::CollectMap { len } => write!(f, "CollectMap {}", len),
+            Instruction::Concat { len } => write!(f, "Concat {}", len),
+            Instruction::Variant { discriminant, len } => {
+                write!(f, "Variant {} {}", discriminant, len)
+            }
+            Instruction::Discriminant => write!(f, "Discriminant"),
+            Instruction::Debug => write!(f, "Debug"),
+        }
+    }
+}
+
+/// Renders the code of an image as a listing of offsets, instructions and the constants they
+/// push. If a source map is given, function bodies are labelled with the function's name.
+/// Bytes which fail to decode end the listing with a description of the error.
+pub fn disassemble(image: &Image, source_map: Option<&SourceMap>) -> String {
+    let mut out = String::new();
+    if let Err(err) = listing(&mut out, image, source_map, 0..image.code.len(), 0) {
+        let _ = writeln!(out, "{}", err);
+    }
+    out
+}
+
+/// Lists the instructions within `range`, indented by `depth` levels.
+fn listing(
+    out: &mut String,
+    image: &Image,
+    source_map: Option<&SourceMap>,
+    range: Range<usize>,
+    depth: usize,
+) -> Result<(), String> {
+    let indent = "    ".repeat(depth);
+    let mut pos = range.start;
+    while pos < range.end {
+        let (instruction, len) = decode(&image.code, &image.constants, pos)
+            .map_err(|kind| format!("{}{:04}  <{}>", indent, pos, kind))?;
+        let _ = write!(out, "{}{:04}  {}", indent, pos, instruction);
+
+        match &instruction {
+            Instruction::Push { constant } => {
+                let _ = write!(out, "  ; {:?}", image.constants[*constant]);
+            }
+            Instruction::PushFunction { constant, body } => {
+                if let Value::Function(function) = &image.constants[*constant] {
+                    let _ = write!(out, "  ; function, arity {}", function.arity);
+                }
+                let name = source_map
+                    .and_then(|source_map| source_map.function(body.start))
+                    .filter(|function| function.bytecode.start == body.start);
+                if let Some(function) = name {
+                    let _ = write!(out, " `{}`", function.name);
+                }
+                let _ = writeln!(out);
+                listing(out, image, source_map, body.clone(), depth + 1)?;
+                pos = body.end;
+                continue;
+            }
+            _ => {}
+        }
+        let _ = writeln!(out);
+        pos += len;
+    }
+    Ok(())
+}
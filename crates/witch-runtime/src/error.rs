@@ -0,0 +1,251 @@
+//! Errors raised by the VM while executing bytecode.
+//!
+//! Every failure path within `Vm::run_frame` surfaces as an `Error`, carrying the kind
+//! of failure as well as the instruction pointer of the offending instruction.
+//! Images which are rejected by `Vm::run` before running surface as errors as well, pointing at
+//! the offending instruction if the image failed verification. Running out of fuel and being
+//! interrupted surface as errors too, although the VM can resume from them.
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::limits::Resource;
+use crate::source_map::TraceFrame;
+use crate::vm::{InfixOp, UnaryOp};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    /// Instruction pointer of the instruction that failed
+    pub ip: usize,
+    pub kind: ErrorKind,
+
+    /// The Witch call stack at the time of the error, innermost frame first.
+    /// Only populated when the `Vm` has a `SourceMap`.
+    pub backtrace: Vec<TraceFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// An instruction attempted to read or pop an entry which isn't on the stack
+    StackUnderflow,
+
+    /// An operand was not of the type the instruction expects
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+
+    /// A binary operation is not defined for the given operands
+    InvalidOperands {
+        op: InfixOp,
+        lhs: &'static str,
+        rhs: &'static str,
+    },
+
+    /// A unary operation is not defined for the given operand
+    InvalidOperand { op: UnaryOp, operand: &'static str },
+
+    /// A heap pointer which does not refer to a live object
+    InvalidPointer(usize),
+
+    /// A list was indexed outside of its bounds
+    IndexOutOfBounds { index: usize, len: usize },
+
+    /// A map was indexed by a key which it does not hold
+    MissingKey,
+
+    /// An arithmetic operation overflowed its type
+    Overflow,
+
+    /// Attempted to divide by zero
+    DivisionByZero,
+
+    /// Attempted to raise an integer to a negative power
+    NegativeExponent,
+
+    /// The byte does not correspond to an `Op`
+    UnknownOp(u8),
+
+    /// The byte does not correspond to an `InfixOp`
+    UnknownInfixOp(u8),
+
+    /// The byte does not correspond to a `UnaryOp`
+    UnknownUnaryOp(u8),
+
+    /// The byte does not correspond to a `Numeric` type
+    UnknownNumeric(u8),
+
+    /// The bytecode ended in the middle of an instruction
+    UnexpectedEnd,
+
+    /// A constant could not be decoded, or `Op::Push` referred to one which does not exist
+    InvalidConstant,
+
+    /// A function, builtin or vtable index which does not exist
+    InvalidFunction(usize),
+
+    /// A module index which does not exist
+    InvalidModule(usize),
+
+    /// An upvalue slot which does not exist
+    InvalidUpvalue(usize),
+
+    /// `Op::Return` did not find a return address below the call frame
+    InvalidReturnAddress,
+
+    /// The bytecode does not start with the magic number of an `Image`
+    NotAnImage,
+
+    /// The image was built for another version of the bytecode format
+    UnsupportedVersion(u16),
+
+    /// The image's checksum does not match its contents
+    ChecksumMismatch,
+
+    /// The sections of the image are truncated or inconsistent
+    MalformedImage,
+
+    /// A jump does not land on the start of an instruction
+    InvalidJumpTarget(usize),
+
+    /// A backward jump by this offset would land before the start of the bytecode
+    JumpBeforeStart(usize),
+
+    /// An instruction refers to a stack slot which does not exist at that point
+    InvalidSlot(usize),
+
+    /// Two paths reach the same instruction with different stack depths
+    InconsistentStack { expected: usize, found: usize },
+
+    /// A function body ends without returning
+    MissingReturn,
+
+    /// The VM executed as many instructions as its fuel allowed, see `Vm::set_fuel`
+    OutOfFuel,
+
+    /// The VM was stopped through an `InterruptHandle`
+    Interrupted,
+
+    /// The script tried to use more memory than the `Limits` of the VM allow
+    ResourceLimitExceeded(Resource),
+}
+
+impl Error {
+    pub fn new(ip: usize, kind: ErrorKind) -> Self {
+        Self {
+            ip,
+            kind,
+            backtrace: Vec::new(),
+        }
+    }
+}
+
+impl ErrorKind {
+    /// Whether the error was raised while loading an image, before any instruction ran.
+    pub fn is_load_error(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::NotAnImage
+                | ErrorKind::UnsupportedVersion(_)
+                | ErrorKind::ChecksumMismatch
+                | ErrorKind::MalformedImage
+        )
+    }
+
+    /// Whether the VM stopped in between instructions, and may continue with `Vm::resume`.
+    pub fn is_resumable(&self) -> bool {
+        matches!(self, ErrorKind::OutOfFuel | ErrorKind::Interrupted)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.kind.is_load_error() {
+            return write!(f, "{}", self.kind);
+        }
+        write!(f, "{} (at instruction {})", self.kind, self.ip)?;
+        for frame in self.backtrace.iter() {
+            write!(f, "\n    {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            ErrorKind::TypeMismatch { expected, found } => {
+                write!(f, "type mismatch: expected {}, found {}", expected, found)
+            }
+            ErrorKind::InvalidOperands { op, lhs, rhs } => write!(
+                f,
+                "operator {:?} is not supported between {} and {}",
+                op, lhs, rhs
+            ),
+            ErrorKind::InvalidOperand { op, operand } => {
+                write!(f, "operator {:?} is not supported for {}", op, operand)
+            }
+            ErrorKind::InvalidPointer(ptr) => write!(f, "invalid heap pointer {}", ptr),
+            ErrorKind::IndexOutOfBounds { index, len } => write!(
+                f,
+                "index out of bounds: the len is {} but the index is {}",
+                len, index
+            ),
+            ErrorKind::MissingKey => write!(f, "key not found in map"),
+            ErrorKind::Overflow => write!(f, "arithmetic overflow"),
+            ErrorKind::DivisionByZero => write!(f, "division by zero"),
+            ErrorKind::NegativeExponent => write!(f, "negative exponent"),
+            ErrorKind::UnknownOp(byte) => write!(f, "unknown opcode {}", byte),
+            ErrorKind::UnknownInfixOp(byte) => write!(f, "unknown infix operator {}", byte),
+            ErrorKind::UnknownUnaryOp(byte) => write!(f, "unknown unary operator {}", byte),
+            ErrorKind::UnknownNumeric(byte) => write!(f, "unknown numeric type {}", byte),
+            ErrorKind::UnexpectedEnd => write!(f, "unexpected end of bytecode"),
+            ErrorKind::InvalidConstant => write!(f, "invalid constant"),
+            ErrorKind::InvalidFunction(idx) => write!(f, "invalid function index {}", idx),
+            ErrorKind::InvalidModule(idx) => write!(f, "invalid module index {}", idx),
+            ErrorKind::InvalidUpvalue(idx) => write!(f, "invalid upvalue slot {}", idx),
+            ErrorKind::InvalidReturnAddress => write!(f, "invalid return address"),
+            ErrorKind::NotAnImage => write!(f, "not a Witch bytecode image"),
+            ErrorKind::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported bytecode format version {}, expected version {}",
+                version,
+                crate::image::VERSION
+            ),
+            ErrorKind::ChecksumMismatch => {
+                write!(f, "bytecode image is corrupted: checksum mismatch")
+            }
+            ErrorKind::MalformedImage => write!(f, "malformed bytecode image"),
+            ErrorKind::InvalidJumpTarget(target) => {
+                write!(
+                    f,
+                    "jump to {} which is not the start of an instruction",
+                    target
+                )
+            }
+            ErrorKind::JumpBeforeStart(offset) => {
+                write!(
+                    f,
+                    "jump back by {} lands before the start of the bytecode",
+                    offset
+                )
+            }
+            ErrorKind::InvalidSlot(slot) => write!(f, "stack slot {} is out of range", slot),
+            ErrorKind::InconsistentStack { expected, found } => write!(
+                f,
+                "inconsistent stack depth: expected {}, found {}",
+                expected, found
+            ),
+            ErrorKind::MissingReturn => write!(f, "function body ends without returning"),
+            ErrorKind::OutOfFuel => write!(f, "out of fuel"),
+            ErrorKind::Interrupted => write!(f, "interrupted"),
+            ErrorKind::ResourceLimitExceeded(resource) => {
+                write!(f, "resource limit exceeded: {}", resource)
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -2,9 +2,13 @@ use alloc::rc::Rc;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
+use core::mem::size_of;
+use hashbrown::HashMap;
 use slab::Slab;
 
-use crate::value::Value;
+use crate::error::ErrorKind;
+use crate::limits::Resource;
+use crate::value::{MapKey, Value, Variant};
 
 #[derive(Debug, Clone)]
 pub enum Object {
@@ -14,56 +18,413 @@ pub enum Object {
     /// A List object is of type Value::List, but contains heap pointers to all items in the list
     /// in order to allow access into items by index
     List(Vec<usize>),
+
+    /// A Map object is of type Value::Map, but contains heap pointers to all of its values
+    /// in order to allow access into them by key
+    Map(HashMap<MapKey, usize>),
+
+    /// A Variant object is of type Value::Variant, holding its discriminant and heap pointers to
+    /// its payload
+    Variant(usize, Vec<usize>),
 }
 
 #[derive(Default)]
 pub struct Heap {
     mem: Slab<Object>,
+
+    /// The approximate size of every object in `mem`, see `size`
+    bytes: usize,
+    max_objects: Option<usize>,
+    max_bytes: Option<usize>,
 }
 
 impl Heap {
+    /// Caps the number of objects and bytes on the heap. Inserting beyond them fails with
+    /// `ErrorKind::ResourceLimitExceeded`.
+    pub fn set_limits(&mut self, max_objects: Option<usize>, max_bytes: Option<usize>) {
+        self.max_objects = max_objects;
+        self.max_bytes = max_bytes;
+    }
+
+    /// The number of objects on the heap.
+    pub fn len(&self) -> usize {
+        self.mem.len()
+    }
+
+    /// The approximate number of bytes taken up by the objects on the heap.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Checks whether `bytes` more bytes fit on the heap, before allocating them.
+    pub fn reserve(&self, bytes: usize) -> Result<(), ErrorKind> {
+        match self.max_bytes {
+            Some(max) if self.bytes.saturating_add(bytes) > max => {
+                Err(ErrorKind::ResourceLimitExceeded(Resource::HeapBytes))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether inserting `value` stays within the limits of the heap.
+    pub fn fits(&self, value: &Value) -> bool {
+        if self.max_objects.is_none() && self.max_bytes.is_none() {
+            return true;
+        }
+        let (objects, bytes) = footprint(value);
+        self.fits_in(objects, bytes)
+    }
+
+    /// Whether a new list of `len` items stays within the limits of the heap.
+    pub fn fits_list(&self, len: usize) -> bool {
+        self.fits_in(1, list_size(len))
+    }
+
+    /// Whether a new variant with a payload of `len` values stays within the limits of the heap.
+    pub fn fits_variant(&self, len: usize) -> bool {
+        self.fits_in(1, list_size(len))
+    }
+
+    /// Whether a new map of `len` pairs stays within the limits of the heap.
+    pub fn fits_map(&self, len: usize) -> bool {
+        self.fits_in(1, map_size(len))
+    }
+
+    fn fits_in(&self, objects: usize, bytes: usize) -> bool {
+        self.max_objects
+            .is_none_or(|max| self.mem.len().saturating_add(objects) <= max)
+            && self.reserve(bytes).is_ok()
+    }
+
     /// Inserts a value into heap memory, returning and index key to retrieve it.
     /// Value::List gets chopped up into multiple heap entries and returns a key to a special Object::List type,
     /// allowing access to individual list items.
-    pub fn insert(&mut self, value: Value) -> usize {
+    pub fn insert(&mut self, value: Value) -> Result<usize, ErrorKind> {
         match value {
             Value::List(list) => {
                 let mut keys = vec![];
                 for v in list.into_iter() {
-                    keys.push(self.insert(v));
+                    keys.push(self.insert(v)?);
                 }
-                self.mem.insert(Object::List(keys))
+                self.alloc(Object::List(keys))
             }
-            _ => self.mem.insert(Object::Value(Rc::new(RefCell::new(value)))),
+            Value::Map(entries) => {
+                let mut map = HashMap::with_capacity(entries.len());
+                for (key, value) in entries.into_iter() {
+                    map.insert(key, self.insert(value)?);
+                }
+                self.alloc(Object::Map(map))
+            }
+            Value::Variant(variant) => {
+                let mut keys = vec![];
+                for v in variant.payload.into_iter() {
+                    keys.push(self.insert(v)?);
+                }
+                self.alloc(Object::Variant(variant.discriminant, keys))
+            }
+            _ => self.alloc(Object::Value(Rc::new(RefCell::new(value)))),
         }
     }
 
     /// Takes a list of heap pointers and "collects" them in a new heap object,
     /// that points to each one
-    pub fn create_list(&mut self, keys: Vec<usize>) -> usize {
-        self.mem.insert(Object::List(keys))
+    pub fn create_list(&mut self, keys: Vec<usize>) -> Result<usize, ErrorKind> {
+        self.alloc(Object::List(keys))
+    }
+
+    /// Takes key and heap pointer pairs and collects them in a new map object, in which later
+    /// pairs replace earlier ones with the same key
+    pub fn create_map(&mut self, pairs: Vec<(MapKey, usize)>) -> Result<usize, ErrorKind> {
+        self.alloc(Object::Map(pairs.into_iter().collect()))
+    }
+
+    /// Takes the discriminant of a variant and heap pointers to its payload, and collects them in a
+    /// new variant object
+    pub fn create_variant(
+        &mut self,
+        discriminant: usize,
+        payload: Vec<usize>,
+    ) -> Result<usize, ErrorKind> {
+        self.alloc(Object::Variant(discriminant, payload))
+    }
+
+    /// The discriminant of the variant at heap pointer `key`.
+    pub fn discriminant(&self, key: usize) -> Result<usize, ErrorKind> {
+        match self.mem.get(key).ok_or(ErrorKind::InvalidPointer(key))? {
+            Object::Variant(discriminant, _) => Ok(*discriminant),
+            object => Err(ErrorKind::TypeMismatch {
+                expected: "enum",
+                found: object.type_name(),
+            }),
+        }
     }
 
-    pub fn get(&mut self, key: usize) -> Rc<RefCell<Value>> {
-        let obj = self.mem.get_mut(key).unwrap().clone();
+    /// Whether the object at heap pointer `key` is a map.
+    pub fn is_map(&self, key: usize) -> bool {
+        matches!(self.mem.get(key), Some(Object::Map(_)))
+    }
+
+    /// Whether the object at heap pointer `key` is a variant.
+    pub fn is_variant(&self, key: usize) -> bool {
+        matches!(self.mem.get(key), Some(Object::Variant(..)))
+    }
+
+    /// Frees every object which is not reachable from `roots`, returning how many were freed.
+    /// Roots which do not refer to a live object are ignored.
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = usize>) -> usize {
+        let mut marked = vec![false; self.mem.capacity()];
+        let mut worklist: Vec<usize> = roots.into_iter().collect();
+        while let Some(key) = worklist.pop() {
+            if marked.get(key) != Some(&false) {
+                continue;
+            }
+            let Some(object) = self.mem.get(key) else {
+                continue;
+            };
+            marked[key] = true;
+            match object {
+                Object::List(keys) => worklist.extend(keys.iter().copied()),
+                Object::Map(map) => worklist.extend(map.values().copied()),
+                Object::Variant(_, payload) => worklist.extend(payload.iter().copied()),
+                Object::Value(_) => {}
+            }
+        }
+
+        let before = self.mem.len();
+        let mut bytes = 0;
+        self.mem.retain(|key, object| {
+            if marked[key] {
+                bytes += size(object);
+            }
+            marked[key]
+        });
+        self.bytes = bytes;
+        before - self.mem.len()
+    }
+
+    fn alloc(&mut self, object: Object) -> Result<usize, ErrorKind> {
+        if self.max_objects.is_some_and(|max| self.mem.len() >= max) {
+            return Err(ErrorKind::ResourceLimitExceeded(Resource::HeapObjects));
+        }
+        let size = size(&object);
+        self.reserve(size)?;
+        self.bytes += size;
+        Ok(self.mem.insert(object))
+    }
+
+    pub fn get(&mut self, key: usize) -> Result<Rc<RefCell<Value>>, ErrorKind> {
+        let obj = self
+            .mem
+            .get_mut(key)
+            .ok_or(ErrorKind::InvalidPointer(key))?
+            .clone();
         match obj {
-            Object::Value(v) => v.clone(),
+            Object::Value(v) => Ok(v.clone()),
             Object::List(ref vec) => {
                 let mut l = vec![];
                 for v in vec.iter() {
-                    let val = self.get(*v).borrow().clone();
+                    let val = self.get(*v)?.borrow().clone();
                     l.push(val);
                 }
-                Rc::new(RefCell::new(Value::List(l)))
+                Ok(Rc::new(RefCell::new(Value::List(l))))
+            }
+            Object::Map(ref map) => {
+                let mut m = HashMap::with_capacity(map.len());
+                for (key, v) in map.iter() {
+                    let val = self.get(*v)?.borrow().clone();
+                    m.insert(key.clone(), val);
+                }
+                Ok(Rc::new(RefCell::new(Value::Map(m))))
+            }
+            Object::Variant(discriminant, ref keys) => {
+                let mut payload = vec![];
+                for v in keys.iter() {
+                    let val = self.get(*v)?.borrow().clone();
+                    payload.push(val);
+                }
+                let variant = Variant::new(discriminant, payload);
+                Ok(Rc::new(RefCell::new(Value::Variant(variant))))
             }
         }
     }
 
-    pub fn get_list_item_ptr(&mut self, key: usize, idx: usize) -> usize {
-        let obj = self.mem.get_mut(key).unwrap();
+    pub fn get_list_item_ptr(&mut self, key: usize, idx: usize) -> Result<usize, ErrorKind> {
+        let obj = self
+            .mem
+            .get_mut(key)
+            .ok_or(ErrorKind::InvalidPointer(key))?;
         match obj {
-            Object::List(list) => list[idx],
-            _ => unreachable!(),
+            Object::List(list) => list.get(idx).copied().ok_or(ErrorKind::IndexOutOfBounds {
+                index: idx,
+                len: list.len(),
+            }),
+            object => Err(ErrorKind::TypeMismatch {
+                expected: "list",
+                found: object.type_name(),
+            }),
+        }
+    }
+
+    /// Gets the pointer to the value at `idx` within the payload of the variant at heap pointer `key`.
+    pub fn get_payload_ptr(&self, key: usize, idx: usize) -> Result<usize, ErrorKind> {
+        match self.mem.get(key).ok_or(ErrorKind::InvalidPointer(key))? {
+            Object::Variant(_, payload) => {
+                payload
+                    .get(idx)
+                    .copied()
+                    .ok_or(ErrorKind::IndexOutOfBounds {
+                        index: idx,
+                        len: payload.len(),
+                    })
+            }
+            object => Err(ErrorKind::TypeMismatch {
+                expected: "enum",
+                found: object.type_name(),
+            }),
+        }
+    }
+
+    /// Inserts the heap object `item` into a list at `idx`, shifting the items after it.
+    pub fn list_insert(&mut self, list: usize, idx: usize, item: usize) -> Result<(), ErrorKind> {
+        let len = self.list_mut(list)?.len();
+        if idx > len {
+            return Err(ErrorKind::IndexOutOfBounds { index: idx, len });
+        }
+        self.reserve(size_of::<usize>())?;
+        self.bytes += size_of::<usize>();
+        self.list_mut(list)?.insert(idx, item);
+        Ok(())
+    }
+
+    /// Removes the item at `idx` from a list, returning the pointer to it.
+    pub fn list_remove(&mut self, list: usize, idx: usize) -> Result<usize, ErrorKind> {
+        let items = self.list_mut(list)?;
+        if idx >= items.len() {
+            return Err(ErrorKind::IndexOutOfBounds {
+                index: idx,
+                len: items.len(),
+            });
+        }
+        let item = items.remove(idx);
+        self.bytes -= size_of::<usize>();
+        Ok(item)
+    }
+
+    /// The list at heap pointer `list`, with pointers to its items.
+    pub fn list_mut(&mut self, list: usize) -> Result<&mut Vec<usize>, ErrorKind> {
+        match self
+            .mem
+            .get_mut(list)
+            .ok_or(ErrorKind::InvalidPointer(list))?
+        {
+            Object::List(items) => Ok(items),
+            object => Err(ErrorKind::TypeMismatch {
+                expected: "list",
+                found: object.type_name(),
+            }),
+        }
+    }
+
+    /// Gets the pointer to the value of `key` within the map at heap pointer `map`.
+    pub fn get_map_item_ptr(&mut self, map: usize, key: &MapKey) -> Result<usize, ErrorKind> {
+        self.map_mut(map)?
+            .get(key)
+            .copied()
+            .ok_or(ErrorKind::MissingKey)
+    }
+
+    /// Points `key` of a map at the heap object `value`, returning the pointer it held before.
+    pub fn map_insert(
+        &mut self,
+        map: usize,
+        key: MapKey,
+        value: usize,
+    ) -> Result<Option<usize>, ErrorKind> {
+        if !self.map_mut(map)?.contains_key(&key) {
+            let added = size_of::<MapKey>() + size_of::<usize>();
+            self.reserve(added)?;
+            self.bytes += added;
+        }
+        Ok(self.map_mut(map)?.insert(key, value))
+    }
+
+    /// Removes `key` from a map, returning the pointer it held.
+    pub fn map_remove(&mut self, map: usize, key: &MapKey) -> Result<Option<usize>, ErrorKind> {
+        let removed = self.map_mut(map)?.remove(key);
+        if removed.is_some() {
+            self.bytes -= size_of::<MapKey>() + size_of::<usize>();
+        }
+        Ok(removed)
+    }
+
+    /// The map at heap pointer `map`, with its keys and pointers to its values.
+    pub fn map_mut(&mut self, map: usize) -> Result<&mut HashMap<MapKey, usize>, ErrorKind> {
+        match self
+            .mem
+            .get_mut(map)
+            .ok_or(ErrorKind::InvalidPointer(map))?
+        {
+            Object::Map(map) => Ok(map),
+            object => Err(ErrorKind::TypeMismatch {
+                expected: "map",
+                found: object.type_name(),
+            }),
+        }
+    }
+}
+
+impl Object {
+    /// The name of the type of value the object holds, as it is written in Witch.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Object::Value(v) => v.borrow().type_name(),
+            Object::List(_) => "list",
+            Object::Map(_) => "map",
+            Object::Variant(..) => "enum",
+        }
+    }
+}
+
+/// Estimates the memory taken up by an object, including what it owns on the Rust heap.
+fn size(object: &Object) -> usize {
+    match object {
+        Object::Value(value) => value_size(&value.borrow()),
+        Object::List(keys) => list_size(keys.len()),
+        Object::Map(map) => map_size(map.len()),
+        Object::Variant(_, payload) => list_size(payload.len()),
+    }
+}
+
+fn value_size(value: &Value) -> usize {
+    let owned = match value {
+        Value::String(s) => s.len(),
+        Value::CString(s) => s.as_bytes().len(),
+        _ => 0,
+    };
+    size_of::<Object>() + size_of::<RefCell<Value>>() + owned
+}
+
+fn list_size(len: usize) -> usize {
+    size_of::<Object>() + len * size_of::<usize>()
+}
+
+fn map_size(len: usize) -> usize {
+    size_of::<Object>() + len * (size_of::<MapKey>() + size_of::<usize>())
+}
+
+/// The number of objects and bytes `Heap::insert` takes up for a value.
+fn footprint(value: &Value) -> (usize, usize) {
+    let add = |(objects, bytes): (usize, usize), item| {
+        let (o, b) = footprint(item);
+        (objects + o, bytes.saturating_add(b))
+    };
+    match value {
+        Value::List(items) => items.iter().fold((1, list_size(items.len())), add),
+        Value::Map(entries) => entries.values().fold((1, map_size(entries.len())), add),
+        Value::Variant(variant) => {
+            let payload = &variant.payload;
+            payload.iter().fold((1, list_size(payload.len())), add)
         }
+        value => (1, value_size(value)),
     }
 }
@@ -0,0 +1,205 @@
+//! The container format which compiled Witch programs are stored and shipped in.
+//!
+//! An image is self-describing and independent of the machine it was built on. Every integer is
+//! encoded in little-endian byte order with a fixed width, regardless of pointer width:
+//!
+//! ```text
+//! magic       4 bytes   b"WTCH"
+//! version     u16       must equal `VERSION`
+//! constants   u32 count, then for each constant: u32 length, encoded `Value`
+//! functions   u32 count, then for each function: u32 address, u32 length, u32 arity
+//! code        u32 length, then the instructions
+//! checksum    u32       CRC-32 of every preceding byte
+//! ```
+//!
+//! Instruction operands which are not single bytes are `OPERAND_SIZE` wide, see `read_operand`.
+use alloc::vec::Vec;
+use bincode::config::{Configuration, Fixint, LittleEndian, NoLimit};
+
+use crate::error::ErrorKind;
+use crate::value::Value;
+
+pub const MAGIC: [u8; 4] = *b"WTCH";
+
+/// Bumped whenever the layout of the image or the encoding of instructions changes.
+pub const VERSION: u16 = 8;
+
+/// The width in bytes of lengths, counts, offsets and constant indices within instructions.
+pub const OPERAND_SIZE: usize = 4;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Image {
+    /// Values referenced by index from `Op::Push`
+    pub constants: Vec<Value>,
+
+    /// Every function body within `code`
+    pub functions: Vec<FunctionEntry>,
+    pub code: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionEntry {
+    /// Offset of the first instruction of the body within the code
+    pub addr: usize,
+
+    /// Length of the body in bytes
+    pub len: usize,
+    pub arity: usize,
+}
+
+impl Image {
+    /// Serializes the image. Fails with `ErrorKind::MalformedImage` if any count, length or
+    /// constant does not fit the format.
+    pub fn encode(&self) -> Result<Vec<u8>, ErrorKind> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+
+        write_u32(&mut bytes, self.constants.len())?;
+        for constant in self.constants.iter() {
+            let encoded = bincode::serde::encode_to_vec(constant, config())
+                .map_err(|_| ErrorKind::MalformedImage)?;
+            write_u32(&mut bytes, encoded.len())?;
+            bytes.extend_from_slice(&encoded);
+        }
+
+        write_u32(&mut bytes, self.functions.len())?;
+        for function in self.functions.iter() {
+            write_u32(&mut bytes, function.addr)?;
+            write_u32(&mut bytes, function.len)?;
+            write_u32(&mut bytes, function.arity)?;
+        }
+
+        write_u32(&mut bytes, self.code.len())?;
+        bytes.extend_from_slice(&self.code);
+
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        Ok(bytes)
+    }
+
+    /// Deserializes and validates an image, rejecting images built for another format version,
+    /// corrupted images and images whose sections are inconsistent.
+    pub fn decode(bytes: &[u8]) -> Result<Self, ErrorKind> {
+        if bytes.get(..MAGIC.len()) != Some(&MAGIC[..]) {
+            return Err(ErrorKind::NotAnImage);
+        }
+        let mut reader = Reader {
+            bytes,
+            pos: MAGIC.len(),
+        };
+        let version = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+        if version != VERSION {
+            return Err(ErrorKind::UnsupportedVersion(version));
+        }
+
+        let (body, checksum) = bytes
+            .split_last_chunk::<4>()
+            .filter(|(body, _)| body.len() >= reader.pos)
+            .ok_or(ErrorKind::MalformedImage)?;
+        if crc32(body) != u32::from_le_bytes(*checksum) {
+            return Err(ErrorKind::ChecksumMismatch);
+        }
+        reader.bytes = body;
+
+        let mut constants = Vec::new();
+        for _ in 0..reader.u32()? {
+            let len = reader.u32()?;
+            let (constant, read) = bincode::serde::decode_from_slice(reader.take(len)?, config())
+                .map_err(|_| ErrorKind::InvalidConstant)?;
+            if read != len {
+                return Err(ErrorKind::InvalidConstant);
+            }
+            constants.push(constant);
+        }
+
+        let mut functions = Vec::new();
+        for _ in 0..reader.u32()? {
+            functions.push(FunctionEntry {
+                addr: reader.u32()?,
+                len: reader.u32()?,
+                arity: reader.u32()?,
+            });
+        }
+
+        let code_len = reader.u32()?;
+        let code = reader.take(code_len)?.to_vec();
+        if reader.pos != reader.bytes.len()
+            || functions
+                .iter()
+                .any(|f| f.addr.checked_add(f.len).is_none_or(|end| end > code.len()))
+        {
+            return Err(ErrorKind::MalformedImage);
+        }
+
+        Ok(Self {
+            constants,
+            functions,
+            code,
+        })
+    }
+}
+
+/// Decodes the operand starting at `pos` within `code`.
+pub fn read_operand(code: &[u8], pos: usize) -> Option<usize> {
+    let bytes = code.get(pos..pos + OPERAND_SIZE)?;
+    let operand = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    usize::try_from(operand).ok()
+}
+
+/// Encodes an operand, or returns `None` if it does not fit into `OPERAND_SIZE` bytes.
+pub fn operand(value: usize) -> Option<[u8; OPERAND_SIZE]> {
+    u32::try_from(value).ok().map(u32::to_le_bytes)
+}
+
+/// The encoding of constants is pinned, so that it does not depend on the defaults of `bincode`.
+fn config() -> Configuration<LittleEndian, Fixint, NoLimit> {
+    bincode::config::standard()
+        .with_little_endian()
+        .with_fixed_int_encoding()
+        .with_no_limit()
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: usize) -> Result<(), ErrorKind> {
+    bytes.extend_from_slice(&operand(value).ok_or(ErrorKind::MalformedImage)?);
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ErrorKind> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or(ErrorKind::MalformedImage)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ErrorKind> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, ErrorKind> {
+        let value = read_operand(self.bytes, self.pos).ok_or(ErrorKind::MalformedImage)?;
+        self.pos += OPERAND_SIZE;
+        Ok(value)
+    }
+}
+
+/// CRC-32 (IEEE 802.3), computed bitwise to avoid a lookup table.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -17,7 +17,14 @@ macro_rules! dbg {
 }
 
 pub mod builtins;
+pub mod disasm;
+pub mod error;
 mod heap;
+pub mod image;
+pub mod limits;
+pub mod numeric;
+pub mod source_map;
 mod stack;
 pub mod value;
+pub mod verify;
 pub mod vm;
@@ -0,0 +1,49 @@
+//! Caps on the memory a `Vm` may use, so that scripts can be run without trusting them.
+//!
+//! Every limit is optional, and unset by default. When a script exceeds one, the VM stops with
+//! `ErrorKind::ResourceLimitExceeded` rather than exhausting the memory of the host.
+use core::fmt;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// The number of objects which may live on the heap at once
+    pub heap_objects: Option<usize>,
+
+    /// The approximate number of bytes the heap may take up
+    pub heap_bytes: Option<usize>,
+
+    /// The number of entries the stack may hold
+    pub stack_depth: Option<usize>,
+
+    /// The number of calls which may be in progress at once
+    pub call_depth: Option<usize>,
+}
+
+/// The memory a `Vm` currently uses, measured the same way as `Limits`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Usage {
+    pub heap_objects: usize,
+    pub heap_bytes: usize,
+    pub stack_depth: usize,
+    pub call_depth: usize,
+}
+
+/// The resource a script ran out of, see `Limits`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resource {
+    HeapObjects,
+    HeapBytes,
+    StackDepth,
+    CallDepth,
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resource::HeapObjects => write!(f, "heap objects"),
+            Resource::HeapBytes => write!(f, "heap bytes"),
+            Resource::StackDepth => write!(f, "stack depth"),
+            Resource::CallDepth => write!(f, "call depth"),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Arithmetic, comparison and conversion of numeric values.
+//!
+//! Both operands of a binary operation must be of the same type. Integer arithmetic is checked,
+//! failing with `ErrorKind::Overflow` or `ErrorKind::DivisionByZero`, while floats follow IEEE 754.
+//! Integer division truncates towards zero, so the remainder of `%` takes the sign of the
+//! dividend, and integers can not be raised to a negative power.
+//! Casts between numeric types fail rather than truncate when the value does not fit its new type.
+use core::fmt;
+
+use crate::error::ErrorKind;
+use crate::value::Value;
+use crate::vm::{InfixOp, UnaryOp};
+
+/// A numeric type, as encoded in the operand of `Op::Cast`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Numeric {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    I128,
+    U128,
+    Isize,
+    Usize,
+    F32,
+    F64,
+}
+
+impl TryFrom<u8> for Numeric {
+    type Error = ErrorKind;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        let numeric = match byte {
+            0 => Numeric::I8,
+            1 => Numeric::U8,
+            2 => Numeric::I16,
+            3 => Numeric::U16,
+            4 => Numeric::I32,
+            5 => Numeric::U32,
+            6 => Numeric::I64,
+            7 => Numeric::U64,
+            8 => Numeric::I128,
+            9 => Numeric::U128,
+            10 => Numeric::Isize,
+            11 => Numeric::Usize,
+            12 => Numeric::F32,
+            13 => Numeric::F64,
+            x => return Err(ErrorKind::UnknownNumeric(x)),
+        };
+        Ok(numeric)
+    }
+}
+
+impl fmt::Display for Numeric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Numeric::I8 => "i8",
+            Numeric::U8 => "u8",
+            Numeric::I16 => "i16",
+            Numeric::U16 => "u16",
+            Numeric::I32 => "i32",
+            Numeric::U32 => "u32",
+            Numeric::I64 => "i64",
+            Numeric::U64 => "u64",
+            Numeric::I128 => "i128",
+            Numeric::U128 => "u128",
+            Numeric::Isize => "isize",
+            Numeric::Usize => "usize",
+            Numeric::F32 => "f32",
+            Numeric::F64 => "f64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Applies a binary operator to two numbers of the same type.
+pub fn binary(lhs: &Value, op: InfixOp, rhs: &Value) -> Result<Value, ErrorKind> {
+    let invalid = |op| ErrorKind::InvalidOperands {
+        op,
+        lhs: lhs.type_name(),
+        rhs: rhs.type_name(),
+    };
+
+    macro_rules! integer {
+        ($variant:ident, $a:expr, $b:expr) => {{
+            let (a, b) = ($a, $b);
+            let result = match op {
+                InfixOp::Add => a.checked_add(b),
+                InfixOp::Sub => a.checked_sub(b),
+                InfixOp::Mul => a.checked_mul(b),
+                InfixOp::Div | InfixOp::Mod if b == 0 => return Err(ErrorKind::DivisionByZero),
+                // Dividing the smallest signed integer by -1 overflows
+                InfixOp::Div => a.checked_div(b),
+                InfixOp::Mod => a.checked_rem(b),
+                InfixOp::Pow => match u32::try_from(b) {
+                    Ok(exp) => a.checked_pow(exp),
+                    // Only 0, 1 and -1 can be raised this far, for which the parity is enough
+                    Err(_) if b > 0 => a.checked_pow(u32::MAX - u32::from(b % 2 == 0)),
+                    Err(_) => return Err(ErrorKind::NegativeExponent),
+                },
+                op => return compare(a, &op, b).ok_or_else(|| invalid(op)),
+            };
+            result.map(Value::$variant).ok_or(ErrorKind::Overflow)
+        }};
+    }
+
+    macro_rules! float {
+        ($variant:ident, $pow:ident, $a:expr, $b:expr) => {{
+            let (a, b) = ($a, $b);
+            let result = match op {
+                InfixOp::Add => a + b,
+                InfixOp::Sub => a - b,
+                InfixOp::Mul => a * b,
+                InfixOp::Div => a / b,
+                InfixOp::Mod => a % b,
+                InfixOp::Pow => libm::$pow(a, b),
+                op => return compare(a, &op, b).ok_or_else(|| invalid(op)),
+            };
+            Ok(Value::$variant(result))
+        }};
+    }
+
+    match (lhs, rhs) {
+        (Value::I8(a), Value::I8(b)) => integer!(I8, *a, *b),
+        (Value::U8(a), Value::U8(b)) => integer!(U8, *a, *b),
+        (Value::I16(a), Value::I16(b)) => integer!(I16, *a, *b),
+        (Value::U16(a), Value::U16(b)) => integer!(U16, *a, *b),
+        (Value::I32(a), Value::I32(b)) => integer!(I32, *a, *b),
+        (Value::U32(a), Value::U32(b)) => integer!(U32, *a, *b),
+        (Value::I64(a), Value::I64(b)) => integer!(I64, *a, *b),
+        (Value::U64(a), Value::U64(b)) => integer!(U64, *a, *b),
+        (Value::I128(a), Value::I128(b)) => integer!(I128, *a, *b),
+        (Value::U128(a), Value::U128(b)) => integer!(U128, *a, *b),
+        (Value::Isize(a), Value::Isize(b)) => integer!(Isize, *a, *b),
+        (Value::Usize(a), Value::Usize(b)) => integer!(Usize, *a, *b),
+        (Value::F32(a), Value::F32(b)) => float!(F32, powf, *a, *b),
+        (Value::F64(a), Value::F64(b)) => float!(F64, pow, *a, *b),
+        _ => Err(invalid(op)),
+    }
+}
+
+/// Applies a comparison operator to two values of any ordered type, or returns `None` if `op`
+/// does not compare.
+pub fn compare<T: PartialOrd>(a: T, op: &InfixOp, b: T) -> Option<Value> {
+    let result = match op {
+        InfixOp::Eq => a == b,
+        InfixOp::NotEq => a != b,
+        InfixOp::Lt => a < b,
+        InfixOp::Lte => a <= b,
+        InfixOp::Gt => a > b,
+        InfixOp::Gte => a >= b,
+        _ => return None,
+    };
+    Some(Value::Bool(result))
+}
+
+/// Applies a unary operator. Only signed integers and floats can be negated, and negating the
+/// smallest signed integer overflows.
+pub fn unary(op: UnaryOp, value: &Value) -> Result<Value, ErrorKind> {
+    let result = match (&op, value) {
+        (UnaryOp::Not, Value::Bool(x)) => Some(Value::Bool(!x)),
+        (UnaryOp::Neg, Value::I8(x)) => x.checked_neg().map(Value::I8),
+        (UnaryOp::Neg, Value::I16(x)) => x.checked_neg().map(Value::I16),
+        (UnaryOp::Neg, Value::I32(x)) => x.checked_neg().map(Value::I32),
+        (UnaryOp::Neg, Value::I64(x)) => x.checked_neg().map(Value::I64),
+        (UnaryOp::Neg, Value::I128(x)) => x.checked_neg().map(Value::I128),
+        (UnaryOp::Neg, Value::Isize(x)) => x.checked_neg().map(Value::Isize),
+        (UnaryOp::Neg, Value::F32(x)) => Some(Value::F32(-x)),
+        (UnaryOp::Neg, Value::F64(x)) => Some(Value::F64(-x)),
+        _ => {
+            return Err(ErrorKind::InvalidOperand {
+                op,
+                operand: value.type_name(),
+            })
+        }
+    };
+    result.ok_or(ErrorKind::Overflow)
+}
+
+/// A number widened to the largest type of its kind.
+enum Number {
+    Signed(i128),
+    Unsigned(u128),
+    Float(f64),
+}
+
+/// -2^127, the smallest `i128`
+const I128_MIN: f64 = -170141183460469231731687303715884105728.0;
+
+/// 2^128, the first float above the range of `u128`
+const U128_END: f64 = 340282366920938463463374607431768211456.0;
+
+/// Converts a number to another numeric type. Integers must fit their new type, and floats are
+/// truncated towards zero when converted to integers. Converting to a float rounds to the nearest
+/// representable value.
+pub fn cast(value: &Value, to: Numeric) -> Result<Value, ErrorKind> {
+    let number = match *value {
+        Value::I8(x) => Number::Signed(x.into()),
+        Value::I16(x) => Number::Signed(x.into()),
+        Value::I32(x) => Number::Signed(x.into()),
+        Value::I64(x) => Number::Signed(x.into()),
+        Value::I128(x) => Number::Signed(x),
+        Value::Isize(x) => Number::Signed(x as i128),
+        Value::U8(x) => Number::Unsigned(x.into()),
+        Value::U16(x) => Number::Unsigned(x.into()),
+        Value::U32(x) => Number::Unsigned(x.into()),
+        Value::U64(x) => Number::Unsigned(x.into()),
+        Value::U128(x) => Number::Unsigned(x),
+        Value::Usize(x) => Number::Unsigned(x as u128),
+        Value::F32(x) => Number::Float(x.into()),
+        Value::F64(x) => Number::Float(x),
+        ref value => {
+            return Err(ErrorKind::TypeMismatch {
+                expected: "number",
+                found: value.type_name(),
+            })
+        }
+    };
+
+    let number = match (number, to) {
+        (Number::Signed(x), Numeric::F32) => return Ok(Value::F32(x as f32)),
+        (Number::Signed(x), Numeric::F64) => return Ok(Value::F64(x as f64)),
+        (Number::Unsigned(x), Numeric::F32) => return Ok(Value::F32(x as f32)),
+        (Number::Unsigned(x), Numeric::F64) => return Ok(Value::F64(x as f64)),
+        (Number::Float(x), Numeric::F32) => return Ok(Value::F32(x as f32)),
+        (Number::Float(x), Numeric::F64) => return Ok(Value::F64(x)),
+
+        // Within these bounds, `as` truncates towards zero without saturating
+        (Number::Float(x), _) if (I128_MIN..0.0).contains(&x) => Number::Signed(x as i128),
+        (Number::Float(x), _) if (0.0..U128_END).contains(&x) => Number::Unsigned(x as u128),
+        (Number::Float(_), _) => return Err(ErrorKind::Overflow),
+        (number, _) => number,
+    };
+
+    macro_rules! integer {
+        ($ty:ty, $variant:ident) => {
+            match number {
+                Number::Signed(x) => <$ty>::try_from(x).ok(),
+                Number::Unsigned(x) => <$ty>::try_from(x).ok(),
+                Number::Float(_) => unreachable!(),
+            }
+            .map(Value::$variant)
+            .ok_or(ErrorKind::Overflow)
+        };
+    }
+
+    match to {
+        Numeric::I8 => integer!(i8, I8),
+        Numeric::U8 => integer!(u8, U8),
+        Numeric::I16 => integer!(i16, I16),
+        Numeric::U16 => integer!(u16, U16),
+        Numeric::I32 => integer!(i32, I32),
+        Numeric::U32 => integer!(u32, U32),
+        Numeric::I64 => integer!(i64, I64),
+        Numeric::U64 => integer!(u64, U64),
+        Numeric::I128 => integer!(i128, I128),
+        Numeric::U128 => integer!(u128, U128),
+        Numeric::Isize => integer!(isize, Isize),
+        Numeric::Usize => integer!(usize, Usize),
+        Numeric::F32 | Numeric::F64 => unreachable!(),
+    }
+}
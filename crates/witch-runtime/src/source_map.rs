@@ -0,0 +1,99 @@
+//! Debug information which maps bytecode offsets back to the Witch source they were compiled from.
+//!
+//! The compiler emits a `SourceMap` as a side table next to the bytecode. It is optional at runtime,
+//! but when the `Vm` has one it is able to produce a Witch-level backtrace for errors.
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct SourceMap {
+    /// Paths of the modules that the bytecode was compiled from
+    pub files: Vec<String>,
+
+    /// Source locations, each covering a range of bytecode
+    pub locations: Vec<Location>,
+
+    /// Named functions, each covering the bytecode of the function body
+    pub functions: Vec<FunctionInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Location {
+    /// The range of bytecode compiled from this location
+    pub bytecode: Range<usize>,
+
+    /// Index into `SourceMap::files`
+    pub file: usize,
+
+    /// Byte range within the source file
+    pub span: Range<usize>,
+
+    /// 1-based line of the start of the span
+    pub line: usize,
+
+    /// 1-based column of the start of the span
+    pub column: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FunctionInfo {
+    /// The range of bytecode making up the function body
+    pub bytecode: Range<usize>,
+    pub name: String,
+}
+
+impl SourceMap {
+    /// Finds the innermost source location covering the instruction at `ip`.
+    pub fn location(&self, ip: usize) -> Option<&Location> {
+        self.locations
+            .iter()
+            .filter(|l| l.bytecode.contains(&ip))
+            .min_by_key(|l| l.bytecode.len())
+    }
+
+    /// Finds the innermost function whose body contains the instruction at `ip`.
+    pub fn function(&self, ip: usize) -> Option<&FunctionInfo> {
+        self.functions
+            .iter()
+            .filter(|f| f.bytecode.contains(&ip))
+            .min_by_key(|f| f.bytecode.len())
+    }
+
+    /// Resolves the instruction at `ip` into a frame of a backtrace.
+    pub fn trace(&self, ip: usize) -> TraceFrame {
+        let location = self.location(ip);
+        TraceFrame {
+            ip,
+            function: self.function(ip).map(|f| f.name.clone()),
+            file: location.and_then(|l| self.files.get(l.file).cloned()),
+            line: location.map(|l| l.line).unwrap_or_default(),
+            column: location.map(|l| l.column).unwrap_or_default(),
+        }
+    }
+}
+
+/// A single call frame of a Witch-level backtrace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub ip: usize,
+
+    /// Name of the function the frame is executing, or `None` for top-level module code
+    pub function: Option<String>,
+
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}", self.function.as_deref().unwrap_or("<module>"))?;
+        match &self.file {
+            Some(file) => write!(f, " ({}:{}:{})", file, self.line, self.column),
+            None => write!(f, " (instruction {})", self.ip),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use alloc::vec;
 use alloc::vec::Vec;
 
+use crate::error::ErrorKind;
 use crate::value::Value;
 
 /// Pointer is a usize referring to a Value on the heap
@@ -38,14 +39,33 @@ impl Entry {
     }
 }
 
-impl From<Entry> for Value {
-    fn from(entry: Entry) -> Self {
-        match entry {
+/// Converts entries which hold their value themselves. Pointers have to be resolved by the `Vm`.
+impl core::convert::TryFrom<Entry> for Value {
+    type Error = ErrorKind;
+
+    fn try_from(entry: Entry) -> Result<Self, ErrorKind> {
+        let value = match entry {
             Entry::Bool(b) => Value::Bool(b),
+            Entry::Isize(i) => Value::Isize(i),
             Entry::Usize(i) => Value::Usize(i),
             Entry::Void => Value::Void,
-            x => todo!("{:?}", x),
-        }
+            Entry::Function(Function {
+                addr,
+                arity,
+                upvalues_refs_idx,
+            }) => Value::StackFunction {
+                addr,
+                arity,
+                upvalues_refs_idx,
+            },
+            Entry::Pointer(_) => {
+                return Err(ErrorKind::TypeMismatch {
+                    expected: "value",
+                    found: "pointer",
+                })
+            }
+        };
+        Ok(value)
     }
 }
 
@@ -79,27 +99,35 @@ impl Stack {
         self.data.last_mut()
     }
 
-    pub fn get(&mut self, idx: usize) -> Entry {
-        self.data[idx]
+    /// Retrieves a copy of the entry at `idx`, if it exists.
+    pub fn get(&mut self, idx: usize) -> Option<Entry> {
+        self.data.get(idx).copied()
     }
 
-    pub fn set(&mut self, idx: usize, entry: Entry) {
-        self.data[idx] = entry;
+    /// Overwrites the entry at `idx`. Returns `None` if there is no such entry.
+    pub fn set(&mut self, idx: usize, entry: Entry) -> Option<()> {
+        *self.data.get_mut(idx)? = entry;
+        Some(())
     }
 
-    pub fn take(&mut self, n: usize) -> Vec<Entry> {
-        let mut taken = vec![];
-        for _ in 0..n {
-            taken.push(self.data.pop().unwrap());
+    /// Takes the `n` topmost entries off the stack, in stack order.
+    /// Returns `None` without modifying the stack if there are fewer than `n` entries.
+    pub fn take(&mut self, n: usize) -> Option<Vec<Entry>> {
+        if n > self.data.len() {
+            return None;
         }
-        taken.reverse();
-        taken
+        Some(self.data.split_off(self.data.len() - n))
     }
 
     pub fn len(&self) -> usize {
         self.data.len()
     }
 
+    /// Iterates over the entries from the bottom of the stack up.
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.data.iter()
+    }
+
     pub fn truncate(&mut self, len: usize) {
         self.data.truncate(len);
     }
@@ -1,8 +1,17 @@
 use core::ffi::c_int;
+use core::hash::{BuildHasher, Hash};
 
-use alloc::{ffi::CString, string::String, vec, vec::Vec};
+use alloc::{
+    ffi::CString,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 
+use crate::error::ErrorKind;
+
 #[derive(Serialize, Debug, Deserialize, PartialEq, Clone)]
 #[repr(C, u8)]
 pub enum Value {
@@ -12,6 +21,8 @@ pub enum Value {
     String(String),
     CString(CString),
     List(Vec<Self>),
+    Map(HashMap<MapKey, Self>),
+    Variant(Variant),
     Function(Function),
     StackFunction {
         //TODO terrible name
@@ -37,18 +48,107 @@ pub enum Value {
     F64(f64),
 }
 
+impl Value {
+    /// The name of the value's type, as it is written in Witch.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Void => "void",
+            Value::Error(_) => "error",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::CString(_) => "cstring",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Variant(_) => "enum",
+            Value::Function(_) | Value::StackFunction { .. } | Value::NativeFunction(_) => {
+                "function"
+            }
+            Value::I8(_) => "i8",
+            Value::U8(_) => "u8",
+            Value::I16(_) => "i16",
+            Value::U16(_) => "u16",
+            Value::I32(_) => "i32",
+            Value::U32(_) => "u32",
+            Value::I64(_) => "i64",
+            Value::U64(_) => "u64",
+            Value::I128(_) => "i128",
+            Value::U128(_) => "u128",
+            Value::Isize(_) => "isize",
+            Value::Usize(_) => "usize",
+            Value::Char(_) => "char",
+            Value::F32(_) => "f32",
+            Value::F64(_) => "f64",
+        }
+    }
+
+    /// Formats a string, char, bool or number as text, the way it is written in Witch but
+    /// without quotes around strings. Other values have no text of their own.
+    pub fn to_text(&self) -> Result<String, ErrorKind> {
+        let text = match self {
+            Value::String(x) => x.clone(),
+            Value::Char(x) => x.to_string(),
+            Value::Bool(x) => x.to_string(),
+            Value::I8(x) => x.to_string(),
+            Value::U8(x) => x.to_string(),
+            Value::I16(x) => x.to_string(),
+            Value::U16(x) => x.to_string(),
+            Value::I32(x) => x.to_string(),
+            Value::U32(x) => x.to_string(),
+            Value::I64(x) => x.to_string(),
+            Value::U64(x) => x.to_string(),
+            Value::I128(x) => x.to_string(),
+            Value::U128(x) => x.to_string(),
+            Value::Isize(x) => x.to_string(),
+            Value::Usize(x) => x.to_string(),
+            Value::F32(x) => x.to_string(),
+            Value::F64(x) => x.to_string(),
+            found => {
+                return Err(ErrorKind::TypeMismatch {
+                    expected: "string",
+                    found: found.type_name(),
+                })
+            }
+        };
+        Ok(text)
+    }
+}
+
 impl From<()> for Value {
     fn from(_val: ()) -> Self {
         Value::Void
     }
 }
 
+impl From<bool> for Value {
+    fn from(val: bool) -> Self {
+        Value::Bool(val)
+    }
+}
+
 impl From<c_int> for Value {
     fn from(val: c_int) -> Self {
         Value::Usize(val as usize)
     }
 }
 
+impl From<usize> for Value {
+    fn from(val: usize) -> Self {
+        Value::Usize(val)
+    }
+}
+
+impl From<isize> for Value {
+    fn from(val: isize) -> Self {
+        Value::Isize(val)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(val: f64) -> Self {
+        Value::F64(val)
+    }
+}
+
 impl From<CString> for Value {
     fn from(val: CString) -> Self {
         Value::CString(val)
@@ -61,29 +161,280 @@ impl From<String> for Value {
     }
 }
 
-impl From<Value> for usize {
-    fn from(val: Value) -> Self {
+impl TryFrom<Value> for usize {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<Self, Self::Error> {
+        match val {
+            Value::Usize(i) => Ok(i),
+            found => Err(ErrorKind::TypeMismatch {
+                expected: "usize",
+                found: found.type_name(),
+            }),
+        }
+    }
+}
+
+/// The items of a list, for builtins which take or return one.
+pub struct List(pub Vec<Value>);
+
+impl From<List> for Value {
+    fn from(val: List) -> Self {
+        Value::List(val.0)
+    }
+}
+
+impl TryFrom<Value> for List {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<Self, Self::Error> {
+        match val {
+            Value::List(items) => Ok(List(items)),
+            found => Err(ErrorKind::TypeMismatch {
+                expected: "list",
+                found: found.type_name(),
+            }),
+        }
+    }
+}
+
+/// The key of a map. Only values which are compared by their contents can be keys, so that two
+/// keys are equal exactly when they hash the same. Floats can not, as NaN is not equal to itself,
+/// and neither can functions, maps or errors.
+#[derive(Serialize, Debug, Deserialize, PartialEq, Eq, Hash, Clone)]
+pub enum MapKey {
+    Void,
+    Bool(bool),
+    String(String),
+    CString(CString),
+    List(Vec<Self>),
+    Variant(usize, Vec<Self>),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+    Isize(isize),
+    Usize(usize),
+    Char(char),
+}
+
+impl TryFrom<Value> for MapKey {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<Self, Self::Error> {
+        let keys = |items: Vec<Value>| -> Result<Vec<_>, _> {
+            items.into_iter().map(MapKey::try_from).collect()
+        };
+        let key = match val {
+            Value::Void => MapKey::Void,
+            Value::Bool(x) => MapKey::Bool(x),
+            Value::String(x) => MapKey::String(x),
+            Value::CString(x) => MapKey::CString(x),
+            Value::List(x) => MapKey::List(keys(x)?),
+            Value::Variant(x) => MapKey::Variant(x.discriminant, keys(x.payload)?),
+            Value::I8(x) => MapKey::I8(x),
+            Value::U8(x) => MapKey::U8(x),
+            Value::I16(x) => MapKey::I16(x),
+            Value::U16(x) => MapKey::U16(x),
+            Value::I32(x) => MapKey::I32(x),
+            Value::U32(x) => MapKey::U32(x),
+            Value::I64(x) => MapKey::I64(x),
+            Value::U64(x) => MapKey::U64(x),
+            Value::I128(x) => MapKey::I128(x),
+            Value::U128(x) => MapKey::U128(x),
+            Value::Isize(x) => MapKey::Isize(x),
+            Value::Usize(x) => MapKey::Usize(x),
+            Value::Char(x) => MapKey::Char(x),
+            found => {
+                return Err(ErrorKind::TypeMismatch {
+                    expected: "map key",
+                    found: found.type_name(),
+                })
+            }
+        };
+        Ok(key)
+    }
+}
+
+impl From<MapKey> for Value {
+    fn from(val: MapKey) -> Self {
+        let values = |keys: Vec<MapKey>| keys.into_iter().map(Value::from).collect();
         match val {
-            Value::Usize(i) => i,
-            _ => unreachable!(),
+            MapKey::Void => Value::Void,
+            MapKey::Bool(x) => Value::Bool(x),
+            MapKey::String(x) => Value::String(x),
+            MapKey::CString(x) => Value::CString(x),
+            MapKey::List(x) => Value::List(values(x)),
+            MapKey::Variant(discriminant, payload) => {
+                Value::Variant(Variant::new(discriminant, values(payload)))
+            }
+            MapKey::I8(x) => Value::I8(x),
+            MapKey::U8(x) => Value::U8(x),
+            MapKey::I16(x) => Value::I16(x),
+            MapKey::U16(x) => Value::U16(x),
+            MapKey::I32(x) => Value::I32(x),
+            MapKey::U32(x) => Value::U32(x),
+            MapKey::I64(x) => Value::I64(x),
+            MapKey::U64(x) => Value::U64(x),
+            MapKey::I128(x) => Value::I128(x),
+            MapKey::U128(x) => Value::U128(x),
+            MapKey::Isize(x) => Value::Isize(x),
+            MapKey::Usize(x) => Value::Usize(x),
+            MapKey::Char(x) => Value::Char(x),
         }
     }
 }
 
-impl From<Value> for String {
-    fn from(val: Value) -> Self {
+impl From<bool> for MapKey {
+    fn from(val: bool) -> Self {
+        MapKey::Bool(val)
+    }
+}
+
+impl From<usize> for MapKey {
+    fn from(val: usize) -> Self {
+        MapKey::Usize(val)
+    }
+}
+
+impl From<isize> for MapKey {
+    fn from(val: isize) -> Self {
+        MapKey::Isize(val)
+    }
+}
+
+impl From<CString> for MapKey {
+    fn from(val: CString) -> Self {
+        MapKey::CString(val)
+    }
+}
+
+impl From<String> for MapKey {
+    fn from(val: String) -> Self {
+        MapKey::String(val)
+    }
+}
+
+impl<K: Into<MapKey>, V: Into<Value>, S> From<HashMap<K, V, S>> for Value {
+    fn from(val: HashMap<K, V, S>) -> Self {
+        Value::Map(
+            val.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V, S> TryFrom<Value> for HashMap<K, V, S>
+where
+    K: TryFrom<Value, Error = ErrorKind> + Eq + Hash,
+    V: TryFrom<Value, Error = ErrorKind>,
+    S: BuildHasher + Default,
+{
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<Self, Self::Error> {
         match val {
-            Value::String(i) => i,
-            _ => unreachable!(),
+            Value::Map(entries) => entries
+                .into_iter()
+                .map(|(key, value)| Ok((K::try_from(key.into())?, V::try_from(value)?)))
+                .collect(),
+            found => Err(ErrorKind::TypeMismatch {
+                expected: "map",
+                found: found.type_name(),
+            }),
         }
     }
 }
 
-impl From<Value> for CString {
-    fn from(val: Value) -> Self {
+/// A variant of a Witch enum, made up of its discriminant, the index of the variant within its
+/// enum, and its payload.
+#[derive(Serialize, Debug, Deserialize, PartialEq, Clone)]
+pub struct Variant {
+    pub discriminant: usize,
+    pub payload: Vec<Value>,
+}
+
+impl Variant {
+    pub fn new(discriminant: usize, payload: Vec<Value>) -> Self {
+        Self {
+            discriminant,
+            payload,
+        }
+    }
+}
+
+impl From<Variant> for Value {
+    fn from(val: Variant) -> Self {
+        Value::Variant(val)
+    }
+}
+
+/// Builtins which may fail return the prelude's `Result` enum, whose `Ok` variant comes first.
+impl<T: Into<Value>, E: Into<Value>> From<Result<T, E>> for Value {
+    fn from(val: Result<T, E>) -> Self {
+        match val {
+            Ok(value) => Variant::new(0, vec![value.into()]).into(),
+            Err(error) => Variant::new(1, vec![error.into()]).into(),
+        }
+    }
+}
+
+/// Builtins which may find nothing return the prelude's `Option` enum, whose `None` variant comes
+/// first.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(val: Option<T>) -> Self {
+        match val {
+            None => Variant::new(0, vec![]).into(),
+            Some(value) => Variant::new(1, vec![value.into()]).into(),
+        }
+    }
+}
+
+impl TryFrom<Value> for Variant {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<Self, Self::Error> {
+        match val {
+            Value::Variant(variant) => Ok(variant),
+            found => Err(ErrorKind::TypeMismatch {
+                expected: "enum",
+                found: found.type_name(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<Self, Self::Error> {
+        match val {
+            Value::String(i) => Ok(i),
+            found => Err(ErrorKind::TypeMismatch {
+                expected: "string",
+                found: found.type_name(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Value> for CString {
+    type Error = ErrorKind;
+
+    fn try_from(val: Value) -> Result<Self, Self::Error> {
         match val {
-            Value::CString(i) => i,
-            _ => unreachable!(),
+            Value::CString(i) => Ok(i),
+            found => Err(ErrorKind::TypeMismatch {
+                expected: "cstring",
+                found: found.type_name(),
+            }),
         }
     }
 }
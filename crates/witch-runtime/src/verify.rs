@@ -0,0 +1,255 @@
+//! Static verification of bytecode images before they are run.
+//!
+//! `Vm::run` verifies every image it loads, so that malformed or hand-edited bytecode is rejected
+//! up front instead of failing halfway through, or worse, misbehaving. The verifier checks that:
+//!
+//! - every instruction decodes, see `disasm::decode`
+//! - every jump target (`Jump`, `JumpIfFalse`, `JumpBack` and `SetReturn`) is the start of an
+//!   instruction
+//! - `Get`, `Set` and `SetProperty` refer to stack slots which exist at that point
+//! - the stack depth is the same along every path reaching an instruction
+//! - function bodies end by returning, and match the function table of the image
+//!
+//! The top level code and each function body are verified separately, as every call gets its own
+//! stack frame. Since a function's frame starts below its arguments, its body starts out with a
+//! stack depth equal to its arity. Code which `Vm::eval` appends to a program starts out with the
+//! stack left behind by the code before it.
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::disasm::{decode, Instruction};
+use crate::error::{Error, ErrorKind, Result};
+use crate::image::{FunctionEntry, Image};
+use crate::value::Value;
+
+/// A stretch of code which runs in a single stack frame.
+struct Unit {
+    range: Range<usize>,
+
+    /// The stack depth when entering the unit
+    depth: usize,
+
+    /// Whether the unit is a function body, which has to end by returning
+    is_function: bool,
+}
+
+/// Verifies the code of an image, returning the first problem found.
+pub fn verify(image: &Image) -> Result<()> {
+    verify_code(&image.code, &image.constants, &image.functions, 0)
+}
+
+/// Verifies code which starts out with `depth` entries on the stack, given the constant pool
+/// and function table it is run with.
+pub(crate) fn verify_code(
+    code: &[u8],
+    constants: &[Value],
+    function_table: &[FunctionEntry],
+    depth: usize,
+) -> Result<()> {
+    let mut units = vec![Unit {
+        range: 0..code.len(),
+        depth,
+        is_function: false,
+    }];
+    let mut functions = vec![];
+    while let Some(unit) = units.pop() {
+        let mut nested = verify_unit(code, constants, &unit)?;
+        functions.extend(nested.iter().map(|(body, arity)| (body.clone(), *arity)));
+        units.extend(nested.drain(..).map(|(range, depth)| Unit {
+            range,
+            depth,
+            is_function: true,
+        }));
+    }
+
+    // The function table has to describe exactly the functions in the code
+    let listed = |(body, arity): &(Range<usize>, usize)| {
+        function_table
+            .iter()
+            .any(|f| f.addr == body.start && f.len == body.len() && f.arity == *arity)
+    };
+    if let Some((body, _)) = functions.iter().find(|f| !listed(f)) {
+        return Err(Error::new(body.start, ErrorKind::MalformedImage));
+    }
+    if functions.len() != function_table.len() {
+        return Err(Error::new(0, ErrorKind::MalformedImage));
+    }
+    Ok(())
+}
+
+/// Verifies a single unit, returning the bodies and arities of the functions defined within it.
+fn verify_unit(
+    code: &[u8],
+    constants: &[Value],
+    unit: &Unit,
+) -> Result<Vec<(Range<usize>, usize)>> {
+    let mut instructions = BTreeMap::new();
+    let mut functions = vec![];
+
+    let mut pos = unit.range.start;
+    while pos < unit.range.end {
+        let (instruction, len) =
+            decode(code, constants, pos).map_err(|kind| Error::new(pos, kind))?;
+        let next = match &instruction {
+            Instruction::PushFunction { constant, body } => {
+                let Some(Value::Function(function)) = constants.get(*constant) else {
+                    return Err(Error::new(pos, ErrorKind::InvalidConstant));
+                };
+                if body.end > unit.range.end {
+                    return Err(Error::new(pos, ErrorKind::UnexpectedEnd));
+                }
+                functions.push((body.clone(), function.arity));
+                body.end
+            }
+            _ => pos + len,
+        };
+        instructions.insert(pos, (instruction, next));
+        pos = next;
+    }
+    if pos != unit.range.end {
+        return Err(Error::new(unit.range.start, ErrorKind::UnexpectedEnd));
+    }
+
+    // Jumps have to land on an instruction, or at the very end of the unit
+    let lands = |target: &usize| instructions.contains_key(target) || *target == unit.range.end;
+    for (pos, (instruction, _)) in instructions.iter() {
+        match instruction {
+            Instruction::Jump { target }
+            | Instruction::JumpIfFalse { target }
+            | Instruction::JumpBack { target }
+            | Instruction::SetReturn { target }
+                if !lands(target) =>
+            {
+                return Err(Error::new(*pos, ErrorKind::InvalidJumpTarget(*target)));
+            }
+            _ => {}
+        }
+    }
+
+    // Follow every path through the unit, tracking the stack depth before each instruction
+    let mut depths: BTreeMap<usize, usize> = BTreeMap::new();
+
+    // The stack depth after a call returns to the target of its `SetReturn`
+    let mut returns: BTreeMap<usize, usize> = BTreeMap::new();
+
+    let mut worklist = vec![(unit.range.start, unit.depth)];
+    while let Some((pos, depth)) = worklist.pop() {
+        if pos == unit.range.end {
+            if unit.is_function {
+                return Err(Error::new(pos, ErrorKind::MissingReturn));
+            }
+            continue;
+        }
+        match depths.get(&pos) {
+            Some(expected) if *expected == depth => continue,
+            Some(expected) => {
+                return Err(Error::new(
+                    pos,
+                    ErrorKind::InconsistentStack {
+                        expected: *expected,
+                        found: depth,
+                    },
+                ))
+            }
+            None => {
+                depths.insert(pos, depth);
+            }
+        }
+
+        let (instruction, next) = &instructions[&pos];
+        let err = |kind| Error::new(pos, kind);
+        let pop = |n: usize| depth.checked_sub(n).ok_or(err(ErrorKind::StackUnderflow));
+        let slot = |slot: u8, depth: usize| {
+            if (slot as usize) < depth {
+                Ok(())
+            } else {
+                Err(err(ErrorKind::InvalidSlot(slot as usize)))
+            }
+        };
+
+        let after = match instruction {
+            Instruction::SetupModule { entries } => pop(*entries as usize)?,
+            Instruction::SetupFunctionCache { count } => pop(*count)?,
+            Instruction::GetModuleSymbol { .. }
+            | Instruction::GetFunction(_)
+            | Instruction::GetBuiltin(_)
+            | Instruction::GetGlobal(_)
+            | Instruction::GetUpvalue(_)
+            | Instruction::Push { .. } => depth + 1,
+            Instruction::PushFunction { constant, .. } => {
+                // Upvalues which are local refer to slots of this frame. A recursive function
+                // captures the slot it is about to be stored in, right above the stack.
+                if let Some(Value::Function(function)) = constants.get(*constant) {
+                    for upvalue in function.upvalues_bytecode.chunks(2) {
+                        if let [1, idx] = upvalue {
+                            slot(*idx, depth + 1)?;
+                        }
+                    }
+                }
+                depth + 1
+            }
+            Instruction::Pop => pop(1)?,
+            Instruction::Dup => pop(1)? + 2,
+            Instruction::Get(idx) => {
+                slot(*idx, depth)?;
+                depth + 1
+            }
+            Instruction::GetMember { index: Some(_) } => pop(1)? + 1,
+            Instruction::GetMember { index: None } => pop(2)? + 1,
+            Instruction::Set(idx) => {
+                // Setting the slot right above the stack pushes onto it
+                let depth = pop(1)?;
+                slot(*idx, depth + 1)?;
+                if *idx as usize == depth {
+                    depth + 1
+                } else {
+                    depth
+                }
+            }
+            Instruction::SetProperty { slot: idx, .. } => {
+                let depth = pop(1)?;
+                slot(*idx, depth)?;
+                depth
+            }
+            Instruction::SetReturn { target } => {
+                returns.insert(*target, depth + 1);
+                depth + 1
+            }
+            Instruction::Jump { target } | Instruction::JumpBack { target } => {
+                worklist.push((*target, depth));
+                continue;
+            }
+            Instruction::JumpIfFalse { target } => {
+                let depth = pop(1)?;
+                worklist.push((*target, depth));
+                depth
+            }
+            Instruction::Binary(_) => pop(2)? + 1,
+            Instruction::Unary(_) | Instruction::Cast(_) => pop(1)? + 1,
+            Instruction::Return => {
+                if !unit.is_function {
+                    return Err(err(ErrorKind::InvalidReturnAddress));
+                }
+                pop(1)?;
+                continue;
+            }
+            Instruction::Call => {
+                pop(1)?;
+                *returns
+                    .get(next)
+                    .ok_or(err(ErrorKind::InvalidReturnAddress))?
+            }
+            Instruction::Collect { len } => pop(*len)? + 1,
+            Instruction::CollectMap { len } => pop(len.saturating_mul(2))? + 1,
+            Instruction::Concat { len } => pop(*len)? + 1,
+            Instruction::Variant { len, .. } => pop(*len)? + 1,
+            Instruction::Discriminant => pop(1)? + 1,
+            Instruction::Debug => depth,
+        };
+        worklist.push((*next, after));
+    }
+
+    Ok(functions)
+}
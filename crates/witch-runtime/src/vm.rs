@@ -1,5 +1,6 @@
 use crate::alloc::borrow::ToOwned;
 use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 #[cfg(feature = "profile")]
 use std::collections::HashMap;
@@ -7,14 +8,21 @@ use std::collections::HashMap;
 use crate::{builtins, dbg};
 
 use alloc::rc::Rc;
+use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use serde::{Deserialize, Serialize};
 
 use crate::builtins::Builtin;
+use crate::error::{Error, ErrorKind, Result};
 use crate::heap::Heap;
+use crate::image::{self, Image, OPERAND_SIZE};
+use crate::limits::{Limits, Resource, Usage};
+use crate::numeric::{self, Numeric};
+use crate::source_map::{SourceMap, TraceFrame};
 use crate::stack::{Entry, Function as StackFunction, Pointer, Stack};
-use crate::value::Value;
+use crate::value::{MapKey, Value};
+use crate::verify::{verify, verify_code};
 
 #[derive(Debug)]
 enum Upvalue {
@@ -41,11 +49,14 @@ pub enum InfixOp {
     Gte,
     And,
     Or,
+    Pow,
 }
 
-impl core::convert::From<u8> for InfixOp {
-    fn from(byte: u8) -> Self {
-        match byte {
+impl core::convert::TryFrom<u8> for InfixOp {
+    type Error = ErrorKind;
+
+    fn try_from(byte: u8) -> core::result::Result<Self, Self::Error> {
+        let op = match byte {
             0 => InfixOp::Add,
             1 => InfixOp::Sub,
             2 => InfixOp::Mul,
@@ -59,8 +70,30 @@ impl core::convert::From<u8> for InfixOp {
             10 => InfixOp::Gte,
             11 => InfixOp::And,
             12 => InfixOp::Or,
-            _ => todo!(),
-        }
+            13 => InfixOp::Pow,
+            x => return Err(ErrorKind::UnknownInfixOp(x)),
+        };
+        Ok(op)
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+impl core::convert::TryFrom<u8> for UnaryOp {
+    type Error = ErrorKind;
+
+    fn try_from(byte: u8) -> core::result::Result<Self, Self::Error> {
+        let op = match byte {
+            0 => UnaryOp::Not,
+            1 => UnaryOp::Neg,
+            x => return Err(ErrorKind::UnknownUnaryOp(x)),
+        };
+        Ok(op)
     }
 }
 
@@ -76,7 +109,9 @@ pub enum Op {
 
     Push,
     Pop,
+    Dup,
     Get,
+    GetGlobal,
     GetUpvalue,
     GetMember,
     Set,
@@ -85,12 +120,19 @@ pub enum Op {
     SetReturn,
     Jump,
     JumpIfFalse,
+    JumpBack,
 
     Binary,
+    Unary,
+    Cast,
     Return,
     Call,
 
     Collect,
+    CollectMap,
+    Concat,
+    Variant,
+    Discriminant,
 
     Debug,
 
@@ -109,23 +151,32 @@ impl core::convert::From<u8> for Op {
 
             6 => Op::Push,
             7 => Op::Pop,
-            8 => Op::Get,
-            9 => Op::GetUpvalue,
-            10 => Op::GetMember,
-            11 => Op::Set,
-            12 => Op::SetProperty,
-
-            13 => Op::SetReturn,
-            14 => Op::Jump,
-            15 => Op::JumpIfFalse,
-
-            16 => Op::Binary,
-            17 => Op::Return,
-            18 => Op::Call,
-
-            19 => Op::Collect,
-
-            20 => Op::Debug,
+            8 => Op::Dup,
+            9 => Op::Get,
+            10 => Op::GetGlobal,
+            11 => Op::GetUpvalue,
+            12 => Op::GetMember,
+            13 => Op::Set,
+            14 => Op::SetProperty,
+
+            15 => Op::SetReturn,
+            16 => Op::Jump,
+            17 => Op::JumpIfFalse,
+            18 => Op::JumpBack,
+
+            19 => Op::Binary,
+            20 => Op::Unary,
+            21 => Op::Cast,
+            22 => Op::Return,
+            23 => Op::Call,
+
+            24 => Op::Collect,
+            25 => Op::CollectMap,
+            26 => Op::Concat,
+            27 => Op::Variant,
+            28 => Op::Discriminant,
+
+            29 => Op::Debug,
 
             _ => Op::Crash,
         }
@@ -149,6 +200,9 @@ pub struct Vm {
     /// The bytecode of the current callframe. Only used for lookups (next N bytes, etc..)
     bytecode: Vec<u8>,
 
+    /// The constant pool of the image being run, indexed by `Op::Push`
+    constants: Vec<Value>,
+
     builtins: Vec<Builtin>,
 
     /// Our function vtable. Struct methods go here.
@@ -159,6 +213,48 @@ pub struct Vm {
 
     /// A vec of pointers to the Heap, where we store our upvalues for closures
     upvalues: Vec<Upvalue>,
+
+    /// Debug information for the bytecode, used to produce backtraces on errors
+    source_map: Option<SourceMap>,
+
+    /// The number of instructions left to execute, or `None` if unlimited
+    fuel: Option<u64>,
+
+    /// Set by an `InterruptHandle` to stop execution at the next instruction
+    interrupt: Arc<AtomicBool>,
+
+    /// The run which stopped with a resumable error, if any. See `Vm::resume`.
+    suspended: Option<Suspended>,
+
+    limits: Limits,
+
+    /// The heap is collected once it holds this many objects, see `Vm::set_gc_threshold`
+    gc_threshold: usize,
+
+    /// The object count which triggers the next collection
+    next_gc: usize,
+}
+
+/// The number of heap objects which triggers the first garbage collection by default.
+const GC_THRESHOLD: usize = 1024;
+
+/// How a suspended run was started, so that `Vm::resume` can finish it the same way.
+#[derive(Debug, Clone, Copy)]
+enum Suspended {
+    Run,
+    Eval { stack_len: usize },
+}
+
+/// Stops a `Vm` at its next instruction, possibly from another thread. See `Vm::interrupt_handle`.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Makes the VM fail with `ErrorKind::Interrupted` before executing its next instruction.
+    /// If the VM is not running, the interrupt takes effect once it starts.
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
 }
 
 impl Default for Vm {
@@ -179,10 +275,116 @@ impl Vm {
             modules: vec![],
             frames: vec![],
             bytecode: vec![],
+            constants: vec![],
             builtins: builtins::builtins(),
             functions: vec![],
             upvalue_refs: vec![],
             upvalues: vec![],
+            source_map: None,
+            fuel: None,
+            interrupt: Arc::new(AtomicBool::new(false)),
+            suspended: None,
+            limits: Limits::default(),
+            gc_threshold: GC_THRESHOLD,
+            next_gc: GC_THRESHOLD,
+        }
+    }
+
+    /// Sets the number of heap objects at which the garbage collector first runs. After each
+    /// collection, the next one is triggered once the heap has doubled, but never below `threshold`.
+    /// Collections also run whenever the heap would exceed its `Limits`.
+    pub fn set_gc_threshold(&mut self, threshold: usize) {
+        self.gc_threshold = threshold;
+        self.next_gc = self.clamp_gc(threshold);
+    }
+
+    /// Frees every heap object which is no longer reachable from the stack, the module stacks or
+    /// closed upvalues, returning how many were freed. Runs automatically in between instructions.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.collect(&[])
+    }
+
+    /// Like `collect_garbage`, but also keeps the `in_flight` heap pointers alive, which an
+    /// instruction has popped off the stack but not yet stored anywhere.
+    fn collect(&mut self, in_flight: &[usize]) -> usize {
+        let entries = self
+            .stack
+            .iter()
+            .chain(self.modules.iter().flat_map(|module| module.iter()))
+            .copied();
+        let upvalues = self.upvalues.iter().filter_map(|upvalue| match upvalue {
+            Upvalue::Closed(ptr) => Some(Entry::Pointer(*ptr)),
+            Upvalue::Open(_) => None,
+        });
+        let roots = entries.chain(upvalues).filter_map(|entry| match entry {
+            Entry::Pointer(Pointer::Heap(ptr)) => Some(ptr),
+            _ => None,
+        });
+
+        let freed = self.heap.collect(roots.chain(in_flight.iter().copied()));
+        self.next_gc = self.clamp_gc(self.gc_threshold.max(self.heap.len() * 2));
+        freed
+    }
+
+    /// Keeps the next collection from being scheduled beyond the heap object limit.
+    fn clamp_gc(&self, next_gc: usize) -> usize {
+        self.limits
+            .heap_objects
+            .map_or(next_gc, |max| next_gc.min(max))
+    }
+
+    /// Caps the memory available to scripts. Limits which are already exceeded only take effect
+    /// once the script tries to use more.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.heap.set_limits(limits.heap_objects, limits.heap_bytes);
+        self.limits = limits;
+        self.next_gc = self.clamp_gc(self.next_gc);
+    }
+
+    /// The memory currently in use, to be compared against the `Limits` of the VM.
+    pub fn usage(&self) -> Usage {
+        Usage {
+            heap_objects: self.heap.len(),
+            heap_bytes: self.heap.bytes(),
+            stack_depth: self.stack.len(),
+            call_depth: self.frames.len(),
+        }
+    }
+
+    /// Limits the number of instructions the VM may execute, each of them consuming one unit of
+    /// fuel. Once the fuel runs out, execution stops with `ErrorKind::OutOfFuel`, and may be
+    /// continued with `resume` after adding more fuel. `None` lifts the limit.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    /// The fuel left, or `None` if the VM is not limited.
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Returns a handle through which the VM can be interrupted while it is running.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(self.interrupt.clone())
+    }
+
+    /// Provides debug information for the bytecode about to be run,
+    /// allowing errors to carry a Witch-level backtrace.
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = Some(source_map);
+    }
+
+    /// Resolves our call frames into a backtrace, innermost frame first.
+    /// Returns an empty backtrace if there is no source map available.
+    pub fn backtrace(&self) -> Vec<TraceFrame> {
+        match &self.source_map {
+            Some(source_map) => self
+                .frames
+                .iter()
+                .rev()
+                .map(|frame| source_map.trace(frame.ip))
+                .collect(),
+            None => vec![],
         }
     }
 
@@ -201,58 +403,115 @@ impl Vm {
         &mut self.frames[idx]
     }
 
+    /// Creates an error of the given kind, pointing at the current instruction.
+    pub fn error(&self, kind: ErrorKind) -> Error {
+        Error::new(self.frames.last().map(|f| f.ip).unwrap_or(0), kind)
+    }
+
+    /// Pops the topmost entry off the stack.
+    pub fn pop(&mut self) -> Result<Entry> {
+        self.stack
+            .pop()
+            .ok_or_else(|| self.error(ErrorKind::StackUnderflow))
+    }
+
+    /// Retrieves a copy of the stack entry at `idx`.
+    fn get(&mut self, idx: usize) -> Result<Entry> {
+        self.stack
+            .get(idx)
+            .ok_or_else(|| self.error(ErrorKind::StackUnderflow))
+    }
+
+    /// Retrieves `len` bytes, starting `offset` bytes after the instruction pointer.
+    fn bytes(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        let start = self.frame().ip + offset;
+        self.bytecode
+            .get(start..start + len)
+            .ok_or_else(|| self.error(ErrorKind::UnexpectedEnd))
+    }
+
     /// Retrieves the byte which the instruction pointer is currently pointing at.
-    fn current_byte(&mut self) -> u8 {
-        self.bytecode[self.frame().ip]
+    fn current_byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(0, 1)?[0])
     }
 
     /// Retrieves the byte which is after the byte that the instruction pointer is currently pointing at.
-    fn next_byte(&mut self) -> u8 {
-        self.bytecode[self.frame().ip + 1]
+    fn next_byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1, 1)?[0])
+    }
+
+    fn next_two_bytes(&mut self) -> Result<[u8; 2]> {
+        let bytes = self.bytes(1, 2)?;
+        Ok([bytes[0], bytes[1]])
     }
 
-    fn next_two_bytes(&mut self) -> [u8; 2] {
-        [
-            self.bytecode[self.frame().ip + 1],
-            self.bytecode[self.frame().ip + 2],
-        ]
+    /// Retrieves the operand starting `offset` bytes after the instruction pointer.
+    fn operand(&self, offset: usize) -> Result<usize> {
+        image::read_operand(&self.bytecode, self.frame().ip + offset)
+            .ok_or_else(|| self.error(ErrorKind::UnexpectedEnd))
     }
 
-    /// Retrieves the next 8 bytes from the current instruction pointer.
-    fn next_eight_bytes(&mut self) -> [u8; 8] {
-        self.bytecode[self.frame().ip + 1..self.frame().ip + 1 + 8]
-            .try_into()
-            .unwrap()
+    /// Retrieves the operand which follows the current instruction.
+    fn next_operand(&self) -> Result<usize> {
+        self.operand(1)
     }
 
-    fn entry_to_value_ref(&mut self, entry: Entry) -> Rc<RefCell<Value>> {
+    fn entry_to_value_ref(&mut self, entry: Entry) -> Result<Rc<RefCell<Value>>> {
         match entry {
-            Entry::Pointer(Pointer::Heap(idx)) => self.heap.get(idx),
-            Entry::Usize(u) => Rc::new(RefCell::new(Value::Usize(u))),
-            x => todo!("{:?}", x),
+            Entry::Pointer(Pointer::Heap(idx)) => {
+                self.heap.get(idx).map_err(|kind| self.error(kind))
+            }
+            Entry::Pointer(Pointer::Vtable(idx)) => {
+                let f = self
+                    .functions
+                    .get(idx)
+                    .ok_or_else(|| self.error(ErrorKind::InvalidFunction(idx)))?;
+                let value =
+                    Value::try_from(Entry::Function(*f)).map_err(|kind| self.error(kind))?;
+                Ok(Rc::new(RefCell::new(value)))
+            }
+            Entry::Pointer(Pointer::Builtin(idx)) => {
+                Ok(Rc::new(RefCell::new(Value::NativeFunction(idx))))
+            }
+            entry => {
+                let value = Value::try_from(entry).map_err(|kind| self.error(kind))?;
+                Ok(Rc::new(RefCell::new(value)))
+            }
         }
     }
 
-    pub fn entry_to_value(&mut self, entry: Entry) -> Value {
-        self.entry_to_value_ref(entry).borrow().clone()
+    pub fn entry_to_value(&mut self, entry: Entry) -> Result<Value> {
+        Ok(self.entry_to_value_ref(entry)?.borrow().clone())
     }
 
-    pub fn pop_value(&mut self) -> Option<Value> {
-        let entry = self.stack.pop();
-        if let Some(entry) = entry {
-            return Some(self.entry_to_value(entry));
-        }
-        None
+    pub fn pop_value(&mut self) -> Result<Value> {
+        let entry = self.pop()?;
+        self.entry_to_value(entry)
     }
 
     /// Pushes a Value onto the stack
-    pub fn push_value(&mut self, value: Value) {
+    pub fn push_value(&mut self, value: Value) -> Result<()> {
         let entry = match value {
             Value::Bool(x) => Entry::Bool(x),
             Value::Usize(x) => Entry::Usize(x),
-            value => Entry::Pointer(Pointer::Heap(self.heap.insert(value))),
+            value => Entry::Pointer(Pointer::Heap(self.alloc(value)?)),
         };
         self.stack.push(entry);
+        Ok(())
+    }
+
+    /// Inserts a value into the heap, failing if the heap is full.
+    fn alloc(&mut self, value: Value) -> Result<usize> {
+        self.alloc_in_flight(value, &[])
+    }
+
+    /// Inserts a value into the heap, first collecting garbage if it would not fit. Only fails if
+    /// the heap is still full afterwards. See `Vm::collect` for `in_flight`.
+    fn alloc_in_flight(&mut self, value: Value, in_flight: &[usize]) -> Result<usize> {
+        if !self.heap.fits(&value) {
+            self.collect(in_flight);
+        }
+        self.heap.insert(value).map_err(|kind| self.error(kind))
     }
 
     /// Moves a stack entry to the heap and stashes a copy of the pointer
@@ -265,15 +524,16 @@ impl Vm {
 
     /// Moves a stack entry to the heap and stashes a copy of the pointer
     /// among our `upvalues` to be referenced by a closure at a later time
-    fn close_upvalues(&mut self, frame: CallFrame) {
+    fn close_upvalues(&mut self, frame: CallFrame) -> Result<()> {
         for idx in (0..self.upvalues.len()).rev() {
             if let Upvalue::Open(stack_index) = self.upvalues[idx] {
                 if stack_index >= frame.stack_start {
-                    let entry = self.stack.get(stack_index);
+                    let entry = self.get(stack_index)?;
                     if let Entry::Pointer(ptr) = entry {
                         self.upvalues[idx] = Upvalue::Closed(ptr);
                     } else {
-                        let ptr = self.heap.insert(entry.into());
+                        let value = Value::try_from(entry).map_err(|kind| self.error(kind))?;
+                        let ptr = self.alloc(value)?;
                         self.upvalues[idx] = Upvalue::Closed(Pointer::Heap(ptr));
                     }
                 } else {
@@ -283,13 +543,14 @@ impl Vm {
                 }
             }
         }
+        Ok(())
     }
 
-    pub fn push_callframe(&mut self, entry: Entry) {
+    pub fn push_callframe(&mut self, entry: Entry) -> Result<()> {
         let f = match entry {
             Entry::Function(f) => f,
             Entry::Pointer(Pointer::Heap(ptr)) => {
-                let value = self.heap.get(ptr);
+                let value = self.heap.get(ptr).map_err(|kind| self.error(kind))?;
                 let tmp = value.borrow();
                 if let Value::StackFunction {
                     addr,
@@ -303,36 +564,66 @@ impl Vm {
                         upvalues_refs_idx,
                     }
                 } else {
-                    unreachable!()
+                    return Err(self.error(ErrorKind::TypeMismatch {
+                        expected: "function",
+                        found: tmp.type_name(),
+                    }));
                 }
             }
-            Entry::Pointer(Pointer::Vtable(p)) => self.functions[p],
+            Entry::Pointer(Pointer::Vtable(p)) => *self
+                .functions
+                .get(p)
+                .ok_or_else(|| self.error(ErrorKind::InvalidFunction(p)))?,
             x => {
-                dbg!(&self.stack);
-                dbg!(&self.entry_to_value(x));
-                unreachable!()
+                let found = self.entry_to_value(x)?.type_name();
+                return Err(self.error(ErrorKind::TypeMismatch {
+                    expected: "function",
+                    found,
+                }));
             }
         };
 
+        let stack_start = self
+            .stack
+            .len()
+            .checked_sub(f.arity)
+            .ok_or_else(|| self.error(ErrorKind::StackUnderflow))?;
+
+        if self
+            .limits
+            .call_depth
+            .is_some_and(|max| self.frames.len() >= max)
+        {
+            return Err(self.error(ErrorKind::ResourceLimitExceeded(Resource::CallDepth)));
+        }
+
         let frame = CallFrame {
             ip: f.addr,
-            stack_start: self.stack.len() - f.arity,
+            stack_start,
             upvalues_refs_idx: f.upvalues_refs_idx,
         };
 
         self.frames.push(frame);
+        Ok(())
     }
 
-    pub fn run(&mut self, bytecode: Vec<u8>) -> Result<Value, Value> {
+    /// Validates, verifies and runs a bytecode image, as produced by the compiler.
+    /// Returns the value left on top of the stack once the program has finished.
+    /// A previous run which was suspended, see `resume`, is abandoned.
+    pub fn run(&mut self, bytecode: Vec<u8>) -> Result<Value> {
         // Set up some profiling data
         #[cfg(feature = "profile")]
         let mut opcode_stats = HashMap::new();
 
-        if bytecode.is_empty() {
+        let image = Image::decode(&bytecode).map_err(|kind| Error::new(0, kind))?;
+        verify(&image)?;
+        self.abandon();
+        if image.code.is_empty() {
             return Ok(Value::Void);
         }
 
-        self.bytecode = bytecode;
+        self.bytecode = image.code;
+        self.constants = image.constants;
 
         let frame = CallFrame {
             ip: 0,
@@ -374,8 +665,95 @@ impl Vm {
         self.run_frame(0)
     }
 
+    /// Appends an image to the program and runs its code from the first instruction. Unlike `run`,
+    /// the stack, heap and function vtable of previous runs are kept, and nothing is popped off
+    /// the stack afterwards. If the code fails, the stack is restored to its previous length.
+    /// The constants of the image are appended to the constant pool, so the image may only
+    /// refer to constants of previous images by their index within the combined pool.
+    /// The code is verified against the stack left behind by previous images before it runs.
+    /// Code which fails verification is still appended, so that later images keep their
+    /// offsets within the program, but it never runs.
+    pub fn eval(&mut self, image: Image) -> Result<()> {
+        self.abandon();
+        let Image {
+            mut constants,
+            functions,
+            mut code,
+        } = image;
+        self.constants.append(&mut constants);
+        if code.is_empty() {
+            return Ok(());
+        }
+
+        let stack_len = self.stack.len();
+        let offset = self.bytecode.len();
+        let verified = verify_code(&code, &self.constants, &functions, stack_len);
+        self.bytecode.append(&mut code);
+        verified.map_err(|err| Error::new(offset + err.ip, err.kind))?;
+
+        let frame = CallFrame {
+            ip: offset,
+            stack_start: 0,
+            upvalues_refs_idx: 0,
+        };
+        self.frames.push(frame);
+        self.eval_frame(stack_len)
+    }
+
+    /// Continues a run which stopped with a resumable error, see `ErrorKind::is_resumable`.
+    /// Returns what `run` would have returned, or `Value::Void` when continuing `eval`.
+    /// If there is nothing to resume, returns `Value::Void` right away.
+    pub fn resume(&mut self) -> Result<Value> {
+        match self.suspended.take() {
+            Some(Suspended::Run) => self.run_frame(0),
+            Some(Suspended::Eval { stack_len }) => self.eval_frame(stack_len).map(|_| Value::Void),
+            None => Ok(Value::Void),
+        }
+    }
+
+    /// Drops the call frames of a suspended run, so that a new one can start.
+    fn abandon(&mut self) {
+        match self.suspended.take() {
+            // A run owns the whole stack, as its bottom frame starts at the very beginning of it
+            Some(Suspended::Run) => self.stack.truncate(0),
+            Some(Suspended::Eval { stack_len }) => self.stack.truncate(stack_len),
+            None => {}
+        }
+        self.frames.clear();
+    }
+
+    fn eval_frame(&mut self, stack_len: usize) -> Result<()> {
+        self.execute(0).map_err(|mut err| {
+            err.backtrace = self.backtrace();
+            if err.kind.is_resumable() {
+                self.suspended = Some(Suspended::Eval { stack_len });
+            } else {
+                self.frames.clear();
+                self.stack.truncate(stack_len);
+            }
+            err
+        })
+    }
+
     /// Executes a particular call frame and any subsequent frames
-    pub fn run_frame(&mut self, bottom_frame: usize) -> Result<Value, Value> {
+    pub fn run_frame(&mut self, bottom_frame: usize) -> Result<Value> {
+        self.execute(bottom_frame).map_err(|mut err| {
+            err.backtrace = self.backtrace();
+            if err.kind.is_resumable() && bottom_frame == 0 {
+                self.suspended = Some(Suspended::Run);
+            }
+            err
+        })?;
+
+        // When the script exits, return whatever is on the top of the stack
+        if let Some(entry) = self.stack.pop() {
+            self.entry_to_value(entry)
+        } else {
+            Ok(Value::Void)
+        }
+    }
+
+    fn execute(&mut self, bottom_frame: usize) -> Result<()> {
         while !self.frames.is_empty() && self.frames.len() > bottom_frame {
             // If we advance the instruction pointer to outside of our bytecode,
             // we implicitly return from the current call frame by popping self.frames.
@@ -391,12 +769,34 @@ impl Vm {
                 }
             }
 
+            if self.interrupt.swap(false, Ordering::Relaxed) {
+                return Err(self.error(ErrorKind::Interrupted));
+            }
+            // Every live pointer is on a stack or in an upvalue in between instructions
+            if self.heap.len() >= self.next_gc {
+                self.collect_garbage();
+            }
+
+            // The stack grows by a bounded number of entries per instruction
+            if self
+                .limits
+                .stack_depth
+                .is_some_and(|max| self.stack.len() > max)
+            {
+                return Err(self.error(ErrorKind::ResourceLimitExceeded(Resource::StackDepth)));
+            }
+            match self.fuel {
+                Some(0) => return Err(self.error(ErrorKind::OutOfFuel)),
+                Some(fuel) => self.fuel = Some(fuel - 1),
+                None => {}
+            }
+
             #[cfg(feature = "profile")]
             let opcode_timer_start = std::time::Instant::now();
 
-            let op = Op::from(self.current_byte());
+            let op = Op::from(self.current_byte()?);
             let mut offset = 0;
-            let forward = true;
+            let mut forward = true;
 
             #[cfg(feature = "profile")]
             let opcode_timer_start = std::time::Instant::now();
@@ -411,11 +811,15 @@ impl Vm {
                     // TODO
                     // Get the module stack length and take it off the top of the main stack
                     // Put it into self.modules
-                    let entries = self.next_byte();
+                    let entries = self.next_byte()?;
                     offset = 1;
 
                     let mut stack = Stack::new();
-                    for e in self.stack.take(entries as usize) {
+                    let taken = self
+                        .stack
+                        .take(entries as usize)
+                        .ok_or_else(|| self.error(ErrorKind::StackUnderflow))?;
+                    for e in taken {
                         stack.push(e);
                     }
 
@@ -423,52 +827,60 @@ impl Vm {
                 }
 
                 Op::SetupFunctionCache => {
-                    let num_items_bytes = self.next_eight_bytes();
-                    let num_items = usize::from_ne_bytes(num_items_bytes);
+                    let num_items = self.next_operand()?;
                     let mut items = vec![];
                     for _ in 0..num_items {
-                        items.push(self.stack.pop().unwrap());
+                        items.push(self.pop()?);
                     }
                     for e in items.iter().rev() {
                         if let Entry::Function(f) = e {
                             self.functions.push(*f);
                         }
                     }
-                    offset = 8;
+                    offset = OPERAND_SIZE;
                 }
 
                 Op::GetModuleSymbol => {
-                    let [module_idx, local_idx] = self.next_two_bytes();
+                    let [module_idx, local_idx] = self.next_two_bytes()?;
                     offset = 2;
 
-                    self.stack
-                        .push(self.modules[module_idx as usize].get(local_idx as usize));
+                    let entry = self
+                        .modules
+                        .get_mut(module_idx as usize)
+                        .ok_or(ErrorKind::InvalidModule(module_idx as usize))
+                        .and_then(|module| {
+                            module
+                                .get(local_idx as usize)
+                                .ok_or(ErrorKind::StackUnderflow)
+                        })
+                        .map_err(|kind| self.error(kind))?;
+                    self.stack.push(entry);
                 }
 
                 Op::GetFunction => {
-                    let idx = self.next_byte();
+                    let idx = self.next_byte()?;
                     self.stack
                         .push(Entry::Pointer(Pointer::Vtable(idx as usize)));
                     offset = 1;
                 }
 
                 Op::GetBuiltin => {
-                    let idx = self.next_byte();
+                    let idx = self.next_byte()?;
                     self.stack
                         .push(Entry::Pointer(Pointer::Builtin(idx as usize)));
                     offset = 1;
                 }
 
                 Op::Push => {
-                    let value_length_bytes: [u8; 8] = self.next_eight_bytes();
-                    let value_length = usize::from_ne_bytes(value_length_bytes);
+                    let constant = self.next_operand()?;
                     let ip = self.frame().ip;
-                    let value_bytes = &self.bytecode[(ip + 9)..(ip + 9 + value_length)];
                     let mut additional_offset = 0;
 
-                    let (value, _): (Value, usize) =
-                        bincode::serde::decode_from_slice(value_bytes, bincode::config::legacy())
-                            .unwrap();
+                    let value = self
+                        .constants
+                        .get(constant)
+                        .cloned()
+                        .ok_or_else(|| self.error(ErrorKind::InvalidConstant))?;
 
                     let stackentry = match value {
                         Value::Usize(i) => Entry::Usize(i),
@@ -486,122 +898,153 @@ impl Vm {
                                     .chunks(2)
                                     .enumerate()
                                 {
-                                    let is_local = x[0];
-                                    let idx = x[1];
+                                    let [is_local, idx] = x else {
+                                        return Err(self.error(ErrorKind::InvalidConstant));
+                                    };
 
                                     // If `is_local` is 1, the upvalue refers to an entry
                                     // in our local callframe's stack. We need to capture it to make sure
                                     // it keeps on living after we pop this frame.
-                                    if is_local == 1 {
+                                    if *is_local == 1 {
                                         let upv = self.capture_upvalue(
-                                            self.frame().stack_start + idx as usize,
+                                            self.frame().stack_start + *idx as usize,
                                         );
                                         upvalue_refs.insert(i, upv);
 
                                     // If it's not local, that means it refers to an upvalue among
                                     // this callframe's upvalues, which in turn refers to something else.
                                     } else {
-                                        let upv = self.upvalue_refs[self.frame().upvalues_refs_idx]
-                                            [idx as usize];
+                                        let upv = self
+                                            .upvalue_refs
+                                            .get(self.frame().upvalues_refs_idx)
+                                            .and_then(|refs| refs.get(*idx as usize))
+                                            .copied()
+                                            .ok_or_else(|| {
+                                                self.error(ErrorKind::InvalidUpvalue(*idx as usize))
+                                            })?;
                                         upvalue_refs.insert(i, upv);
                                     }
                                 }
 
                                 self.upvalue_refs.push(upvalue_refs);
 
-                                let func_len_bytes = (&self.bytecode
-                                    [(ip + 9 + value_length)..(ip + 9 + value_length + 8)])
-                                    .try_into()
-                                    .unwrap();
-                                additional_offset = usize::from_ne_bytes(func_len_bytes) + 8;
+                                // The function body follows its length, right after the constant
+                                let body_length = self.operand(1 + OPERAND_SIZE)?;
+                                additional_offset = OPERAND_SIZE + body_length;
 
                                 Entry::Function(StackFunction {
-                                    addr: ip + 9 + value_length + 8,
+                                    addr: ip + 1 + 2 * OPERAND_SIZE,
                                     arity: f.arity,
                                     upvalues_refs_idx: self.upvalue_refs.len() - 1,
                                 })
                             } else {
-                                Entry::Pointer(Pointer::Heap(self.heap.insert(value)))
+                                Entry::Pointer(Pointer::Heap(self.alloc(value)?))
                             }
                         }
                     };
 
                     self.stack.push(stackentry);
 
-                    offset = 8 + value_length + additional_offset;
+                    offset = OPERAND_SIZE + additional_offset;
                 }
 
                 Op::Pop => {
-                    self.stack.pop();
+                    self.pop()?;
+                }
+
+                Op::Dup => {
+                    let entry = self.pop()?;
+                    self.stack.push(entry);
+                    self.stack.push(entry);
                 }
 
                 Op::Get => {
-                    let b = self.next_byte();
-                    let entry = self.stack.get(self.frame().stack_start + b as usize);
+                    let b = self.next_byte()?;
+                    let entry = self.get(self.frame().stack_start + b as usize)?;
                     self.stack.push(entry);
 
                     offset = 1;
                 }
 
-                Op::GetUpvalue => {
-                    let slot = self.next_byte();
-                    let idx = self.upvalue_refs[self.frame().upvalues_refs_idx][slot as usize];
+                Op::GetGlobal => {
+                    let b = self.next_byte()?;
+                    let entry = self.get(b as usize)?;
+                    self.stack.push(entry);
 
-                    let upv = &self.upvalues[idx];
+                    offset = 1;
+                }
 
-                    let entry = match upv {
-                        Upvalue::Closed(ptr) => Entry::Pointer(*ptr),
-                        Upvalue::Open(idx) => self.stack.get(*idx),
+                Op::GetUpvalue => {
+                    let slot = self.next_byte()?;
+                    let upv = self
+                        .upvalue_refs
+                        .get(self.frame().upvalues_refs_idx)
+                        .and_then(|refs| refs.get(slot as usize))
+                        .and_then(|idx| self.upvalues.get(*idx))
+                        .ok_or_else(|| self.error(ErrorKind::InvalidUpvalue(slot as usize)))?;
+
+                    let entry = match *upv {
+                        Upvalue::Closed(ptr) => Entry::Pointer(ptr),
+                        Upvalue::Open(idx) => self.get(idx)?,
                     };
                     self.stack.push(entry);
 
                     offset = 1;
                 }
 
-                // Gets a list item by index
+                // Gets a list item by index, or the value of a map by key
                 Op::GetMember => {
-                    let [first, second] = self.next_two_bytes();
-                    let idx_is_next_byte = first == 1;
+                    // A popped key has no second byte, so the flag may be the last byte of the code
+                    let key_is_next_byte = self.next_byte()? == 1;
 
-                    let idx = if idx_is_next_byte {
+                    let key = if key_is_next_byte {
                         offset = 2;
-                        second as usize
+                        Entry::Usize(self.bytes(2, 1)?[0] as usize)
                     } else {
                         offset = 1;
+                        self.pop()?
+                    };
 
-                        match self.stack.pop() {
-                            Some(Entry::Usize(idx)) => idx,
-                            Some(e @ Entry::Pointer(Pointer::Heap(_))) => {
-                                match self.entry_to_value(e) {
-                                    Value::Usize(idx) => idx,
-                                    x => {
-                                        dbg!(&x);
-                                        unreachable!()
-                                    }
-                                }
-                            }
-                            x => {
-                                dbg!(&x);
-                                unreachable!();
-                            }
+                    let ptr = match self.pop()? {
+                        Entry::Pointer(Pointer::Heap(ptr)) => ptr,
+                        x => {
+                            let found = self.entry_to_value(x)?.type_name();
+                            return Err(self.error(ErrorKind::TypeMismatch {
+                                expected: "list",
+                                found,
+                            }));
                         }
                     };
-                    if let Some(entry) = self.stack.last_mut() {
-                        *entry = match entry {
-                            Entry::Pointer(Pointer::Heap(ptr)) => Entry::Pointer(Pointer::Heap(
-                                self.heap.get_list_item_ptr(*ptr, idx),
-                            )),
-                            x => {
-                                dbg!(&x);
-                                unreachable!()
-                            }
+
+                    let item = if self.heap.is_map(ptr) {
+                        let key = self.entry_to_value(key)?;
+                        let key = MapKey::try_from(key).map_err(|kind| self.error(kind))?;
+                        self.heap.get_map_item_ptr(ptr, &key)
+                    } else if let (true, Entry::Usize(idx)) = (self.heap.is_variant(ptr), key) {
+                        // Matching gets at the payload of variants, which can not be indexed into
+                        self.heap.get_payload_ptr(ptr, idx)
+                    } else {
+                        let idx = match key {
+                            Entry::Usize(idx) => idx,
+                            e => match self.entry_to_value(e)? {
+                                Value::Usize(idx) => idx,
+                                found => {
+                                    return Err(self.error(ErrorKind::TypeMismatch {
+                                        expected: "usize",
+                                        found: found.type_name(),
+                                    }));
+                                }
+                            },
                         };
-                    }
+                        self.heap.get_list_item_ptr(ptr, idx)
+                    };
+                    let item = item.map_err(|kind| self.error(kind))?;
+                    self.stack.push(Entry::Pointer(Pointer::Heap(item)));
                 }
 
                 Op::Set => {
-                    let idx = self.next_byte();
-                    let stackentry = self.stack.pop().unwrap();
+                    let idx = self.next_byte()?;
+                    let stackentry = self.pop()?;
 
                     let stack_idx = self.frame().stack_start + idx as usize;
                     if stack_idx == self.stack.len() {
@@ -618,114 +1061,191 @@ impl Vm {
                         //     stackentry =
                         //         StackEntry::Pointer(self.heap.insert(Value::Dylib(new_dy)));
                         // }
-                        self.stack.set(stack_idx, stackentry);
+                        self.stack
+                            .set(stack_idx, stackentry)
+                            .ok_or_else(|| self.error(ErrorKind::StackUnderflow))?;
                     }
                     offset = 1;
                 }
 
                 Op::SetProperty => {
-                    let idx = self.next_byte();
-                    let property_idx = self.next_byte();
-                    let rhs = self.stack.pop().unwrap();
+                    let [idx, property_idx] = self.next_two_bytes()?;
+                    let rhs = self.pop()?;
 
-                    let entry = self.stack.get(self.frame().stack_start + idx as usize);
+                    let entry = self.get(self.frame().stack_start + idx as usize)?;
                     match entry {
                         Entry::Pointer(Pointer::Heap(ptr)) => {
-                            let item_ptr = self.heap.get_list_item_ptr(ptr, property_idx as usize);
-                            let item = self.heap.get(item_ptr);
-                            let mut item = item.borrow_mut();
-                            *item = self.entry_to_value_ref(rhs).borrow().to_owned();
+                            let item_ptr = self
+                                .heap
+                                .get_list_item_ptr(ptr, property_idx as usize)
+                                .map_err(|kind| self.error(kind))?;
+                            let item = self.heap.get(item_ptr).map_err(|kind| self.error(kind))?;
+                            let value = self.entry_to_value(rhs)?;
+                            *item.borrow_mut() = value;
+                        }
+                        x => {
+                            let found = self.entry_to_value(x)?.type_name();
+                            return Err(self.error(ErrorKind::TypeMismatch {
+                                expected: "list",
+                                found,
+                            }));
                         }
-                        _ => unreachable!(),
                     }
 
                     offset = 2;
                 }
 
-                // Sets the next byte as the return address on the stack.
+                // Sets the operand as the return address on the stack, relative to this instruction.
                 // This gets placed before the arguments for an upcoming Call instruction.
                 Op::SetReturn => {
-                    let jmp_offset = usize::from_ne_bytes(self.next_eight_bytes());
+                    let jmp_offset = self.next_operand()?;
                     self.stack.push(Entry::Usize(self.frame().ip + jmp_offset));
-                    offset = 8;
+                    offset = OPERAND_SIZE;
                 }
 
-                Op::Jump => {}
+                // Skips forward by the operand, which includes the size of the operand itself.
+                Op::Jump => {
+                    offset = self.next_operand()?;
+                }
 
                 Op::JumpIfFalse => {
-                    let mut jmp_offset = 0;
-                    let cond = self.stack.pop().unwrap();
-                    if let Entry::Bool(false) = cond {
-                        jmp_offset = u64::from_ne_bytes(self.next_eight_bytes()) as usize;
+                    // Bools may also be held on the heap, such as when they are read from a list
+                    let cond = match self.pop()? {
+                        Entry::Bool(cond) => cond,
+                        entry => match self.entry_to_value(entry)? {
+                            Value::Bool(cond) => cond,
+                            value => {
+                                return Err(self.error(ErrorKind::TypeMismatch {
+                                    expected: "bool",
+                                    found: value.type_name(),
+                                }))
+                            }
+                        },
+                    };
+                    let jmp_offset = if cond { 0 } else { self.next_operand()? };
+                    offset = OPERAND_SIZE + jmp_offset;
+                }
+
+                // Skips backward by the operand, counting from the start of this instruction.
+                Op::JumpBack => {
+                    offset = self.next_operand()?;
+                    if self.frame().ip.checked_sub(offset).is_none() {
+                        return Err(self.error(ErrorKind::JumpBeforeStart(offset)));
                     }
-                    offset = 8 + jmp_offset;
+                    forward = false;
                 }
 
                 // Conducts a binary operation between the two top entries on the stack.
                 Op::Binary => {
-                    let bin_op = InfixOp::from(self.next_byte());
-                    let b = self.stack.pop().unwrap();
-                    let a = self.stack.pop().unwrap();
-
-                    let r = match (a, bin_op, b) {
-                        (Entry::Usize(a), InfixOp::Add, Entry::Usize(b)) => Entry::Usize(a + b),
-                        (Entry::Usize(a), InfixOp::Sub, Entry::Usize(b)) => Entry::Usize(a - b),
-                        (Entry::Usize(a), InfixOp::Mul, Entry::Usize(b)) => Entry::Usize(a * b),
-                        (Entry::Usize(a), InfixOp::Div, Entry::Usize(b)) => Entry::Usize(a / b),
-                        (Entry::Usize(a), InfixOp::Lt, Entry::Usize(b)) => Entry::Bool(a < b),
-
-                        (e1 @ Entry::Pointer(_), op, e2) | (e1, op, e2 @ Entry::Pointer(_)) => {
-                            match (
-                                &*self.entry_to_value_ref(e1).borrow(),
-                                op,
-                                &*self.entry_to_value_ref(e2).borrow(),
-                            ) {
-                                (Value::Usize(a), InfixOp::Add, Value::Usize(b)) => {
-                                    Entry::Usize(a + b)
-                                }
+                    let bin_op =
+                        InfixOp::try_from(self.next_byte()?).map_err(|kind| self.error(kind))?;
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+
+                    let result = match (a, bin_op, b) {
+                        (Entry::Usize(a), op, Entry::Usize(b)) => {
+                            numeric::binary(&Value::Usize(a), op, &Value::Usize(b))
+                        }
 
+                        (e1, op, e2) => {
+                            let a = self.entry_to_value_ref(e1)?;
+                            let b = self.entry_to_value_ref(e2)?;
+                            let result = match (&*a.borrow(), op, &*b.borrow()) {
                                 (Value::String(a), InfixOp::Mul, Value::Usize(b)) => {
-                                    Entry::Pointer(Pointer::Heap(
-                                        self.heap.insert(Value::String(a.repeat(*b))),
-                                    ))
+                                    // Check the size up front, as repeating allocates it
+                                    let len = a.len().checked_mul(*b).ok_or(ErrorKind::Overflow);
+                                    len.and_then(|len| {
+                                        if self.heap.reserve(len).is_err() {
+                                            self.collect(&[]);
+                                        }
+                                        self.heap.reserve(len)
+                                    })
+                                    .map(|_| Value::String(a.repeat(*b)))
                                 }
 
-                                x => {
-                                    dbg!(&x);
-                                    dbg!(&self.stack);
-                                    todo!()
+                                (Value::String(a), InfixOp::Add, Value::String(b)) => {
+                                    Ok(Value::String([a.as_str(), b].concat()))
                                 }
-                            }
+
+                                (Value::String(a), op, Value::String(b)) => {
+                                    numeric::compare(a, &op, b).ok_or(ErrorKind::InvalidOperands {
+                                        op,
+                                        lhs: "string",
+                                        rhs: "string",
+                                    })
+                                }
+                                (Value::Char(a), op, Value::Char(b)) => numeric::compare(a, &op, b)
+                                    .ok_or(ErrorKind::InvalidOperands {
+                                        op,
+                                        lhs: "char",
+                                        rhs: "char",
+                                    }),
+
+                                (Value::List(a), InfixOp::Add, Value::List(b)) => {
+                                    Ok(Value::List(a.iter().chain(b).cloned().collect()))
+                                }
+
+                                // Equality is structural, lists and structs are equal if all of
+                                // their items are
+                                (lhs, InfixOp::Eq, rhs) => Ok(Value::Bool(lhs == rhs)),
+                                (lhs, InfixOp::NotEq, rhs) => Ok(Value::Bool(lhs != rhs)),
+
+                                (lhs, op, rhs) => numeric::binary(lhs, op, rhs),
+                            };
+                            result
                         }
+                    };
+                    let value = result.map_err(|kind| self.error(kind))?;
+                    self.push_value(value)?;
+                    offset = 1;
+                }
 
-                        (x, op, y) => {
-                            dbg!(&x, &op, &y);
-                            unreachable!()
+                // Applies a unary operation to the top entry on the stack.
+                Op::Unary => {
+                    let op =
+                        UnaryOp::try_from(self.next_byte()?).map_err(|kind| self.error(kind))?;
+                    let entry = self.pop()?;
+                    let value = match (op, entry) {
+                        (UnaryOp::Not, Entry::Bool(b)) => Ok(Value::Bool(!b)),
+                        (op, entry) => {
+                            let value = self.entry_to_value(entry)?;
+                            numeric::unary(op, &value)
                         }
                     };
-                    self.stack.push(r);
+                    let value = value.map_err(|kind| self.error(kind))?;
+                    self.push_value(value)?;
+                    offset = 1;
+                }
+
+                Op::Cast => {
+                    let to =
+                        Numeric::try_from(self.next_byte()?).map_err(|kind| self.error(kind))?;
+                    let entry = self.pop()?;
+                    let value = self.entry_to_value(entry)?;
+                    let value = numeric::cast(&value, to).map_err(|kind| self.error(kind))?;
+                    self.push_value(value)?;
                     offset = 1;
                 }
 
                 // Pops the current callframe, truncates the stack to its original size
                 // and puts the return value on top of it.
                 Op::Return => {
-                    let frame = self.frames.pop().unwrap();
+                    let frame = *self.frame();
 
-                    self.close_upvalues(frame);
+                    self.close_upvalues(frame)?;
 
-                    let result = self.stack.pop().unwrap();
+                    let result = self.pop()?;
 
                     self.stack.truncate(frame.stack_start);
 
-                    let ret = &self.stack.pop().unwrap();
-                    match ret {
-                        Entry::Usize(addr) => {
-                            self.frame_mut().ip = *addr;
+                    let ret = self.pop()?;
+                    self.frames.pop();
+                    match (ret, self.frames.last_mut()) {
+                        (Entry::Usize(addr), Some(caller)) => {
+                            caller.ip = addr;
                         }
-                        x => {
-                            dbg!(&self.entry_to_value(*x));
-                            unreachable!()
+                        _ => {
+                            return Err(Error::new(frame.ip, ErrorKind::InvalidReturnAddress));
                         }
                     }
 
@@ -735,57 +1255,145 @@ impl Vm {
                 }
 
                 Op::Call => {
-                    let entry = self.stack.pop().unwrap();
+                    let entry = self.pop()?;
                     match entry {
                         Entry::Pointer(Pointer::Builtin(p)) => {
-                            self.builtins[p].0.clone()(self); // TODO get this non-cloneable
+                            let builtin = self
+                                .builtins
+                                .get(p)
+                                .ok_or_else(|| self.error(ErrorKind::InvalidFunction(p)))?
+                                .0
+                                .clone(); // TODO get this non-cloneable
+                            builtin(self)?;
+
+                            // Like `Op::Return`, replace the return address with the result
+                            let result = self.pop()?;
+                            self.pop()?;
+                            self.stack.push(result);
                         }
                         entry => {
-                            self.push_callframe(entry);
+                            self.push_callframe(entry)?;
                             continue;
                         }
                     }
                 }
 
                 Op::Collect => {
-                    let vec_len = usize::from_ne_bytes(self.next_eight_bytes());
+                    let vec_len = self.next_operand()?;
                     let mut vec = vec![];
                     for _ in 0..vec_len {
-                        let entry = self.stack.pop().unwrap();
+                        let entry = self.pop()?;
                         match entry {
                             Entry::Pointer(Pointer::Heap(ptr)) => {
                                 vec.push(ptr);
                             }
-                            Entry::Usize(n) => {
-                                let ptr = self.heap.insert(Value::Usize(n));
-                                vec.push(ptr);
-                            }
-                            Entry::Function(crate::stack::Function {
-                                addr,
-                                arity,
-                                upvalues_refs_idx,
-                            }) => {
-                                let ptr = self.heap.insert(Value::StackFunction {
-                                    addr,
-                                    arity,
-                                    upvalues_refs_idx,
-                                });
-                                vec.push(ptr);
-                            }
-                            x => {
-                                dbg!(&x);
-                                unreachable!()
+                            entry => {
+                                let value = self.entry_to_value(entry)?;
+                                vec.push(self.alloc_in_flight(value, &vec)?);
                             }
                         }
                     }
                     vec.reverse();
-                    self.stack
-                        .push(Entry::Pointer(Pointer::Heap(self.heap.create_list(vec))));
-                    offset = 8;
+                    if !self.heap.fits_list(vec.len()) {
+                        self.collect(&vec);
+                    }
+                    let list = self
+                        .heap
+                        .create_list(vec)
+                        .map_err(|kind| self.error(kind))?;
+                    self.stack.push(Entry::Pointer(Pointer::Heap(list)));
+                    offset = OPERAND_SIZE;
                 }
 
-                x => {
-                    return Err(Value::Error(crate::value::Error::InvalidOp(x as u8)));
+                // Collects the operand's number of key and value pairs into a map. Later pairs
+                // replace earlier ones with the same key.
+                Op::CollectMap => {
+                    let len = self.next_operand()?;
+                    let mut keys = vec![];
+                    let mut values = vec![];
+                    for _ in 0..len {
+                        let value = match self.pop()? {
+                            Entry::Pointer(Pointer::Heap(ptr)) => ptr,
+                            entry => {
+                                let value = self.entry_to_value(entry)?;
+                                self.alloc_in_flight(value, &values)?
+                            }
+                        };
+                        values.push(value);
+                        let key = self.pop_value()?;
+                        keys.push(MapKey::try_from(key).map_err(|kind| self.error(kind))?);
+                    }
+                    if !self.heap.fits_map(values.len()) {
+                        self.collect(&values);
+                    }
+                    let pairs = keys.into_iter().zip(values).rev().collect();
+                    let map = self
+                        .heap
+                        .create_map(pairs)
+                        .map_err(|kind| self.error(kind))?;
+                    self.stack.push(Entry::Pointer(Pointer::Heap(map)));
+                    offset = OPERAND_SIZE;
+                }
+
+                // Pops the second operand's number of values as the payload of a variant, whose
+                // discriminant is the first operand
+                Op::Variant => {
+                    let discriminant = self.next_operand()?;
+                    let len = self.operand(1 + OPERAND_SIZE)?;
+                    let mut payload = vec![];
+                    for _ in 0..len {
+                        let ptr = match self.pop()? {
+                            Entry::Pointer(Pointer::Heap(ptr)) => ptr,
+                            entry => {
+                                let value = self.entry_to_value(entry)?;
+                                self.alloc_in_flight(value, &payload)?
+                            }
+                        };
+                        payload.push(ptr);
+                    }
+                    payload.reverse();
+                    if !self.heap.fits_variant(payload.len()) {
+                        self.collect(&payload);
+                    }
+                    let variant = self
+                        .heap
+                        .create_variant(discriminant, payload)
+                        .map_err(|kind| self.error(kind))?;
+                    self.stack.push(Entry::Pointer(Pointer::Heap(variant)));
+                    offset = 2 * OPERAND_SIZE;
+                }
+
+                // Replaces the variant on top of the stack with its discriminant
+                Op::Discriminant => {
+                    let discriminant = match self.pop()? {
+                        Entry::Pointer(Pointer::Heap(ptr)) => self.heap.discriminant(ptr),
+                        entry => Err(ErrorKind::TypeMismatch {
+                            expected: "enum",
+                            found: self.entry_to_value(entry)?.type_name(),
+                        }),
+                    };
+                    let discriminant = discriminant.map_err(|kind| self.error(kind))?;
+                    self.stack.push(Entry::Usize(discriminant));
+                }
+
+                // Pops the operand's number of values and joins their text into a single string,
+                // as built by interpolated string literals
+                Op::Concat => {
+                    let len = self.next_operand()?;
+                    let mut parts = vec![];
+                    for _ in 0..len {
+                        let value = self.pop_value()?;
+                        parts.push(value.to_text().map_err(|kind| self.error(kind))?);
+                    }
+                    parts.reverse();
+                    self.push_value(Value::String(parts.concat()))?;
+                    offset = OPERAND_SIZE;
+                }
+
+                // Unassigned bytes decode to `Op::Crash`, and some ops are not yet implemented
+                _ => {
+                    let byte = self.current_byte()?;
+                    return Err(self.error(ErrorKind::UnknownOp(byte)));
                 }
             };
 
@@ -805,12 +1413,6 @@ impl Vm {
                 .or_insert((opcode_timer_start.elapsed().as_nanos(), 1));
         }
 
-        // When the script exits, return whatever is on the top of the stack
-        if let Some(entry) = self.stack.pop() {
-            let value = self.entry_to_value_ref(entry);
-            Ok((*value).clone().borrow().to_owned())
-        } else {
-            Ok(Value::Void)
-        }
+        Ok(())
     }
 }
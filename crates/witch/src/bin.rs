@@ -1,23 +1,324 @@
 use std::env;
 
+#[cfg(feature = "compiler")]
 use std::path::PathBuf;
 use std::process;
 
-pub use witch_compiler::compile;
+#[cfg(feature = "compiler")]
+use miette::{Diagnostic, GraphicalReportHandler, GraphicalTheme, LabeledSpan, SourceCode};
+#[cfg(feature = "compiler")]
+use std::fmt::{self, Display};
+#[cfg(feature = "compiler")]
+use std::io::{self, BufRead, IsTerminal, Write};
+#[cfg(feature = "compiler")]
+use witch::repl::{Outcome, Repl};
+#[cfg(feature = "compiler")]
+use witch_runtime::value::Value;
+
+#[cfg(feature = "compiler")]
+pub use witch_compiler::compile_with_source_map;
 pub use witch_runtime::vm::Vm;
+use witch_runtime::{disasm::disassemble, image::Image, source_map::SourceMap};
+
+/// The program ran (or compiled) successfully
+const EXIT_OK: i32 = 0;
+
+/// The program failed to compile, or failed at runtime
+const EXIT_FAILURE: i32 = 1;
+
+/// The command line arguments were invalid
+const EXIT_USAGE: i32 = 2;
+
+/// Files could not be read or written
+const EXIT_IO: i32 = 3;
+
+const USAGE: &str = "Usage: witch <command> [args]
+
+Commands:
+    run <file>                 Compile and run a Witch source file
+    build <file> [-o <output>] Compile a Witch source file to bytecode
+    exec <file>                Run precompiled bytecode
+    check <file>               Parse and type check a Witch source file without running it
+    repl                       Start an interactive session
+    disasm <file>              Print the instructions of a Witch source file or of precompiled bytecode
+    help                       Print this message
+
+Running `witch <file>` is short for `witch run <file>`.";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("Please provide a file path");
-        process::exit(1);
-    }
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
 
-    run(&args[1]);
+    let code = match args.as_slice() {
+        ["run", file] => run(file),
+        ["build", file] => build(file, None),
+        ["build", file, "-o" | "--output", output] | ["build", "-o" | "--output", output, file] => {
+            build(file, Some(output))
+        }
+        ["exec", file] => exec(file),
+        ["check", file] => check(file),
+        ["repl"] => repl(),
+        ["disasm", file] => disasm(file),
+        ["help" | "-h" | "--help"] => {
+            println!("{}", USAGE);
+            EXIT_OK
+        }
+        [file] if !file.starts_with('-') && !is_command(file) => run(file),
+        _ => {
+            eprintln!("{}", USAGE);
+            EXIT_USAGE
+        }
+    };
+
+    process::exit(code);
+}
+
+fn is_command(arg: &str) -> bool {
+    matches!(
+        arg,
+        "run" | "build" | "exec" | "check" | "repl" | "disasm" | "help"
+    )
 }
 
-fn run(file_path: &str) {
-    let bytecode = compile(PathBuf::from(file_path)).unwrap();
+#[cfg(feature = "compiler")]
+fn run(file_path: &str) -> i32 {
+    let (bytecode, source_map) = match compile_with_source_map(PathBuf::from(file_path)) {
+        Ok(result) => result,
+        Err(err) => return report(err),
+    };
     let mut vm = Vm::new();
-    let result = vm.run(bytecode).unwrap();
+    vm.set_source_map(source_map);
+    execute(&mut vm, bytecode)
+}
+
+#[cfg(feature = "compiler")]
+fn build(file_path: &str, output: Option<&str>) -> i32 {
+    let bytecode = match witch_compiler::compile(PathBuf::from(file_path)) {
+        Ok(bytecode) => bytecode,
+        Err(err) => return report(err),
+    };
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(file_path).with_extension("wbc"));
+    if let Err(err) = std::fs::write(&output, bytecode) {
+        eprintln!("Error: failed to write {}: {}", output.display(), err);
+        return EXIT_IO;
+    }
+    EXIT_OK
+}
+
+#[cfg(feature = "compiler")]
+fn check(file_path: &str) -> i32 {
+    match witch_compiler::check(PathBuf::from(file_path)) {
+        Ok(_) => EXIT_OK,
+        Err(err) => report(err),
+    }
+}
+
+/// Reads inputs from stdin until it is closed, printing the value of each one.
+/// Lines are collected until they form a complete input, so blocks may span several lines.
+#[cfg(feature = "compiler")]
+fn repl() -> i32 {
+    let mut repl = match Repl::new() {
+        Ok(repl) => repl,
+        Err(err) => return report(err),
+    };
+
+    let stdin = io::stdin();
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { "... " });
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => return EXIT_OK,
+            Ok(_) => input.push_str(&line),
+            Err(err) => {
+                eprintln!("Error: failed to read from stdin: {}", err);
+                return EXIT_IO;
+            }
+        }
+
+        match repl.eval(&input) {
+            Ok(Outcome::Incomplete) => continue,
+            Ok(Outcome::Value(Value::Void)) => {}
+            // Values without a text of their own, such as lists, are printed as they are held
+            Ok(Outcome::Value(value)) => match value.to_text() {
+                Ok(text) => println!("{}", text),
+                Err(_) => println!("{:?}", value),
+            },
+            Err(err) if err.is::<witch_runtime::error::Error>() => eprintln!("Error: {}", err),
+            Err(err) => {
+                report(err);
+            }
+        }
+        input.clear();
+    }
+}
+
+/// Writes a compilation error to stderr, as an annotated source snippet where possible.
+/// Errors which the parser recovered from are written one after another, the same way.
+/// Source files which could not be read are reported as IO errors.
+#[cfg(feature = "compiler")]
+fn report(err: anyhow::Error) -> i32 {
+    match witch_compiler::diagnostic(&err) {
+        Some(diagnostic) => {
+            let handler = GraphicalReportHandler::new_themed(theme());
+            let related = diagnostic.related().into_iter().flatten();
+            for diagnostic in std::iter::once(diagnostic).chain(related) {
+                let mut out = String::new();
+                let _ = handler.render_report(&mut out, &Unrelated(diagnostic));
+                eprintln!("{}", out);
+            }
+        }
+        // Alternate formatting includes the causes, such as the reason a file could not be read
+        None => eprintln!("Error: {:#}", err),
+    }
+    if err.root_cause().is::<std::io::Error>() {
+        return EXIT_IO;
+    }
+    EXIT_FAILURE
+}
+
+/// Colours diagnostics only when they are written to a terminal, unless `NO_COLOR` is set.
+#[cfg(feature = "compiler")]
+fn theme() -> GraphicalTheme {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| value != "0");
+    if io::stderr().is_terminal() && !no_color {
+        GraphicalTheme::unicode()
+    } else {
+        GraphicalTheme::none()
+    }
+}
+
+/// A diagnostic without its related diagnostics, so that each of them gets reported on its own.
+#[cfg(feature = "compiler")]
+#[derive(Debug)]
+struct Unrelated<'a>(&'a dyn Diagnostic);
+
+#[cfg(feature = "compiler")]
+impl Display for Unrelated<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(feature = "compiler")]
+impl std::error::Error for Unrelated<'_> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(feature = "compiler")]
+impl Diagnostic for Unrelated<'_> {
+    fn code<'b>(&'b self) -> Option<Box<dyn Display + 'b>> {
+        self.0.code()
+    }
+
+    fn help<'b>(&'b self) -> Option<Box<dyn Display + 'b>> {
+        self.0.help()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.0.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.0.labels()
+    }
+}
+
+#[cfg(not(feature = "compiler"))]
+fn run(_file_path: &str) -> i32 {
+    requires_compiler("run")
+}
+
+#[cfg(not(feature = "compiler"))]
+fn build(_file_path: &str, _output: Option<&str>) -> i32 {
+    requires_compiler("build")
+}
+
+#[cfg(not(feature = "compiler"))]
+fn check(_file_path: &str) -> i32 {
+    requires_compiler("check")
+}
+
+#[cfg(not(feature = "compiler"))]
+fn repl() -> i32 {
+    requires_compiler("repl")
+}
+
+#[cfg(not(feature = "compiler"))]
+fn requires_compiler(command: &str) -> i32 {
+    eprintln!(
+        "Error: `witch {}` requires witch to be built with the \"compiler\" feature. Use `witch exec` to run precompiled bytecode.",
+        command
+    );
+    EXIT_USAGE
+}
+
+fn exec(file_path: &str) -> i32 {
+    let bytecode = match std::fs::read(file_path) {
+        Ok(bytecode) => bytecode,
+        Err(err) => {
+            eprintln!("Error: failed to read {}: {}", file_path, err);
+            return EXIT_IO;
+        }
+    };
+    execute(&mut Vm::new(), bytecode)
+}
+
+/// Prints the listing of a bytecode image, or of a source file which gets compiled first.
+fn disasm(file_path: &str) -> i32 {
+    let bytecode = match std::fs::read(file_path) {
+        Ok(bytecode) => bytecode,
+        Err(err) => {
+            eprintln!("Error: failed to read {}: {}", file_path, err);
+            return EXIT_IO;
+        }
+    };
+
+    let (bytecode, source_map) = if bytecode.starts_with(&witch_runtime::image::MAGIC) {
+        (bytecode, None)
+    } else {
+        match compile_for_disasm(file_path) {
+            Ok(compiled) => compiled,
+            Err(code) => return code,
+        }
+    };
+
+    match Image::decode(&bytecode) {
+        Ok(image) => {
+            print!("{}", disassemble(&image, source_map.as_ref()));
+            EXIT_OK
+        }
+        Err(kind) => {
+            eprintln!("Error: {}", kind);
+            EXIT_FAILURE
+        }
+    }
+}
+
+#[cfg(feature = "compiler")]
+fn compile_for_disasm(file_path: &str) -> Result<(Vec<u8>, Option<SourceMap>), i32> {
+    compile_with_source_map(PathBuf::from(file_path))
+        .map(|(bytecode, source_map)| (bytecode, Some(source_map)))
+        .map_err(report)
+}
+
+#[cfg(not(feature = "compiler"))]
+fn compile_for_disasm(_file_path: &str) -> Result<(Vec<u8>, Option<SourceMap>), i32> {
+    Err(requires_compiler("disasm"))
+}
+
+fn execute(vm: &mut Vm, bytecode: Vec<u8>) -> i32 {
+    match vm.run(bytecode) {
+        Ok(_) => EXIT_OK,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            EXIT_FAILURE
+        }
+    }
 }
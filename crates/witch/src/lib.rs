@@ -6,7 +6,10 @@
 //! dependencies to the heavy lifting for a lot of things. This is why it's behind a feature flag.
 #![cfg_attr(not(feature = "compiler"), no_std)]
 
+#[cfg(feature = "compiler")]
+pub mod repl;
+
 #[cfg(feature = "compiler")]
 pub use witch_compiler::compile;
 
-pub use witch_runtime::vm::Vm;
+pub use witch_runtime::vm::{InterruptHandle, Vm};
@@ -0,0 +1,55 @@
+//! An interactive session which keeps its state between inputs.
+//!
+//! Each input is compiled against the variables, functions and types declared by the inputs
+//! before it, and its bytecode is appended to a `Vm` which is kept alive for the whole session.
+use anyhow::Result;
+use witch_compiler::Session;
+use witch_runtime::value::Value;
+
+use crate::Vm;
+
+pub struct Repl {
+    session: Session,
+    vm: Vm,
+}
+
+/// The result of evaluating an input.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// The value of the input's last expression, or `Value::Void` if there is none
+    Value(Value),
+
+    /// The input ended before it was complete, e.g. within an unclosed block.
+    /// Nothing was evaluated, the caller should read more input and try again.
+    Incomplete,
+}
+
+impl Repl {
+    pub fn new() -> Result<Self> {
+        let (session, prelude) = Session::new()?;
+        let mut vm = Vm::new();
+        vm.set_source_map(session.source_map().clone());
+        vm.eval(prelude)?;
+        Ok(Self { session, vm })
+    }
+
+    /// Compiles and runs an input. If it fails to compile, the session stays as it was. If it
+    /// fails while running, the variables and types it declared are discarded, but its functions
+    /// stay declared since its bytecode has become part of the program.
+    pub fn eval(&mut self, source: &str) -> Result<Outcome> {
+        let Some(mut snippet) = self.session.compile(source)? else {
+            return Ok(Outcome::Incomplete);
+        };
+
+        self.vm.set_source_map(self.session.source_map().clone());
+        self.vm.eval(std::mem::take(&mut snippet.image))?;
+
+        let value = if self.vm.stack.len() > snippet.locals {
+            self.vm.pop_value()?
+        } else {
+            Value::Void
+        };
+        self.session.commit(snippet);
+        Ok(Outcome::Value(value))
+    }
+}
@@ -1,3 +1,31 @@
+/// Evaluates an input in the REPL, returning the value of its last expression or the kind of
+/// error it failed with at runtime. Panics if the input does not compile.
+#[cfg(feature = "compiler")]
+fn eval(
+    repl: &mut witch::repl::Repl,
+    source: &str,
+) -> Result<witch_runtime::value::Value, witch_runtime::error::ErrorKind> {
+    use witch::repl::Outcome;
+    use witch_runtime::error::Error;
+
+    match repl.eval(source) {
+        Ok(Outcome::Value(value)) => Ok(value),
+        Ok(Outcome::Incomplete) => panic!("incomplete input: {}", source),
+        Err(err) => match err.downcast::<Error>() {
+            Ok(err) => Err(err.kind),
+            Err(err) => panic!("{}: {}", source, err),
+        },
+    }
+}
+
+/// Asserts that an input fails to compile with the diagnostic of the given code.
+#[cfg(feature = "compiler")]
+fn assert_diagnostic(repl: &mut witch::repl::Repl, source: &str, code: &str) {
+    let err = repl.eval(source).unwrap_err();
+    let report = witch_compiler::diagnostic(&err).unwrap();
+    assert_eq!(report.code().unwrap().to_string(), code, "{}", source);
+}
+
 #[cfg(feature = "compiler")]
 #[test]
 fn basic() {
@@ -126,6 +154,78 @@ fn lists() {
     assert_eq!(expected, result);
 }
 
+#[cfg(feature = "compiler")]
+#[test]
+fn list_methods() {
+    use std::path::PathBuf;
+
+    use witch::repl::Repl;
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::{Error, ErrorKind};
+    use witch_runtime::value::Value;
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/list_methods.witch")).unwrap();
+    let result = Vm::new().run(bytecode).unwrap();
+    assert_eq!(result, Value::Usize(1029 + 60 + 2));
+
+    let mut repl = Repl::new().unwrap();
+    repl.eval("let xs = [1, 2, 3]").unwrap();
+    for (source, kind) in [
+        (
+            "xs.slice(2, 4)",
+            ErrorKind::IndexOutOfBounds { index: 4, len: 3 },
+        ),
+        (
+            "xs.remove(3)",
+            ErrorKind::IndexOutOfBounds { index: 3, len: 3 },
+        ),
+        (
+            "xs[5] = 1",
+            ErrorKind::IndexOutOfBounds { index: 5, len: 3 },
+        ),
+    ] {
+        let err = repl.eval(source).unwrap_err();
+        assert_eq!(err.downcast::<Error>().unwrap().kind, kind, "{}", source);
+    }
+
+    for (source, code) in [
+        ("xs.push(\"a\")", "witch::type_mismatch"),
+        ("xs[0] = \"a\"", "witch::type_mismatch"),
+        ("xs[xs.len() - 1] = true", "witch::type_mismatch"),
+        ("xs + [\"a\"]", "witch::invalid_operands"),
+        ("xs.map((x: string) -> string: x)", "witch::type_mismatch"),
+        ("xs.shuffle()", "witch::unknown_field"),
+    ] {
+        assert_diagnostic(&mut repl, source, code);
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn method_chains() {
+    use std::path::PathBuf;
+
+    use witch::repl::{Outcome, Repl};
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::value::Value;
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/method_chains.witch")).unwrap();
+    let result = Vm::new().run(bytecode).unwrap();
+    assert_eq!(result, Value::Usize(55 + 101));
+
+    // Each method compiles the value it is called on once, so long chains compile quickly
+    let mut repl = Repl::new().unwrap();
+    let source = format!("\" a \"{}", ".trim()".repeat(20));
+    assert_eq!(
+        repl.eval(&source).unwrap(),
+        Outcome::Value(Value::String("a".to_string()))
+    );
+    let source = format!("[1]{}.len()", ".map((x: usize) -> usize: x + 1)".repeat(20));
+    assert_eq!(repl.eval(&source).unwrap(), Outcome::Value(Value::Usize(1)));
+}
+
 #[cfg(feature = "compiler")]
 #[test]
 fn types() {
@@ -141,3 +241,1295 @@ fn types() {
     let result = vm.run(bytecode).unwrap();
     assert_eq!(expected, result);
 }
+
+#[cfg(feature = "compiler")]
+#[test]
+fn loops() {
+    use std::path::PathBuf;
+
+    use witch::repl::Repl;
+    use witch::Vm;
+    use witch_compiler::{compile, diagnostic};
+    use witch_runtime::value::Value;
+
+    let expected = Value::Usize(50 + 10 + 102);
+    let bytecode = compile(PathBuf::from("tests/fixtures/loops.witch")).unwrap();
+    let mut vm = Vm::new();
+    let result = vm.run(bytecode).unwrap();
+    assert_eq!(expected, result);
+
+    let mut repl = Repl::new().unwrap();
+    for source in ["break", "continue", "if 1 < 2 { break }"] {
+        let err = repl.eval(source).unwrap_err();
+        let report = diagnostic(&err).unwrap();
+        assert_eq!(
+            report.code().unwrap().to_string(),
+            "witch::break_outside_loop"
+        );
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn numbers() {
+    use std::path::PathBuf;
+
+    use witch::repl::Repl;
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::ErrorKind;
+    use witch_runtime::value::Value;
+
+    let expected = Value::Usize(5 + 100);
+    let bytecode = compile(PathBuf::from("tests/fixtures/numbers.witch")).unwrap();
+    let mut vm = Vm::new();
+    let result = vm.run(bytecode).unwrap();
+    assert_eq!(expected, result);
+
+    let mut repl = Repl::new().unwrap();
+    assert_eq!(eval(&mut repl, "7 as i32 / 2 as i32"), Ok(Value::I32(3)));
+    assert_eq!(eval(&mut repl, "7 as f32 / 2 as f32"), Ok(Value::F32(3.5)));
+    assert_eq!(eval(&mut repl, "0.1 + 0.2 > 0.3"), Ok(Value::Bool(true)));
+    assert_eq!(
+        eval(&mut repl, "3 as i64 <= 3 as i64"),
+        Ok(Value::Bool(true))
+    );
+    assert_eq!(eval(&mut repl, "2.9 as u8"), Ok(Value::U8(2)));
+    assert_eq!(eval(&mut repl, "1 / 0"), Err(ErrorKind::DivisionByZero));
+    assert_eq!(eval(&mut repl, "0 - 1"), Err(ErrorKind::Overflow));
+    assert_eq!(
+        eval(&mut repl, "128 as i16 as i8"),
+        Err(ErrorKind::Overflow)
+    );
+    assert_eq!(
+        eval(&mut repl, "100 as u8 * 3 as u8"),
+        Err(ErrorKind::Overflow)
+    );
+    assert_eq!(
+        eval(&mut repl, "(1.0 / 0.0) as u64"),
+        Err(ErrorKind::Overflow)
+    );
+
+    // Both sides of an operation must be of the same type
+    assert!(repl.eval("1 + 1.0").is_err());
+    assert!(repl.eval("\"a\" as u8").is_err());
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn powers_and_remainders() {
+    use witch::repl::Repl;
+    use witch_runtime::error::ErrorKind;
+    use witch_runtime::value::Value;
+
+    let mut repl = Repl::new().unwrap();
+    assert_eq!(eval(&mut repl, "2 ^ 10"), Ok(Value::Usize(1024)));
+    // `^` is right associative
+    assert_eq!(eval(&mut repl, "2 ^ 3 ^ 2"), Ok(Value::Usize(512)));
+    assert_eq!(eval(&mut repl, "2 * 3 ^ 2"), Ok(Value::Usize(18)));
+    assert_eq!(
+        eval(&mut repl, "-(2 as i32) ^ (3 as i32)"),
+        Ok(Value::I32(-8))
+    );
+    assert_eq!(eval(&mut repl, "4.0 ^ 0.5"), Ok(Value::F64(2.0)));
+    assert_eq!(
+        eval(&mut repl, "2 as u8 ^ (8 as u8)"),
+        Err(ErrorKind::Overflow)
+    );
+    assert_eq!(
+        eval(&mut repl, "2 as i64 ^ -(1 as i64)"),
+        Err(ErrorKind::NegativeExponent)
+    );
+    assert_eq!(
+        eval(&mut repl, "-(1 as i64) ^ (5000000001 as i64)"),
+        Ok(Value::I64(-1))
+    );
+
+    assert_eq!(eval(&mut repl, "17 % 5"), Ok(Value::Usize(2)));
+    // The remainder takes the sign of the dividend
+    assert_eq!(
+        eval(&mut repl, "-(7 as i32) % 3 as i32"),
+        Ok(Value::I32(-1))
+    );
+    assert_eq!(eval(&mut repl, "7 as i32 % -(3 as i32)"), Ok(Value::I32(1)));
+    assert_eq!(eval(&mut repl, "5.5 % 2.0"), Ok(Value::F64(1.5)));
+    assert_eq!(eval(&mut repl, "1 % 0"), Err(ErrorKind::DivisionByZero));
+    assert_eq!(
+        eval(&mut repl, "-(128 as i16) as i8 % -(1 as i8)"),
+        Err(ErrorKind::Overflow)
+    );
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn logic() {
+    use std::path::PathBuf;
+
+    use witch::repl::Repl;
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::ErrorKind;
+    use witch_runtime::value::Value;
+
+    let expected = Value::Usize(1 + 100 + 1000);
+    let bytecode = compile(PathBuf::from("tests/fixtures/logic.witch")).unwrap();
+    let mut vm = Vm::new();
+    let result = vm.run(bytecode).unwrap();
+    assert_eq!(expected, result);
+
+    let mut repl = Repl::new().unwrap();
+    assert_eq!(eval(&mut repl, "!(1 < 2)"), Ok(Value::Bool(false)));
+    assert_eq!(eval(&mut repl, "true || 1 / 0 == 0"), Ok(Value::Bool(true)));
+    assert_eq!(eval(&mut repl, "false || 2 >= 2"), Ok(Value::Bool(true)));
+    assert_eq!(eval(&mut repl, "true == !false"), Ok(Value::Bool(true)));
+    assert_eq!(eval(&mut repl, "\"b\" >= \"abc\""), Ok(Value::Bool(true)));
+    assert_eq!(
+        eval(&mut repl, "[\"a\"] == [\"a\", \"b\"]"),
+        Ok(Value::Bool(false))
+    );
+    assert_eq!(eval(&mut repl, "-(3 as i8) * 2 as i8"), Ok(Value::I8(-6)));
+    assert_eq!(eval(&mut repl, "-1.5"), Ok(Value::F64(-1.5)));
+    assert_eq!(
+        eval(&mut repl, "-(-(127 as i8) - 1 as i8)"),
+        Err(ErrorKind::Overflow)
+    );
+
+    // Only booleans can be negated with `!`, and only signed numbers with `-`
+    assert!(repl.eval("!1").is_err());
+    assert!(repl.eval("-1").is_err());
+    assert!(repl.eval("1 && true").is_err());
+    assert!(repl.eval("\"a\" == 1").is_err());
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn matching() {
+    use std::path::PathBuf;
+
+    use witch::repl::Repl;
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::value::Value;
+
+    let expected = Value::Usize(6 + 2 + 40);
+    let bytecode = compile(PathBuf::from("tests/fixtures/matching.witch")).unwrap();
+    let mut vm = Vm::new();
+    let result = vm.run(bytecode).unwrap();
+    assert_eq!(expected, result);
+
+    let mut repl = Repl::new().unwrap();
+    eval(&mut repl, "let x = 5").unwrap();
+    assert_eq!(
+        eval(&mut repl, "match x { 1 -> \"a\", _ -> \"b\" }"),
+        Ok(Value::String("b".into()))
+    );
+    // Bindings shadow variables of the same name within their arm
+    assert_eq!(
+        eval(&mut repl, "let y = match 7 { x -> x + 1 }; y"),
+        Ok(Value::Usize(8))
+    );
+    assert_eq!(eval(&mut repl, "x"), Ok(Value::Usize(5)));
+
+    // A match can be used amid other values, such as operands, arguments and list items
+    assert_eq!(
+        eval(&mut repl, "1 + match x { 5 -> 10, _ -> 0 }"),
+        Ok(Value::Usize(11))
+    );
+    eval(
+        &mut repl,
+        "function double(n: usize) -> usize { return n * 2 }",
+    )
+    .unwrap();
+    assert_eq!(
+        eval(&mut repl, "double(match x { 5 -> 3, _ -> 0 })"),
+        Ok(Value::Usize(6))
+    );
+    assert_eq!(
+        eval(
+            &mut repl,
+            "let z = 2 * match x { n if n > 1 -> n, _ -> 0 } + 1; z"
+        ),
+        Ok(Value::Usize(11))
+    );
+    assert_eq!(
+        eval(&mut repl, "[x, match x { n -> n + 1 }, x].len()"),
+        Ok(Value::Usize(3))
+    );
+    eval(&mut repl, "function g(a: usize) -> usize { let b = 1; return b + match a { 0 -> 1, n -> n * b * 2 } + b }").unwrap();
+    assert_eq!(eval(&mut repl, "g(0) + g(4)"), Ok(Value::Usize(3 + 10)));
+
+    let mut repl = Repl::new().unwrap();
+    repl.eval("enum Color { Red, Green, Blue(usize) }").unwrap();
+    for (source, code) in [
+        ("match 1 { 1 -> 2 }", "witch::non_exhaustive_match"),
+        ("match true { true -> 1 }", "witch::non_exhaustive_match"),
+        (
+            "function f(c: Color) -> usize { return match c { Red -> 1, Blue(0) -> 2 } }",
+            "witch::non_exhaustive_match",
+        ),
+        (
+            "function f(c: Color) -> usize { return match c { Purple(n) -> n, _ -> 2 } }",
+            "witch::invalid_pattern",
+        ),
+        (
+            "function f(c: Color) -> usize { return match c { Blue -> 1, _ -> 2 } }",
+            "witch::invalid_pattern",
+        ),
+        ("match 1 { 1 -> \"a\", _ -> 2 }", "witch::type_mismatch"),
+        ("match 1 { \"a\" -> 1, _ -> 2 }", "witch::type_mismatch"),
+        ("match 1 { x if x -> 1, _ -> 2 }", "witch::type_mismatch"),
+    ] {
+        assert_diagnostic(&mut repl, source, code);
+    }
+    repl.eval(
+        "function f(c: Color) -> usize { return match c { Red -> 1, Green -> 2, Blue(n) -> n } }",
+    )
+    .unwrap();
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn enums() {
+    use std::convert::TryFrom;
+    use std::path::PathBuf;
+
+    use witch::repl::Repl;
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::value::{Value, Variant};
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/enums.witch")).unwrap();
+    let mut vm = Vm::new();
+    let result = vm.run(bytecode).unwrap();
+    assert_eq!(
+        Variant::try_from(result).unwrap(),
+        Variant::new(2, vec![Value::F64(2.0), Value::F64(3.0)])
+    );
+    assert_eq!(
+        Value::from(Variant::new(0, vec![])),
+        Value::Variant(Variant::new(0, vec![]))
+    );
+    assert!(Variant::try_from(Value::Usize(0)).is_err());
+    assert!(Variant::try_from(Value::List(vec![Value::Usize(0)])).is_err());
+
+    let mut repl = Repl::new().unwrap();
+    repl.eval("enum Color { Red, Green, Custom(u8, u8, u8) }")
+        .unwrap();
+    for (source, code) in [
+        ("Color.Purple", "witch::unknown_variant"),
+        ("Color.Custom", "witch::wrong_arity"),
+        ("Color.Red(1)", "witch::wrong_arity"),
+        ("Color.Custom(1, 2, 3)", "witch::type_mismatch"),
+        ("Color.Red == 1", "witch::invalid_operands"),
+    ] {
+        assert_diagnostic(&mut repl, source, code);
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn options() {
+    use std::path::PathBuf;
+
+    use witch::repl::{Outcome, Repl};
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::value::Value;
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/options.witch")).unwrap();
+    let mut vm = Vm::new();
+    let result = vm.run(bytecode).unwrap();
+    assert_eq!(result, Value::Usize(4 + 100 + 7));
+
+    let mut repl = Repl::new().unwrap();
+    assert_eq!(
+        repl.eval("Option.Some(2).map((x: usize) -> usize: x * 3).unwrap_or(0)")
+            .unwrap(),
+        Outcome::Value(Value::Usize(6))
+    );
+    for (source, code) in [
+        ("Option.Some(2).unwrap_or(\"a\")", "witch::type_mismatch"),
+        (
+            "Option.Some(2).map((x: usize) -> string: \"a\").unwrap_or(1)",
+            "witch::type_mismatch",
+        ),
+        ("Result.Ok(1).unwrap()", "witch::unknown_field"),
+    ] {
+        assert_diagnostic(&mut repl, source, code);
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn try_operator() {
+    use std::path::PathBuf;
+
+    use witch::repl::Repl;
+    use witch::Vm;
+    use witch_compiler::{compile, diagnostic};
+    use witch_runtime::value::{Value, Variant};
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/try.witch")).unwrap();
+    let mut vm = Vm::new();
+    let result = vm.run(bytecode).unwrap();
+    assert_eq!(result, Value::Usize(8 + 100));
+
+    // Builtins which fail hand back an `Err` rather than panicking
+    assert_eq!(
+        Value::from(Err::<usize, String>("invalid".to_string())),
+        Value::from(Variant::new(1, vec![Value::String("invalid".to_string())]))
+    );
+
+    let mut repl = Repl::new().unwrap();
+    repl.eval("let r: Result[usize, string] = Result.Ok(1)")
+        .unwrap();
+    for (source, code) in [
+        ("r?", "witch::invalid_try"),
+        (
+            "function f() -> usize { return r? }",
+            "witch::type_mismatch",
+        ),
+        (
+            "function f() -> Result[usize, bool] { return Result.Ok(r?) }",
+            "witch::type_mismatch",
+        ),
+        (
+            "function f() -> Result[usize, string] { return Result.Ok(1?) }",
+            "witch::type_mismatch",
+        ),
+    ] {
+        assert_diagnostic(&mut repl, source, code);
+    }
+
+    // A mismatched error points at both the `?` and the return type of the function
+    let source = "function f() -> Result[usize, usize] { return Result.Ok(r?) }";
+    let err = repl.eval(source).unwrap_err();
+    let report = diagnostic(&err).unwrap();
+    assert_eq!(
+        report.to_string(),
+        "`?` cannot convert `Result[usize, string]` into the function's return type `Result[usize, usize]`"
+    );
+    let spans: Vec<_> = report
+        .labels()
+        .unwrap()
+        .map(|label| label.offset()..label.offset() + label.len())
+        .collect();
+    assert_eq!(spans, vec![56..58, 16..36]);
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn maps() {
+    use std::convert::TryFrom;
+    use std::path::PathBuf;
+
+    use hashbrown::HashMap;
+    use witch::repl::{Outcome, Repl};
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::{Error, ErrorKind};
+    use witch_runtime::value::{MapKey, Value};
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/maps.witch")).unwrap();
+    let mut vm = Vm::new();
+    let result = vm.run(bytecode).unwrap();
+    assert_eq!(result, Value::Usize(37 + 85 + 2 + 1 + 41 + 1000));
+
+    // Maps round-trip to the HashMaps of the host
+    let mut repl = Repl::new().unwrap();
+    let Outcome::Value(map) = repl.eval("{ \"a\": 1, \"b\": 2 }").unwrap() else {
+        panic!("expected a value");
+    };
+    let expected = HashMap::from([("a".to_string(), 1_usize), ("b".to_string(), 2)]);
+    assert_eq!(
+        HashMap::<String, usize>::try_from(map.clone()),
+        Ok(expected.clone())
+    );
+    assert_eq!(Value::from(expected), map);
+
+    // Only values which are compared by their contents can be keys
+    assert_eq!(MapKey::try_from(Value::Usize(1)), Ok(MapKey::Usize(1)));
+    assert!(MapKey::try_from(Value::F64(0.0)).is_err());
+    assert!(MapKey::try_from(Value::List(vec![Value::F32(f32::NAN)])).is_err());
+
+    let err = repl.eval("{ \"a\": 1 }[\"b\"]").unwrap_err();
+    assert_eq!(err.downcast::<Error>().unwrap().kind, ErrorKind::MissingKey);
+
+    repl.eval("enum Num { Int(usize), Float(f64) }").unwrap();
+    for (source, code) in [
+        ("{ \"a\": 1, \"b\": true }", "witch::type_mismatch"),
+        ("{ 1.5: 1 }", "witch::invalid_key"),
+        ("{ Num.Int(1): 1 }", "witch::invalid_key"),
+        ("{ \"a\": 1 }[1]", "witch::type_mismatch"),
+        ("{ \"a\": 1 }.push(1)", "witch::unknown_field"),
+        ("{ \"a\": 1 }.insert(\"b\", \"c\")", "witch::type_mismatch"),
+    ] {
+        assert_diagnostic(&mut repl, source, code);
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn strings() {
+    use std::path::PathBuf;
+
+    use witch::repl::{Outcome, Repl};
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::{Error, ErrorKind};
+    use witch_runtime::value::Value;
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/strings.witch")).unwrap();
+    let result = Vm::new().run(bytecode).unwrap();
+    assert_eq!(result, Value::Usize(1213 + 462 + 1000));
+
+    let mut repl = Repl::new().unwrap();
+    assert_eq!(
+        repl.eval("\"ab\" + \"c\" == \"abc\"").unwrap(),
+        Outcome::Value(Value::Bool(true))
+    );
+    assert_eq!(
+        repl.eval("(2 as i8 - 5 as i8).to_string()").unwrap(),
+        Outcome::Value(Value::String("-3".to_string()))
+    );
+
+    let err = repl.eval("\"ab\".slice(1, 3)").unwrap_err();
+    assert_eq!(
+        err.downcast::<Error>().unwrap().kind,
+        ErrorKind::IndexOutOfBounds { index: 3, len: 2 }
+    );
+
+    for (source, code) in [
+        ("\"a\" + 1", "witch::invalid_operands"),
+        ("\"a\".contains(1)", "witch::type_mismatch"),
+        ("\"a\".reverse()", "witch::unknown_field"),
+        ("let x: usize = \"1\".parse_usize()", "witch::type_mismatch"),
+    ] {
+        assert_diagnostic(&mut repl, source, code);
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn string_literals() {
+    use std::ffi::CString;
+
+    use witch::repl::{Outcome, Repl};
+    use witch_compiler::diagnostic;
+    use witch_runtime::value::Value;
+
+    let mut repl = Repl::new().unwrap();
+    repl.eval("let n = 2").unwrap();
+    for (source, expected) in [
+        (r#""a\tb\n\"c\" \\""#, "a\tb\n\"c\" \\"),
+        (r#""\x41\u{42}\u{1F600}""#, "AB😀"),
+        (r#""\{n} {n}\u{7D}""#, "{n} 2}"),
+        (r#"r"C:\dir\{n}""#, r"C:\dir\{n}"),
+        (r##"r#"say "hi""#"##, r#"say "hi""#),
+    ] {
+        assert_eq!(
+            repl.eval(source).unwrap(),
+            Outcome::Value(Value::String(expected.to_string())),
+            "{}",
+            source
+        );
+    }
+    assert_eq!(
+        repl.eval(r#"c"\xFF\n""#).unwrap(),
+        Outcome::Value(Value::CString(CString::new([0xFF, b'\n']).unwrap()))
+    );
+
+    // Errors point at the invalid escape sequence
+    for (source, span) in [
+        (r#""ab \q""#, 4..6),
+        (r#""{n} \u{D800}""#, 5..13),
+        (r#""\x4g""#, 1..4),
+        (r#""\xFF""#, 1..5),
+        (r#"c"a\0""#, 3..5),
+    ] {
+        let err = repl.eval(source).unwrap_err();
+        let report = diagnostic(&err).unwrap();
+        assert_eq!(report.code().unwrap().to_string(), "witch::parse");
+        let label = report.labels().unwrap().next().unwrap();
+        assert_eq!(
+            label.offset()..label.offset() + label.len(),
+            span,
+            "{}",
+            source
+        );
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn interpolation() {
+    use std::path::PathBuf;
+
+    use witch::repl::{Outcome, Repl};
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::value::Value;
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/interpolation.witch")).unwrap();
+    let result = Vm::new().run(bytecode).unwrap();
+    assert_eq!(result, Value::Usize(1920));
+
+    let mut repl = Repl::new().unwrap();
+    repl.eval("let name = \"Ada\"").unwrap();
+    assert_eq!(
+        repl.eval("\"hi {name}, {name.len() - 1} {true}\"").unwrap(),
+        Outcome::Value(Value::String("hi Ada, 2 true".to_string()))
+    );
+
+    // Escaped and doubled braces are literal
+    for (source, text) in [
+        ("\"{{name}} is {name}\"", "{name} is Ada"),
+        ("\"json: {{}}\"", "json: {}"),
+        ("\"a \\{ b \\} c }\"", "a { b } c }"),
+        ("\"{ {\"k\": name}[\"k\"] }\"", "Ada"),
+    ] {
+        assert_eq!(
+            repl.eval(source).unwrap(),
+            Outcome::Value(Value::String(text.to_string())),
+            "{}",
+            source
+        );
+    }
+
+    for (source, code) in [
+        ("\"{[1, 2]}\"", "witch::type_mismatch"),
+        ("\"{name\"", "witch::parse"),
+        ("\"{}\"", "witch::parse"),
+        ("\"a { b\"", "witch::parse"),
+        ("\"{name name}\"", "witch::parse"),
+    ] {
+        assert_diagnostic(&mut repl, source, code);
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn runtime_errors() {
+    use std::path::PathBuf;
+
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::ErrorKind;
+    use witch_runtime::image::Image;
+    use witch_runtime::value::Value;
+    use witch_runtime::vm::Op;
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/runtime_error.witch")).unwrap();
+    let mut vm = Vm::new();
+    let err = vm.run(bytecode).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::DivisionByZero);
+
+    // Malformed bytecode surfaces as errors rather than panics
+    let image = |code: Vec<u8>| {
+        Image {
+            code,
+            ..Default::default()
+        }
+        .encode()
+        .unwrap()
+    };
+
+    let mut vm = Vm::new();
+    let err = vm.run(image(vec![Op::Pop as u8])).unwrap_err();
+    assert_eq!((err.ip, err.kind), (0, ErrorKind::StackUnderflow));
+
+    let mut vm = Vm::new();
+    let err = vm.run(image(vec![Op::Push as u8, 0xff])).unwrap_err();
+    assert_eq!((err.ip, err.kind), (0, ErrorKind::UnexpectedEnd));
+
+    let mut vm = Vm::new();
+    let err = vm.run(image(vec![Op::Push as u8, 0, 0, 0, 0])).unwrap_err();
+    assert_eq!((err.ip, err.kind), (0, ErrorKind::InvalidConstant));
+
+    let mut vm = Vm::new();
+    let err = vm.run(image(vec![255])).unwrap_err();
+    assert_eq!((err.ip, err.kind), (0, ErrorKind::UnknownOp(255)));
+
+    // Only bools decide whether to jump
+    let mut vm = Vm::new();
+    let bytecode = Image {
+        constants: vec![Value::Usize(1)],
+        code: vec![
+            Op::Push as u8,
+            0,
+            0,
+            0,
+            0,
+            Op::JumpIfFalse as u8,
+            0,
+            0,
+            0,
+            0,
+        ],
+        ..Default::default()
+    };
+    let err = vm.run(bytecode.encode().unwrap()).unwrap_err();
+    let mismatch = ErrorKind::TypeMismatch {
+        expected: "bool",
+        found: "usize",
+    };
+    assert_eq!((err.ip, err.kind), (5, mismatch));
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn bytecode_image() {
+    use std::path::PathBuf;
+
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::ErrorKind;
+    use witch_runtime::image::{Image, MAGIC, VERSION};
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/fib.witch")).unwrap();
+    assert_eq!(bytecode[..4], MAGIC);
+    assert_eq!(bytecode[4..6], VERSION.to_le_bytes());
+
+    let image = Image::decode(&bytecode).unwrap();
+    assert!(!image.constants.is_empty());
+    assert!(image.functions.iter().any(|f| f.arity == 1));
+    assert_eq!(image.encode().unwrap(), bytecode);
+
+    let run = |bytecode: Vec<u8>| Vm::new().run(bytecode).map_err(|err| err.kind);
+
+    let mut corrupted = bytecode.clone();
+    let last_code_byte = corrupted.len() - 5;
+    corrupted[last_code_byte] ^= 0xff;
+    assert_eq!(run(corrupted), Err(ErrorKind::ChecksumMismatch));
+
+    let mut future = bytecode.clone();
+    future[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert_eq!(run(future), Err(ErrorKind::UnsupportedVersion(VERSION + 1)));
+
+    let truncated = bytecode[..bytecode.len() - 1].to_vec();
+    assert_eq!(run(truncated), Err(ErrorKind::ChecksumMismatch));
+    assert_eq!(run(bytecode[..7].to_vec()), Err(ErrorKind::MalformedImage));
+    assert_eq!(run(image.code), Err(ErrorKind::NotAnImage));
+    assert_eq!(run(vec![]), Err(ErrorKind::NotAnImage));
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn verifier() {
+    use std::path::PathBuf;
+
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::ErrorKind;
+    use witch_runtime::image::{FunctionEntry, Image};
+    use witch_runtime::value::{Function, Value};
+    use witch_runtime::verify::verify;
+    use witch_runtime::vm::Op;
+
+    for fixture in [
+        "basic",
+        "builtins",
+        "closures",
+        "enums",
+        "fib",
+        "garbage",
+        "interpolation",
+        "lambda",
+        "list_methods",
+        "lists",
+        "logic",
+        "loops",
+        "maps",
+        "matching",
+        "method_chains",
+        "module",
+        "numbers",
+        "options",
+        "strings",
+        "try",
+    ] {
+        let bytecode = compile(PathBuf::from(format!("tests/fixtures/{}.witch", fixture))).unwrap();
+        verify(&Image::decode(&bytecode).unwrap()).unwrap();
+    }
+
+    let check = |image: Image| {
+        let err = verify(&image).unwrap_err();
+        // The VM refuses to run what the verifier rejects
+        let run = Vm::new().run(image.encode().unwrap()).unwrap_err();
+        assert_eq!(run, err);
+        (err.ip, err.kind)
+    };
+    let code = |code: Vec<u8>| Image {
+        code,
+        constants: vec![Value::Bool(true)],
+        ..Default::default()
+    };
+
+    // A jump into the middle of an instruction
+    let jump = code(vec![Op::Jump as u8, 1, 0, 0, 0]);
+    assert_eq!(check(jump), (0, ErrorKind::InvalidJumpTarget(2)));
+
+    let jump_back = code(vec![Op::Debug as u8, Op::JumpBack as u8, 9, 0, 0, 0]);
+    assert_eq!(check(jump_back), (1, ErrorKind::JumpBeforeStart(9)));
+
+    let get = code(vec![Op::Get as u8, 5]);
+    assert_eq!(check(get), (0, ErrorKind::InvalidSlot(5)));
+
+    // The stack is one entry deeper when the conditional branch is not taken
+    let branch = code(vec![
+        Op::Push as u8,
+        0,
+        0,
+        0,
+        0,
+        Op::JumpIfFalse as u8,
+        5,
+        0,
+        0,
+        0,
+        Op::Push as u8,
+        0,
+        0,
+        0,
+        0,
+        Op::Debug as u8,
+    ]);
+    let inconsistent = ErrorKind::InconsistentStack {
+        expected: 1,
+        found: 0,
+    };
+    assert_eq!(check(branch), (15, inconsistent));
+
+    let ret = code(vec![Op::Return as u8]);
+    assert_eq!(check(ret), (0, ErrorKind::InvalidReturnAddress));
+
+    // A function body of arity 1 which pops its argument and falls off the end
+    let mut function = Function::new();
+    function.arity = 1;
+    let fallthrough = Image {
+        constants: vec![Value::Function(function)],
+        functions: vec![FunctionEntry {
+            addr: 9,
+            len: 1,
+            arity: 1,
+        }],
+        code: vec![
+            Op::Push as u8,
+            0,
+            0,
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+            Op::Pop as u8,
+            Op::Pop as u8,
+        ],
+    };
+    assert_eq!(check(fallthrough.clone()), (10, ErrorKind::MissingReturn));
+
+    // The function table has to match the functions in the code
+    let mut unlisted = Image {
+        functions: vec![],
+        ..fallthrough
+    };
+    unlisted.code[9] = Op::Return as u8;
+    assert_eq!(check(unlisted), (9, ErrorKind::MalformedImage));
+
+    // Appended code is verified against the stack left behind by the code before it
+    let mut vm = Vm::new();
+    vm.eval(code(vec![Op::Push as u8, 0, 0, 0, 0])).unwrap();
+    let get = |slot| Image {
+        code: vec![Op::Get as u8, slot],
+        ..Default::default()
+    };
+    vm.eval(get(0)).unwrap();
+    let err = vm.eval(get(5)).unwrap_err();
+    assert_eq!((err.ip, err.kind), (7, ErrorKind::InvalidSlot(5)));
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn fuel() {
+    use std::path::PathBuf;
+
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::ErrorKind;
+
+    let bytecode = compile(PathBuf::from("tests/fixtures/fib.witch")).unwrap();
+    let expected = Vm::new().run(bytecode.clone()).unwrap();
+
+    // Time-slice the program, topping up its fuel whenever it runs out
+    let mut vm = Vm::new();
+    vm.set_fuel(Some(100));
+    let mut result = vm.run(bytecode.clone());
+    let mut slices = 1;
+    while let Err(err) = &result {
+        assert_eq!(err.kind, ErrorKind::OutOfFuel);
+        assert_eq!(vm.fuel(), Some(0));
+        vm.set_fuel(Some(100));
+        result = vm.resume();
+        slices += 1;
+    }
+    assert_eq!(result.unwrap(), expected);
+    assert!(slices > 1);
+
+    // Interrupts may come from another thread, and take effect at the next instruction
+    let mut vm = Vm::new();
+    let handle = vm.interrupt_handle();
+    std::thread::spawn(move || handle.interrupt())
+        .join()
+        .unwrap();
+    let err = vm.run(bytecode.clone()).unwrap_err();
+    assert_eq!((err.ip, err.kind), (0, ErrorKind::Interrupted));
+    assert_eq!(vm.resume().unwrap(), expected);
+
+    // A suspended run is abandoned by starting another one
+    let mut vm = Vm::new();
+    vm.set_fuel(Some(10));
+    assert!(vm.run(bytecode.clone()).unwrap_err().kind.is_resumable());
+    vm.set_fuel(None);
+    assert_eq!(vm.run(bytecode).unwrap(), expected);
+    assert_eq!(vm.resume().unwrap(), witch_runtime::value::Value::Void);
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn memory_limits() {
+    use std::path::PathBuf;
+
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::error::ErrorKind;
+    use witch_runtime::image::Image;
+    use witch_runtime::limits::{Limits, Resource};
+    use witch_runtime::value::Value;
+    use witch_runtime::vm::{InfixOp, Op};
+
+    let run = |fixture: &str, limits: Limits| {
+        let bytecode = compile(PathBuf::from(format!("tests/fixtures/{}.witch", fixture))).unwrap();
+        let mut vm = Vm::new();
+        vm.set_limits(limits);
+        vm.run(bytecode).map_err(|err| err.kind)
+    };
+    let exceeded = |resource| Err(ErrorKind::ResourceLimitExceeded(resource));
+
+    // fib(10) recurses ten calls deep
+    let calls = |max| Limits {
+        call_depth: Some(max),
+        ..Default::default()
+    };
+    assert_eq!(run("fib", calls(5)), exceeded(Resource::CallDepth));
+    assert_eq!(run("fib", calls(20)), Ok(Value::Usize(55)));
+
+    let stack = Limits {
+        stack_depth: Some(4),
+        ..Default::default()
+    };
+    assert_eq!(run("fib", stack), exceeded(Resource::StackDepth));
+
+    let objects = Limits {
+        heap_objects: Some(3),
+        ..Default::default()
+    };
+    assert_eq!(run("lists", objects), exceeded(Resource::HeapObjects));
+
+    // The heap is collected before it counts as full, regardless of the collection threshold
+    let objects = Limits {
+        heap_objects: Some(200),
+        ..Default::default()
+    };
+    assert_eq!(run("garbage", objects), Ok(Value::Usize(500)));
+    let bytes = Limits {
+        heap_bytes: Some(4096),
+        ..Default::default()
+    };
+    assert_eq!(run("garbage", bytes), Ok(Value::Usize(500)));
+
+    // Usage is reported for monitoring, and stays within the limits
+    let bytecode = compile(PathBuf::from("tests/fixtures/lists.witch")).unwrap();
+    let mut vm = Vm::new();
+    vm.set_limits(Limits {
+        heap_objects: Some(16),
+        ..Default::default()
+    });
+    vm.run(bytecode).unwrap();
+    let usage = vm.usage();
+    assert!(usage.heap_objects >= 5 && usage.heap_objects <= 16);
+    assert!(usage.heap_bytes > 0);
+    assert_eq!(usage.call_depth, 0);
+
+    // Huge strings are refused before they are allocated
+    let image = Image {
+        constants: vec![Value::String("ab".to_string()), Value::Usize(1 << 40)],
+        code: vec![
+            Op::Push as u8,
+            0,
+            0,
+            0,
+            0,
+            Op::Push as u8,
+            1,
+            0,
+            0,
+            0,
+            Op::Binary as u8,
+            InfixOp::Mul as u8,
+        ],
+        ..Default::default()
+    };
+    let mut vm = Vm::new();
+    vm.set_limits(Limits {
+        heap_bytes: Some(1 << 20),
+        ..Default::default()
+    });
+    let err = vm.run(image.encode().unwrap()).unwrap_err();
+    assert_eq!(
+        err.kind,
+        ErrorKind::ResourceLimitExceeded(Resource::HeapBytes)
+    );
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn garbage_collection() {
+    use std::path::PathBuf;
+
+    use witch::Vm;
+    use witch_compiler::compile;
+    use witch_runtime::value::Value;
+
+    // Collecting in between every instruction must not change what programs do
+    for fixture in [
+        "basic",
+        "closures",
+        "enums",
+        "fib",
+        "garbage",
+        "interpolation",
+        "lambda",
+        "list_methods",
+        "lists",
+        "logic",
+        "loops",
+        "maps",
+        "matching",
+        "method_chains",
+        "module",
+        "numbers",
+        "options",
+        "strings",
+        "try",
+        "types",
+    ] {
+        let bytecode = compile(PathBuf::from(format!("tests/fixtures/{}.witch", fixture))).unwrap();
+        let expected = Vm::new().run(bytecode.clone()).unwrap();
+        let mut vm = Vm::new();
+        vm.set_gc_threshold(0);
+        assert_eq!(vm.run(bytecode).unwrap(), expected, "{}", fixture);
+    }
+
+    // Once the program is done, its list is unreachable
+    let bytecode = compile(PathBuf::from("tests/fixtures/lists.witch")).unwrap();
+    let mut vm = Vm::new();
+    assert_eq!(vm.run(bytecode).unwrap(), Value::Usize(3));
+    let objects = vm.usage().heap_objects;
+    assert!(objects >= 5);
+    assert_eq!(vm.collect_garbage(), objects);
+    assert_eq!(vm.usage().heap_objects, 0);
+    assert_eq!(vm.usage().heap_bytes, 0);
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn disassembler() {
+    use std::path::PathBuf;
+
+    use witch_compiler::compile_with_source_map;
+    use witch_runtime::disasm::{decode, disassemble, Instruction};
+    use witch_runtime::image::Image;
+    use witch_runtime::value::Value;
+
+    let (bytecode, source_map) =
+        compile_with_source_map(PathBuf::from("tests/fixtures/fib.witch")).unwrap();
+    let image = Image::decode(&bytecode).unwrap();
+
+    // Every function in the function table is pushed by an instruction, and its body decodes
+    let mut pos = 0;
+    let mut bodies = vec![];
+    while pos < image.code.len() {
+        let (instruction, len) = decode(&image.code, &image.constants, pos).unwrap();
+        if let Instruction::PushFunction { body, .. } = &instruction {
+            bodies.push(body.start);
+        }
+        pos += len;
+    }
+    assert_eq!(pos, image.code.len());
+    assert!(image.functions.iter().all(|f| bodies.contains(&f.addr)));
+
+    let listing = disassemble(&image, Some(&source_map));
+    assert!(listing.contains("function, arity 1 `fib`"));
+    assert!(listing.contains("Binary Lt"));
+    assert!(listing.contains("JumpIfFalse -> "));
+    assert!(listing.contains(&format!("{:?}", Value::Usize(10))));
+
+    // Undecodable bytes end the listing rather than panicking
+    let truncated = Image {
+        code: image.code[..image.code.len() - 2].to_vec(),
+        ..image
+    };
+    assert!(disassemble(&truncated, None).ends_with("<unexpected end of bytecode>\n"));
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn stack_trace() {
+    use std::path::PathBuf;
+
+    use witch::Vm;
+    use witch_compiler::compile_with_source_map;
+    use witch_runtime::error::ErrorKind;
+
+    let (bytecode, source_map) =
+        compile_with_source_map(PathBuf::from("tests/fixtures/stack_trace.witch")).unwrap();
+    let mut vm = Vm::new();
+    vm.set_source_map(source_map);
+    let err = vm.run(bytecode).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::DivisionByZero);
+
+    let frames: Vec<_> = err
+        .backtrace
+        .iter()
+        .map(|frame| {
+            let file = frame.file.as_ref().unwrap();
+            assert!(file.ends_with("stack_trace.witch"));
+            (frame.function.as_deref(), frame.line)
+        })
+        .collect();
+    assert_eq!(
+        frames,
+        vec![(Some("divide"), 2), (Some("half_of"), 6), (None, 9)]
+    );
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn diagnostics() {
+    use std::path::PathBuf;
+
+    use witch::repl::Repl;
+    use witch_compiler::{compile, diagnostic};
+
+    let err = compile(PathBuf::from("tests/fixtures/type_error.witch")).unwrap_err();
+    let report = diagnostic(&err).unwrap();
+    assert_eq!(report.code().unwrap().to_string(), "witch::type_mismatch");
+    assert!(report.source_code().is_some());
+    let label = report.labels().unwrap().next().unwrap();
+    assert_eq!(label.offset(), 13);
+
+    let err = compile(PathBuf::from("tests/fixtures/parse_error.witch")).unwrap_err();
+    let report = diagnostic(&err).unwrap();
+    assert_eq!(report.code().unwrap().to_string(), "witch::parse");
+    let label = report.labels().unwrap().next().unwrap();
+    assert_eq!(label.offset(), 18);
+
+    // Binary operations are labelled from their left to their right operand
+    let mut repl = Repl::new().unwrap();
+    let err = repl.eval("let bbbbbb = 1 + \"xxxxxxxx\"").unwrap_err();
+    let label = diagnostic(&err).unwrap().labels().unwrap().next().unwrap();
+    assert_eq!(label.offset()..label.offset() + label.len(), 13..27);
+
+    // Unknown variables are labelled by themselves rather than the expression using them
+    let err = repl
+        .eval("libc.puts(string.from(s).unwrap_or(\"\"))")
+        .unwrap_err();
+    let report = diagnostic(&err).unwrap();
+    assert_eq!(
+        report.code().unwrap().to_string(),
+        "witch::unknown_variable"
+    );
+    let label = report.labels().unwrap().next().unwrap();
+    assert_eq!(label.offset()..label.offset() + label.len(), 0..4);
+
+    // Types are written the way they are in source
+    repl.eval("function f() -> Result[usize, string] { return Result.Ok(1) }")
+        .unwrap();
+    for (source, msg) in [
+        (
+            "let x: List[usize] = f()",
+            "mismatched types: expected `[usize]`, found `Result[usize, string]`",
+        ),
+        (
+            "let x: usize = { \"a\": [1.5] }",
+            "mismatched types: expected `usize`, found `{string: [f64]}`",
+        ),
+        (
+            "let x: usize = (a: usize) -> bool: true",
+            "mismatched types: expected `usize`, found `(usize) -> bool`",
+        ),
+    ] {
+        let err = repl.eval(source).unwrap_err();
+        assert_eq!(diagnostic(&err).unwrap().to_string(), msg);
+    }
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn cli() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let witch = env!("CARGO_BIN_EXE_witch");
+    let output = std::env::temp_dir().join("witch_cli_fib.wbc");
+
+    let status = Command::new(witch)
+        .args(["build", "tests/fixtures/fib.witch", "-o"])
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+
+    let status = Command::new(witch)
+        .arg("exec")
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+
+    let result = Command::new(witch)
+        .arg("disasm")
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&result.stdout).contains("SetupFunctionCache"));
+    let _ = std::fs::remove_file(&output);
+
+    let result = Command::new(witch)
+        .args(["check", "tests/fixtures/type_error.witch"])
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&result.stderr).contains("witch::type_mismatch"));
+
+    // Every parse error is reported the same way, without colours when not in a terminal
+    let result = Command::new(witch)
+        .args(["check", "tests/fixtures/parse_error.witch"])
+        .output()
+        .unwrap();
+    assert_eq!(result.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert_eq!(stderr.matches("witch::parse").count(), 2, "{}", stderr);
+    assert!(!stderr.contains("Error:"), "{}", stderr);
+    assert!(!stderr.contains('\x1b'), "{}", stderr);
+
+    let status = Command::new(witch)
+        .args(["check", "tests/fixtures/fib.witch"])
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+
+    // Missing files are IO errors, which are reported with their cause
+    for command in ["run", "check", "build"] {
+        let result = Command::new(witch)
+            .args([command, "tests/fixtures/missing.witch"])
+            .output()
+            .unwrap();
+        assert_eq!(result.status.code(), Some(3), "{}", command);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(
+            stderr.starts_with("Error: Failed to canonicalize path"),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("No such file or directory"), "{}", stderr);
+    }
+
+    // Imports which cannot be resolved or read are reported at the import
+    for (file, code, diagnostic) in [
+        ("unknown_import", 1, "witch::unknown_module"),
+        ("missing_import", 3, "witch::io"),
+    ] {
+        for command in ["run", "check"] {
+            let result = Command::new(witch)
+                .args([command, &format!("tests/fixtures/{}.witch", file)])
+                .output()
+                .unwrap();
+            assert_eq!(result.status.code(), Some(code), "{} {}", command, file);
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            assert!(stderr.contains(diagnostic), "{}", stderr);
+            assert!(stderr.contains("import"), "{}", stderr);
+        }
+    }
+
+    let status = Command::new(witch)
+        .args(["run", "tests/fixtures/runtime_error.witch"])
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(1));
+
+    let status = Command::new(witch)
+        .args(["frobnicate", "tests/fixtures/fib.witch"])
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(2));
+
+    // The REPL prints values the way they are written
+    let mut child = Command::new(witch)
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"1 + 2\n\"a\" + \"b\"\n")
+        .unwrap();
+    let result = child.wait_with_output().unwrap();
+    assert_eq!(result.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&result.stdout), "> 3\n> ab\n> ");
+}
+
+#[cfg(feature = "compiler")]
+#[test]
+fn repl() {
+    use witch::repl::{Outcome, Repl};
+    use witch_runtime::value::Value;
+
+    let mut repl = Repl::new().unwrap();
+    let mut eval = |source: &str| repl.eval(source).unwrap();
+
+    assert_eq!(eval("let x = 40"), Outcome::Value(Value::Void));
+    assert_eq!(eval("x + 2"), Outcome::Value(Value::Usize(42)));
+    assert_eq!(
+        eval("function add(a: usize, b: usize) -> usize {"),
+        Outcome::Incomplete
+    );
+    assert_eq!(
+        eval("function add(a: usize, b: usize) -> usize {\n    return a + b\n}"),
+        Outcome::Value(Value::Void)
+    );
+    assert_eq!(
+        eval("struct Point {\n    x: usize\n}\nlet p = new Point { x: 2 }"),
+        Outcome::Value(Value::Void)
+    );
+    assert_eq!(eval("add(x, p.x)"), Outcome::Value(Value::Usize(42)));
+
+    // Failed inputs leave the session as it was
+    assert!(repl.eval("let y = )").is_err());
+    assert!(repl.eval("let y: usize = \"hello\"").is_err());
+    assert!(repl.eval("y").is_err());
+    assert!(repl.eval("let z = 1\nx / 0").is_err());
+    assert!(repl.eval("z").is_err());
+    assert_eq!(repl.eval("x").unwrap(), Outcome::Value(Value::Usize(40)));
+    assert_eq!(
+        repl.eval("add(1, 1)").unwrap(),
+        Outcome::Value(Value::Usize(2))
+    );
+}